@@ -3,7 +3,7 @@ use crate::diagnostic_program::{
     OpCode, ParameterMode, INPUT_OUTPUT_INS_LENGTH, INSTRUCTION_LENGTH,
 };
 
-fn process_instructions(input: &mut Vec<i32>, instructions: &[i32]) -> Vec<i32> {
+pub(crate) fn process_instructions(input: &mut Vec<i32>, instructions: &[i32]) -> Vec<i32> {
     let mut processed_instructions = Vec::from(instructions);
     let mut output = Vec::new();
     let mut instruction_pointer = 0;
@@ -96,7 +96,7 @@ fn process_instructions(input: &mut Vec<i32>, instructions: &[i32]) -> Vec<i32>
     output
 }
 
-pub(crate) fn find_best_phase_setting_sequence(instructions: &[i32]) -> i32 {
+pub fn find_best_phase_setting_sequence(instructions: &[i32]) -> i32 {
     let mut highest_output = 0;
     let start = 0;
     let end = 5;
@@ -0,0 +1,555 @@
+use advent_of_code::{
+    intcode::{
+        load_image, load_program_input, write_image, write_program_text, ComputerState,
+        InstructionSet, IntcodeComputer, Observer, ProgramImage, SanitizerMode,
+    },
+    intcode_asm::{assemble_file, disassemble},
+    intcode_batch::{parse_rows, write_csv, Batch},
+    intcode_conformance::{engines, IntcodeEngine},
+    intcode_console::Console,
+    intcode_dashboard::{Control, Dashboard},
+    intcode_gdb::GdbStub,
+    intcode_minimize::{diverges, faults_with, minimize, outputs},
+    intcode_tape::{replay, Recording, Tape},
+    intcode_transpiler::transpile,
+    intcode_vectors::load_path,
+};
+use std::{
+    convert::TryFrom,
+    env, fs,
+    io::{self, BufRead, Write},
+    iter, panic, process,
+    sync::{mpsc::channel, Arc, Mutex},
+    thread,
+};
+
+const DEFAULT_PORT: u16 = 1234;
+const USAGE: &str = "usage: intcode <command> <file> [options]
+
+commands:
+  run <program>      run a program and print its output
+  replay <program>   run a program on the inputs of --tape, checking it does the same io
+  trace <program>    run a program, printing every instruction as it executes
+  console <program>  play an ascii program, typed lines are its input, ctrl-d ends
+  dashboard <program> watch the program run full screen, keys are read a line at a time
+  debug <program>    wait for gdb to connect and debug the program
+  disasm <program>   print the program as assembly
+  asm <source>       assemble a source file into a program
+  batch <program>    run the program once for every row of --rows, writing csv
+  convert <program>  turn a text program into a binary image or back, needs -o
+  transpile <program> turn a program into rust source with run and run_with_input functions
+  minimize <program> shrink the program and --input while --until keeps holding
+  test <bundle>      run a test vector bundle, or every bundle in a directory
+
+options:
+  --input VALUES     comma separated input values, can be given more than once
+  --ascii            each --input is a line of text, output is printed as text
+  --max-steps N      stop after N instructions
+  --record FILE      save every input and output of run to a tape file
+  --tape FILE        the tape replay checks the program against
+  --speed N          instructions per second for dashboard, as fast as possible by default
+  --memory ADDRESS   where the dashboard memory window starts instead of following ip
+  --port PORT        the port debug listens on, 1234 by default
+  -o, --output FILE  where asm, batch, transpile and minimize write instead of stdout
+  --relative-base N  the initial relative base convert stores in a binary image
+  --rows FILE        csv of input values for batch, a [N] header column patches cell N
+  --workers N        how many threads batch runs rows on
+  --watch ADDRESS    a cell batch reports the final value of, can be given more than once
+  --until CHECK      what minimize preserves: faults, faults=KIND, output=N or diverges=ENGINE
+  --sanitize MODE    check for suspicious behaviour, MODE is report or stop";
+
+struct Options {
+    file: String,
+    input: Vec<String>,
+    ascii: bool,
+    max_steps: Option<u64>,
+    port: u16,
+    output: Option<String>,
+    rows: Option<String>,
+    workers: Option<u64>,
+    watch: Vec<u64>,
+    until: Option<String>,
+    relative_base: Option<u64>,
+    sanitize: Option<SanitizerMode>,
+    speed: Option<u64>,
+    memory: Option<u64>,
+    record: Option<String>,
+    tape: Option<String>,
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut file = None;
+    let mut options = Options {
+        file: String::new(),
+        input: Vec::new(),
+        ascii: false,
+        max_steps: None,
+        port: DEFAULT_PORT,
+        output: None,
+        rows: None,
+        workers: None,
+        watch: Vec::new(),
+        until: None,
+        relative_base: None,
+        sanitize: None,
+        speed: None,
+        memory: None,
+        record: None,
+        tape: None,
+    };
+    let mut idx = 0;
+    while idx < args.len() {
+        let arg = args[idx].as_str();
+        let value = || {
+            args.get(idx + 1)
+                .cloned()
+                .ok_or_else(|| format!("{} needs a value", arg))
+        };
+        let number = |value: String| {
+            value
+                .parse::<u64>()
+                .map_err(|_| format!("{} needs a number, got '{}'", arg, value))
+        };
+        match arg {
+            "--input" => options.input.push(value()?),
+            "--max-steps" => options.max_steps = Some(number(value()?)?),
+            "--speed" => options.speed = Some(number(value()?)?),
+            "--memory" => options.memory = Some(number(value()?)?),
+            "--port" => {
+                let port = number(value()?)?;
+                options.port = u16::try_from(port).map_err(|_| format!("Bad port {}", port))?;
+            }
+            "-o" | "--output" => options.output = Some(value()?),
+            "--rows" => options.rows = Some(value()?),
+            "--record" => options.record = Some(value()?),
+            "--tape" => options.tape = Some(value()?),
+            "--workers" => options.workers = Some(number(value()?)?),
+            "--watch" => options.watch.push(number(value()?)?),
+            "--until" => options.until = Some(value()?),
+            "--relative-base" => options.relative_base = Some(number(value()?)?),
+            "--sanitize" => {
+                options.sanitize = Some(match value()?.as_str() {
+                    "report" => SanitizerMode::Report,
+                    "stop" => SanitizerMode::Stop,
+                    mode => return Err(format!("Unknown sanitizer mode '{}'", mode)),
+                })
+            }
+            "--ascii" => {
+                options.ascii = true;
+                idx += 1;
+                continue;
+            }
+            flag if flag.starts_with('-') => return Err(format!("Unknown option {}", flag)),
+            _ if file.is_none() => {
+                file = Some(arg.to_string());
+                idx += 1;
+                continue;
+            }
+            _ => return Err(format!("Unexpected argument '{}'", arg)),
+        }
+        idx += 2;
+    }
+    options.file = file.ok_or("Missing the file name")?;
+    Ok(options)
+}
+
+//in the order the program reads it
+fn input_values(options: &Options) -> Result<Vec<i64>, String> {
+    if options.ascii {
+        return Ok(options
+            .input
+            .iter()
+            .flat_map(|line| line.chars().chain(iter::once('\n')))
+            .map(|c| c as i64)
+            .collect());
+    }
+    options
+        .input
+        .iter()
+        .flat_map(|values| values.split(','))
+        .map(|v| {
+            v.trim()
+                .parse::<i64>()
+                .map_err(|_| format!("Bad input value '{}'", v))
+        })
+        .collect()
+}
+
+fn load(options: &Options) -> Result<(IntcodeComputer, Vec<i64>), String> {
+    let image = load_image(&options.file).map_err(|err| format!("{}: {}", options.file, err))?;
+    let mut input = input_values(options)?;
+    input.reverse();
+    let mut computer = IntcodeComputer::from_image(&image);
+    //a bad program faults and is reported instead of panicking
+    computer.restrict(InstructionSet::Day9);
+    if let Some(mode) = options.sanitize {
+        computer.sanitize(mode);
+    }
+    Ok((computer, input))
+}
+
+fn print_findings(computer: &IntcodeComputer) {
+    for finding in computer.findings() {
+        eprintln!("sanitizer: {}", finding);
+    }
+}
+
+fn print_output(output: &[i64], ascii: bool) {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for &value in output {
+        let _ = match value {
+            0..=127 if ascii => write!(stdout, "{}", value as u8 as char),
+            _ => writeln!(stdout, "{}", value),
+        };
+    }
+}
+
+//what the state the program stopped in means for the command, `steps` is how many ran
+fn outcome(computer: &IntcodeComputer, steps: u64) -> Result<(), String> {
+    match computer.state() {
+        ComputerState::Running => Err(format!("Stopped after {} steps", steps)),
+        ComputerState::Halted => Ok(()),
+        ComputerState::Waiting => Err("The program is waiting for more input".into()),
+        ComputerState::Paused => Err(format!("Paused at {}", computer.instruction_pointer())),
+        ComputerState::Faulted(fault) => Err(format!(
+            "Fault at {}: {:?}",
+            fault.instruction_pointer(),
+            fault.kind()
+        )),
+    }
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let (mut computer, mut input) = load(options)?;
+    let recording = options
+        .record
+        .as_ref()
+        .map(|_| Recording::start(&mut computer));
+    let steps = computer.run_for(&mut input, options.max_steps.unwrap_or(u64::MAX));
+    let result = outcome(&computer, steps);
+    print_output(computer.output(), options.ascii);
+    print_findings(&computer);
+    //the tape is kept even when the run failed, that is when it is most useful
+    if let (Some(file), Some(recording)) = (&options.record, recording) {
+        recording
+            .stop(&mut computer)
+            .save(file)
+            .map_err(|err| format!("{}: {}", file, err))?;
+    }
+    result
+}
+
+fn replay_tape(options: &Options) -> Result<(), String> {
+    let image = load_image(&options.file).map_err(|err| format!("{}: {}", options.file, err))?;
+    let file = options.tape.as_ref().ok_or("replay needs --tape")?;
+    let tape = Tape::load(file).map_err(|err| format!("{}: {}", file, err))?;
+    let computer = replay(&image, &tape).map_err(|err| err.to_string())?;
+    print_output(computer.output(), options.ascii);
+    eprintln!("Replayed {} events", tape.events().len());
+    Ok(())
+}
+
+//what one instruction did, collected while it executes
+#[derive(Default)]
+struct Effects {
+    effects: Vec<String>,
+}
+
+impl Observer for Effects {
+    fn memory_write(&mut self, address: u64, value: i64) {
+        self.effects.push(format!("[{}] = {}", address, value))
+    }
+
+    fn input_consumed(&mut self, value: i64) {
+        self.effects.push(format!("in {}", value))
+    }
+
+    fn output_produced(&mut self, value: i64) {
+        self.effects.push(format!("out {}", value))
+    }
+
+    fn relative_base_changed(&mut self, _old_base: u64, new_base: u64) {
+        self.effects.push(format!("rb = {}", new_base))
+    }
+}
+
+fn trace(options: &Options) -> Result<(), String> {
+    let (mut computer, mut input) = load(options)?;
+    let effects = Arc::new(Mutex::new(Effects::default()));
+    computer.attach_observer(Box::new(Arc::clone(&effects)));
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let max_steps = options.max_steps.unwrap_or(u64::MAX);
+    let mut steps = 0;
+    while steps < max_steps {
+        let address = computer.instruction_pointer();
+        let instruction = computer.instruction(address).map_or_else(
+            || format!("?? {}", computer.peek(address)),
+            |i| i.to_string(),
+        );
+        computer.step(&mut input);
+        if computer.state() == &ComputerState::Waiting {
+            break;
+        }
+        steps += 1;
+        let mut effects = effects.lock().expect("Trace lock poisoned");
+        let _ = writeln!(
+            stdout,
+            "{:>6}  {:<32} {}",
+            address,
+            instruction,
+            effects.effects.join(", ")
+        );
+        effects.effects.clear();
+        if computer.state() != &ComputerState::Running {
+            break;
+        }
+    }
+    let result = outcome(&computer, steps);
+    print_findings(&computer);
+    result
+}
+
+fn console(options: &Options) -> Result<(), String> {
+    let (mut computer, mut input) = load(options)?;
+    let stdin = io::stdin();
+    Console::new(&mut computer, &mut input)
+        .session(stdin.lock(), io::stdout())
+        .map_err(|err| err.to_string())?;
+    print_findings(&computer);
+    check_fault(&computer)
+}
+
+fn check_fault(computer: &IntcodeComputer) -> Result<(), String> {
+    match computer.fault() {
+        Some(fault) => Err(format!(
+            "Fault at {}: {:?}",
+            fault.instruction_pointer(),
+            fault.kind()
+        )),
+        None => Ok(()),
+    }
+}
+
+fn dashboard(options: &Options) -> Result<(), String> {
+    let (computer, mut input) = load(options)?;
+    input.reverse();
+    let mut dashboard = Dashboard::new(computer, &input);
+    if options.speed.is_some() {
+        dashboard.set_speed(options.speed);
+    }
+    if let Some(address) = options.memory {
+        dashboard.show_memory(address);
+    }
+    //without a raw terminal a key only arrives once enter is pressed
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let control = match line.ok().as_deref().and_then(Control::parse) {
+                Some(control) => control,
+                None => continue,
+            };
+            if sender.send(control).is_err() {
+                break;
+            }
+        }
+    });
+    dashboard
+        .run(io::stdout(), &receiver)
+        .map_err(|err| err.to_string())?;
+    print_findings(dashboard.computer());
+    check_fault(dashboard.computer())
+}
+
+fn debug(options: &Options) -> Result<(), String> {
+    let (mut computer, mut input) = load(options)?;
+    eprintln!(
+        "Waiting for gdb on 127.0.0.1:{}, connect with: target remote :{}",
+        options.port, options.port
+    );
+    GdbStub::new(&mut computer, &mut input)
+        .serve(options.port)
+        .map_err(|err| err.to_string())?;
+    print_output(computer.output(), options.ascii);
+    Ok(())
+}
+
+fn disasm(options: &Options) -> Result<(), String> {
+    let program =
+        load_program_input(&options.file).map_err(|err| format!("{}: {}", options.file, err))?;
+    let _ = write!(io::stdout(), "{}", disassemble(&program));
+    Ok(())
+}
+
+fn write_output(options: &Options, text: &str) -> Result<(), String> {
+    match &options.output {
+        Some(file) => fs::write(file, text).map_err(|err| format!("{}: {}", file, err)),
+        None => {
+            let _ = write!(io::stdout(), "{}", text);
+            Ok(())
+        }
+    }
+}
+
+fn asm(options: &Options) -> Result<(), String> {
+    let program =
+        assemble_file(&options.file).map_err(|err| format!("{}: {}", options.file, err))?;
+    let program: Vec<String> = program.iter().map(|v| v.to_string()).collect();
+    write_output(options, &format!("{}\n", program.join(",")))
+}
+
+fn convert(options: &Options) -> Result<(), String> {
+    let image = load_image(&options.file).map_err(|err| format!("{}: {}", options.file, err))?;
+    let output = options.output.as_ref().ok_or("convert needs -o")?;
+    let is_image = fs::read(&options.file)
+        .map(|bytes| ProgramImage::is_image(&bytes))
+        .map_err(|err| format!("{}: {}", options.file, err))?;
+    let written = if is_image {
+        if let Some(relative_base) = image.relative_base {
+            eprintln!(
+                "The relative base of {} is dropped, text programs cannot hold it",
+                relative_base
+            );
+        }
+        write_program_text(output, &image.program)
+    } else {
+        write_image(
+            output,
+            &ProgramImage {
+                relative_base: options.relative_base,
+                ..image
+            },
+        )
+    };
+    written.map_err(|err| format!("{}: {}", output, err))
+}
+
+fn transpile_program(options: &Options) -> Result<(), String> {
+    let program =
+        load_program_input(&options.file).map_err(|err| format!("{}: {}", options.file, err))?;
+    let source = transpile(&program).map_err(|err| format!("{}: {}", options.file, err))?;
+    write_output(options, &source)
+}
+
+fn batch(options: &Options) -> Result<(), String> {
+    let program =
+        load_program_input(&options.file).map_err(|err| format!("{}: {}", options.file, err))?;
+    let rows_file = options.rows.as_ref().ok_or("batch needs --rows")?;
+    let rows = fs::read_to_string(rows_file)
+        .map_err(|err| err.to_string())
+        .and_then(|csv| parse_rows(&csv))
+        .map_err(|err| format!("{}: {}", rows_file, err))?;
+    let mut batch = Batch::new(&program);
+    if let Some(workers) = options.workers {
+        batch.set_workers(workers as usize);
+    }
+    if let Some(max_steps) = options.max_steps {
+        batch.set_max_steps(max_steps);
+    }
+    for &address in &options.watch {
+        batch.watch(address);
+    }
+    let results = batch.run(&rows);
+    write_output(options, &write_csv(&rows, &results, batch.watched()))
+}
+
+fn minimize_program(options: &Options) -> Result<(), String> {
+    let program =
+        load_program_input(&options.file).map_err(|err| format!("{}: {}", options.file, err))?;
+    let input = input_values(options)?;
+    let until = options.until.as_deref().ok_or("minimize needs --until")?;
+    let (check, argument) = match until.find('=') {
+        Some(idx) => (&until[..idx], Some(&until[idx + 1..])),
+        None => (until, None),
+    };
+    let engines = engines();
+    let reproducer = match (check, argument) {
+        ("faults", kind) => minimize(&program, &input, faults_with(kind.unwrap_or(""))),
+        ("output", Some(value)) => {
+            let value = value
+                .parse()
+                .map_err(|_| format!("Bad output value '{}'", value))?;
+            minimize(&program, &input, outputs(value))
+        }
+        ("diverges", Some(name)) => {
+            let other = engines
+                .iter()
+                .find(|e| e.name() == name)
+                .ok_or_else(|| format!("Unknown engine '{}'", name))?;
+            minimize(&program, &input, diverges(&IntcodeEngine, other.as_ref()))
+        }
+        _ => return Err(format!("Unknown check '{}'", until)),
+    };
+    let reproducer = reproducer.ok_or_else(|| format!("The program does not satisfy {}", until))?;
+    let program: Vec<String> = reproducer.program.iter().map(|v| v.to_string()).collect();
+    let input: Vec<String> = reproducer.input.iter().map(|v| v.to_string()).collect();
+    eprintln!("input: {} ({} checks)", input.join(","), reproducer.tests);
+    write_output(options, &format!("{}\n", program.join(",")))
+}
+
+fn test(options: &Options) -> Result<(), String> {
+    let bundles = load_path(&options.file).map_err(|err| format!("{}: {}", options.file, err))?;
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let mut failed = 0;
+    for (file, bundle) in bundles {
+        let report = bundle.run();
+        let _ = writeln!(stdout, "{}\n{}\n", file, report);
+        failed += report.failed();
+    }
+    match failed {
+        0 => Ok(()),
+        1 => Err("1 case failed".to_string()),
+        _ => Err(format!("{} cases failed", failed)),
+    }
+}
+
+fn dispatch(command: &str, args: &[String]) -> Result<(), String> {
+    let options = parse_options(args)?;
+    match command {
+        "run" => run(&options),
+        "replay" => replay_tape(&options),
+        "trace" => trace(&options),
+        "console" => console(&options),
+        "dashboard" => dashboard(&options),
+        "debug" => debug(&options),
+        "disasm" => disasm(&options),
+        "asm" => asm(&options),
+        "batch" => batch(&options),
+        "convert" => convert(&options),
+        "transpile" => transpile_program(&options),
+        "minimize" => minimize_program(&options),
+        "test" => test(&options),
+        _ => Err(format!("Unknown command '{}'\n\n{}", command, USAGE)),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let command = match args.first() {
+        Some(command) if command != "help" && command != "--help" && command != "-h" => command,
+        _ => {
+            let _ = writeln!(io::stdout(), "{}", USAGE);
+            return;
+        }
+    };
+    //a panic is reported like any other error. the older engines minimize compares
+    //against panic on programs they do not support, so the message is not printed as it happens
+    panic::set_hook(Box::new(|_| {}));
+    let result = panic::catch_unwind(|| dispatch(command, &args[1..])).unwrap_or_else(|payload| {
+        let message = match payload.downcast_ref::<&str>() {
+            Some(message) => message.to_string(),
+            None => payload
+                .downcast_ref::<String>()
+                .cloned()
+                .unwrap_or_else(|| "panicked".to_string()),
+        };
+        Err(message)
+    });
+    if let Err(err) = result {
+        let _ = io::stdout().flush();
+        eprintln!("intcode: {}", err);
+        process::exit(1);
+    }
+}
@@ -64,7 +64,7 @@ impl From<u32> for ParameterMode {
     }
 }
 
-pub(crate) fn process_instructions(input: Option<i32>, instructions: &[i32]) -> Vec<i32> {
+pub fn process_instructions(input: Option<i32>, instructions: &[i32]) -> Vec<i32> {
     let mut processed_instructions = Vec::from(instructions);
     let mut output = Vec::new();
     let mut instruction_pointer = 0;
@@ -349,6 +349,7 @@ pub(crate) fn determine_positions(instruction_pointer: usize, instructions: &[i3
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::intcode_vectors::load_directory;
 
     #[test]
     fn test_process_opcode_and_param_mode() {
@@ -379,58 +380,21 @@ mod tests {
         assert_eq!(output.parameter_modes, correct_output.parameter_modes)
     }
 
+    //the day 5 bundles, run on this computer, which takes at most one input
     #[test]
-    fn test_run_opcodes() {
-        let test_sets = [
-            (vec![3, 0, 4, 0, 99], vec![10]),
-            (vec![3, 3, 1101, 100, -1, 1, 4, 1, 99], vec![9]),
-        ];
-
-        for test_set in test_sets.iter() {
-            assert_eq!(process_instructions(Some(10), &test_set.0), test_set.1)
+    fn test_vectors() {
+        let bundles = load_directory("test_vectors").unwrap();
+        let day_5 = bundles.iter().filter(|(file, _)| file.contains("day05"));
+        for (file, bundle) in day_5 {
+            let program: Vec<i32> = bundle.program.iter().map(|&v| v as i32).collect();
+            for case in &bundle.cases {
+                let output: Vec<i64> =
+                    process_instructions(case.input.first().map(|&v| v as i32), &program)
+                        .into_iter()
+                        .map(i64::from)
+                        .collect();
+                assert_eq!(output, case.output, "{}: {}", file, case.name);
+            }
         }
     }
-
-    #[test]
-    fn test_conditional_opcodes() {
-        let eq_to = vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
-        assert_eq!(process_instructions(Some(8), &eq_to), vec![1]);
-        assert_eq!(process_instructions(Some(4), &eq_to), vec![0]);
-
-        let less_than = vec![3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8];
-        assert_eq!(process_instructions(Some(3), &less_than), vec![1]);
-        assert_eq!(process_instructions(Some(10), &less_than), vec![0]);
-
-        let eq_to = vec![3, 3, 1108, -1, 8, 3, 4, 3, 99];
-        assert_eq!(process_instructions(Some(8), &eq_to), vec![1]);
-        assert_eq!(process_instructions(Some(4), &eq_to), vec![0]);
-
-        let less_than = vec![3, 3, 1107, -1, 8, 3, 4, 3, 99];
-        assert_eq!(process_instructions(Some(3), &less_than), vec![1]);
-        assert_eq!(process_instructions(Some(10), &less_than), vec![0]);
-    }
-
-    #[test]
-    fn test_jump_opcodes() {
-        let jump = vec![3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
-        assert_eq!(process_instructions(Some(8), &jump), vec![1]);
-        assert_eq!(process_instructions(Some(0), &jump), vec![0]);
-
-        let jump = vec![3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1];
-        assert_eq!(process_instructions(Some(8), &jump), vec![1]);
-        assert_eq!(process_instructions(Some(0), &jump), vec![0]);
-    }
-
-    #[test]
-    fn test_all_opcodes() {
-        let prog = vec![
-            3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0,
-            0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
-            20, 1105, 1, 46, 98, 99,
-        ];
-
-        assert_eq!(process_instructions(Some(4), &prog), vec![999]);
-        assert_eq!(process_instructions(Some(8), &prog), vec![1000]);
-        assert_eq!(process_instructions(Some(11), &prog), vec![1001]);
-    }
 }
@@ -1,7 +1,11 @@
-use crate::diagnostic_program::{
-    comparison, determine_positions, jump, process_opcode_and_param_mode, update_instructions,
-    OpCode, ParameterMode, INPUT_OUTPUT_INS_LENGTH, INSTRUCTION_LENGTH,
+use crate::{
+    diagnostic_program::{
+        comparison, determine_positions, jump, process_opcode_and_param_mode, update_instructions,
+        OpCode, ParameterMode, INPUT_OUTPUT_INS_LENGTH, INSTRUCTION_LENGTH,
+    },
+    intcode_scheduler::Scheduler,
 };
+use std::convert::TryInto;
 
 #[derive(Debug)]
 enum AmplifierState {
@@ -10,14 +14,14 @@ enum AmplifierState {
 }
 
 #[derive(Debug)]
-struct Amplifier {
+pub struct Amplifier {
     processed_instructions: Vec<i32>,
     instruction_pointer: usize,
     state: AmplifierState,
 }
 
 impl Amplifier {
-    fn new(instructions: &[i32]) -> Amplifier {
+    pub fn new(instructions: &[i32]) -> Amplifier {
         let processed_instructions = Vec::from(instructions);
         let instruction_pointer = 0;
         let state = AmplifierState::Waiting;
@@ -28,14 +32,18 @@ impl Amplifier {
         }
     }
 
-    fn is_waiting(&self) -> bool {
+    pub fn is_waiting(&self) -> bool {
         match self.state {
             AmplifierState::Halted => false,
             AmplifierState::Waiting => true,
         }
     }
 
-    fn process_instructions(&mut self, input: &mut Vec<i32>) -> Vec<i32> {
+    pub fn memory(&self) -> &[i32] {
+        &self.processed_instructions
+    }
+
+    pub fn process_instructions(&mut self, input: &mut Vec<i32>) -> Vec<i32> {
         let mut output = Vec::new();
 
         loop {
@@ -136,36 +144,32 @@ impl Amplifier {
 }
 
 fn process_feedback_phase_setting_sequence(sequence: &[i32], instructions: &[i32]) -> i32 {
-    let mut amp_a = Amplifier::new(&instructions);
-    let mut amp_b = Amplifier::new(&instructions);
-    let mut amp_c = Amplifier::new(&instructions);
-    let mut amp_d = Amplifier::new(&instructions);
-    let mut amp_e = Amplifier::new(&instructions);
-    //first run
-    let mut out_a = amp_a.process_instructions(&mut vec![0, sequence[0]]);
-    let mut out_b = amp_b.process_instructions(&mut vec![sequence[1]]);
-    let mut out_c = amp_c.process_instructions(&mut vec![sequence[2]]);
-    let mut out_d = amp_d.process_instructions(&mut vec![sequence[3]]);
-    let mut out_e = amp_e.process_instructions(&mut vec![sequence[4]]);
-
-    //run till all halted
-    while amp_a.is_waiting()
-        && amp_b.is_waiting()
-        && amp_c.is_waiting()
-        && amp_d.is_waiting()
-        && amp_e.is_waiting()
-    {
-        out_a.append(&mut amp_a.process_instructions(&mut out_e));
-        out_b.append(&mut amp_b.process_instructions(&mut out_a));
-        out_c.append(&mut amp_c.process_instructions(&mut out_b));
-        out_d.append(&mut amp_d.process_instructions(&mut out_c));
-        out_e.append(&mut amp_e.process_instructions(&mut out_d));
+    let program: Vec<i64> = instructions.iter().map(|&i| i64::from(i)).collect();
+    let mut scheduler = Scheduler::new();
+    let amplifiers: Vec<usize> = sequence
+        .iter()
+        .map(|&phase| {
+            let amplifier = scheduler.add_program(&program);
+            scheduler.send(amplifier, i64::from(phase));
+            amplifier
+        })
+        .collect();
+    //every amplifier feeds the next, the last one feeds the first
+    for (idx, &amplifier) in amplifiers.iter().enumerate() {
+        scheduler.connect(amplifier, amplifiers[(idx + 1) % amplifiers.len()]);
     }
+    scheduler.send(amplifiers[0], 0);
+    scheduler.run();
 
-    *out_e.get(0).expect("There should be a final output")
+    let last = amplifiers[amplifiers.len() - 1];
+    let signal = *scheduler
+        .output(last)
+        .last()
+        .expect("There should be a final output");
+    signal.try_into().expect("The signal should fit in an i32")
 }
 
-pub(crate) fn find_best_feedback_phase_setting_sequence(instructions: &[i32]) -> i32 {
+pub fn find_best_feedback_phase_setting_sequence(instructions: &[i32]) -> i32 {
     let mut highest_output = 0;
     let start = 5;
     let end = 10;
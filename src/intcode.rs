@@ -1,16 +1,20 @@
+use crate::intcode_records::{records, Record, Records};
 use std::{
-    collections::HashMap,
-    convert::TryInto,
-    fs::File,
-    io,
-    io::{BufRead, BufReader},
+    collections::{BTreeMap, HashMap, HashSet},
+    convert::{TryFrom, TryInto},
+    fmt, fs, io,
+    ops::Range,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
 };
 
 const INSTRUCTION_LENGTH: u64 = 4;
 const INPUT_OUTPUT_INS_LENGTH: u64 = 2;
 
-#[derive(Debug, PartialEq)]
-enum OpCode {
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum OpCode {
     Add,
     Multiply,
     Input,
@@ -59,6 +63,63 @@ impl From<i64> for OpCode {
     }
 }
 
+impl OpCode {
+    pub(crate) fn from_number(opcode_number: i64) -> Option<OpCode> {
+        match opcode_number {
+            1 => Some(OpCode::Add),
+            2 => Some(OpCode::Multiply),
+            3 => Some(OpCode::Input),
+            4 => Some(OpCode::Output),
+            5 => Some(OpCode::JumpIfTrue),
+            6 => Some(OpCode::JumpIfFalse),
+            7 => Some(OpCode::LessThan),
+            8 => Some(OpCode::Equals),
+            9 => Some(OpCode::AdjustRelativeBaseOffset),
+            99 => Some(OpCode::Halt),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn parameter_count(self) -> usize {
+        match self {
+            OpCode::Add | OpCode::Multiply | OpCode::LessThan | OpCode::Equals => 3,
+            OpCode::JumpIfTrue | OpCode::JumpIfFalse => 2,
+            OpCode::Input | OpCode::Output | OpCode::AdjustRelativeBaseOffset => 1,
+            OpCode::Halt => 0,
+        }
+    }
+
+    pub(crate) fn number(self) -> i64 {
+        match self {
+            OpCode::Add => 1,
+            OpCode::Multiply => 2,
+            OpCode::Input => 3,
+            OpCode::Output => 4,
+            OpCode::JumpIfTrue => 5,
+            OpCode::JumpIfFalse => 6,
+            OpCode::LessThan => 7,
+            OpCode::Equals => 8,
+            OpCode::AdjustRelativeBaseOffset => 9,
+            OpCode::Halt => 99,
+        }
+    }
+
+    pub(crate) fn mnemonic(self) -> &'static str {
+        match self {
+            OpCode::Add => "add",
+            OpCode::Multiply => "mul",
+            OpCode::Input => "in",
+            OpCode::Output => "out",
+            OpCode::JumpIfTrue => "jnz",
+            OpCode::JumpIfFalse => "jz",
+            OpCode::LessThan => "lt",
+            OpCode::Equals => "eq",
+            OpCode::AdjustRelativeBaseOffset => "arb",
+            OpCode::Halt => "hlt",
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub(crate) enum ParameterMode {
     Position,
@@ -88,34 +149,506 @@ impl From<u32> for ParameterMode {
     }
 }
 
-#[derive(Debug)]
-enum ComputerState {
+#[derive(Debug, Clone, PartialEq)]
+pub enum ComputerState {
     Running,
     Waiting,
     Halted,
+    Faulted(Fault),
+    //stopped by an interrupt, run or step carries on from the instruction pointer
+    Paused,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Limits {
+    pub max_address: Option<u64>,
+    pub max_cells: Option<usize>,
+    pub max_output: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FaultKind {
+    AddressOutOfRange(u64),
+    TooManyCells(usize),
+    OutputTooLong(usize),
+    ProtectedWrite(u64),
+    ProtectedExecute(u64),
+    Suspicious(Suspicion),
+    //not an instruction at all, or not part of the instruction set the computer is restricted to
+    UndefinedInstruction(i64),
+    //the program ends before the instruction does, this is where the operand should be
+    MissingOperand(u64),
+    NegativeAddress(i64),
+    //an addition, multiplication or relative base adjustment that does not fit in an i64
+    Overflow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Protection {
+    ReadOnly,
+    NoExecute,
+}
+
+//what each day's puzzle had defined by then, every later day keeps what came before
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InstructionSet {
+    //add, multiply and halt, position mode only
+    Day2,
+    //input, output, jumps and comparisons, and immediate mode
+    Day5,
+    //adjusting the relative base, and relative mode
+    Day9,
+}
+
+impl InstructionSet {
+    //parameter modes included
+    pub fn defines(self, instruction: i64) -> bool {
+        let decoded = match decode_instruction(&[instruction, 0, 0, 0], 0) {
+            Some(decoded) => decoded,
+            None => return false,
+        };
+        let opcode_defined = match decoded.opcode() {
+            OpCode::Add | OpCode::Multiply | OpCode::Halt => true,
+            OpCode::AdjustRelativeBaseOffset => self == InstructionSet::Day9,
+            _ => self != InstructionSet::Day2,
+        };
+        //the interpreter reads three mode digits whatever the opcode
+        let modes_known = (2..5).all(|digit| instruction / 10_i64.pow(digit) % 10 <= 2);
+        opcode_defined
+            && modes_known
+            && decoded.parameters().iter().all(|&(mode, _)| match mode {
+                ParameterMode::Position => true,
+                ParameterMode::Immediate => self != InstructionSet::Day2,
+                ParameterMode::Relative => self == InstructionSet::Day9,
+            })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fault {
+    kind: FaultKind,
+    instruction_pointer: u64,
+}
+
+impl Fault {
+    pub fn kind(&self) -> &FaultKind {
+        &self.kind
+    }
+
+    pub fn instruction_pointer(&self) -> u64 {
+        self.instruction_pointer
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SanitizerMode {
+    //keep running and collect findings
+    Report,
+    //fault on the first finding. the instruction still finishes, except for
+    //a write through immediate mode, which is caught before it runs
+    Stop,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Suspicion {
+    //past the end of the program, never written or patched
+    UninitialisedRead(u64),
+    //onto an operand of an instruction that ran, a cell the program wrote as data,
+    //or past the end of the program, and never executed before
+    JumpIntoData(u64),
+    //the base would have gone below zero and was clamped instead
+    RelativeBaseSaturated { base: u64, adjustment: i64 },
+    WriteThroughImmediate,
+}
+
+impl fmt::Display for Suspicion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Suspicion::UninitialisedRead(address) => {
+                write!(f, "read of uninitialised cell {}", address)
+            }
+            Suspicion::JumpIntoData(address) => write!(f, "jump into data at {}", address),
+            Suspicion::RelativeBaseSaturated { base, adjustment } => write!(
+                f,
+                "relative base {} adjusted by {} was clamped to 0",
+                base, adjustment
+            ),
+            Suspicion::WriteThroughImmediate => write!(f, "write through an immediate parameter"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub instruction_pointer: u64,
+    pub suspicion: Suspicion,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ip {}: {}", self.instruction_pointer, self.suspicion)
+    }
+}
+
+struct Sanitizer {
+    mode: SanitizerMode,
+    program_length: u64,
+    written: HashSet<u64>,
+    //initialised from outside, not data the program wrote
+    patched: HashSet<u64>,
+    executed: HashSet<u64>,
+    operands: HashSet<u64>,
+    //what the current instruction has done so far
+    pending: Vec<Suspicion>,
+    findings: Vec<Finding>,
+}
+
+impl Sanitizer {
+    fn new(mode: SanitizerMode, program_length: u64) -> Sanitizer {
+        Sanitizer {
+            mode,
+            program_length,
+            written: HashSet::new(),
+            patched: HashSet::new(),
+            executed: HashSet::new(),
+            operands: HashSet::new(),
+            pending: Vec::new(),
+            findings: Vec::new(),
+        }
+    }
+
+    fn is_data(&self, address: u64) -> bool {
+        !self.executed.contains(&address)
+            && (self.operands.contains(&address)
+                || self.written.contains(&address)
+                || address >= self.program_length)
+    }
+}
+
+//shared with other threads to pause a running computer at the next instruction boundary.
+//a request made while the computer is not running is kept until it next runs
+#[derive(Debug, Default, Clone)]
+pub struct Interrupt {
+    requested: Arc<AtomicBool>,
+}
+
+impl Interrupt {
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::SeqCst)
+    }
+
+    pub fn cancel(&self) {
+        self.requested.store(false, Ordering::SeqCst)
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+
+    fn take(&self) -> bool {
+        self.requested.swap(false, Ordering::SeqCst)
+    }
+}
+
+pub trait Observer: Send {
+    fn before_execute(&mut self, _instruction_pointer: u64, _instruction: i64) {}
+    fn memory_read(&mut self, _address: u64, _value: i64) {}
+    fn memory_write(&mut self, _address: u64, _value: i64) {}
+    fn input_consumed(&mut self, _value: i64) {}
+    fn output_produced(&mut self, _value: i64) {}
+    fn relative_base_changed(&mut self, _old_base: u64, _new_base: u64) {}
+    fn state_changed(&mut self, _old_state: &ComputerState, _new_state: &ComputerState) {}
+}
+
+impl Observer for Box<dyn Observer> {
+    fn before_execute(&mut self, instruction_pointer: u64, instruction: i64) {
+        (**self).before_execute(instruction_pointer, instruction)
+    }
+
+    fn memory_read(&mut self, address: u64, value: i64) {
+        (**self).memory_read(address, value)
+    }
+
+    fn memory_write(&mut self, address: u64, value: i64) {
+        (**self).memory_write(address, value)
+    }
+
+    fn input_consumed(&mut self, value: i64) {
+        (**self).input_consumed(value)
+    }
+
+    fn output_produced(&mut self, value: i64) {
+        (**self).output_produced(value)
+    }
+
+    fn relative_base_changed(&mut self, old_base: u64, new_base: u64) {
+        (**self).relative_base_changed(old_base, new_base)
+    }
+
+    fn state_changed(&mut self, old_state: &ComputerState, new_state: &ComputerState) {
+        (**self).state_changed(old_state, new_state)
+    }
+}
+
+impl<T: Observer> Observer for Arc<Mutex<T>> {
+    fn before_execute(&mut self, instruction_pointer: u64, instruction: i64) {
+        self.lock()
+            .expect("Observer lock poisoned")
+            .before_execute(instruction_pointer, instruction)
+    }
+
+    fn memory_read(&mut self, address: u64, value: i64) {
+        self.lock()
+            .expect("Observer lock poisoned")
+            .memory_read(address, value)
+    }
+
+    fn memory_write(&mut self, address: u64, value: i64) {
+        self.lock()
+            .expect("Observer lock poisoned")
+            .memory_write(address, value)
+    }
+
+    fn input_consumed(&mut self, value: i64) {
+        self.lock()
+            .expect("Observer lock poisoned")
+            .input_consumed(value)
+    }
+
+    fn output_produced(&mut self, value: i64) {
+        self.lock()
+            .expect("Observer lock poisoned")
+            .output_produced(value)
+    }
+
+    fn relative_base_changed(&mut self, old_base: u64, new_base: u64) {
+        self.lock()
+            .expect("Observer lock poisoned")
+            .relative_base_changed(old_base, new_base)
+    }
+
+    fn state_changed(&mut self, old_state: &ComputerState, new_state: &ComputerState) {
+        self.lock()
+            .expect("Observer lock poisoned")
+            .state_changed(old_state, new_state)
+    }
+}
+
+struct Memory {
+    cells: HashMap<u64, i64>,
+    limits: Limits,
+    protected: Vec<(Range<u64>, Protection)>,
+    fault: Option<FaultKind>,
+    observer: Option<Box<dyn Observer>>,
+    sanitizer: Option<Sanitizer>,
+}
+
+impl Memory {
+    fn new(instructions: &[i64], limits: Limits) -> Memory {
+        let mut memory = Memory {
+            cells: HashMap::new(),
+            limits,
+            protected: Vec::new(),
+            fault: None,
+            observer: None,
+            sanitizer: None,
+        };
+        memory.load(instructions);
+        memory
+    }
+
+    fn load(&mut self, instructions: &[i64]) {
+        self.cells.clear();
+        for (idx, i) in instructions.iter().enumerate() {
+            self.cells.insert(idx as u64, *i);
+        }
+        self.fault = None;
+        if let Some(sanitizer) = self.sanitizer.as_mut() {
+            *sanitizer = Sanitizer::new(sanitizer.mode, instructions.len() as u64);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    fn fetch(&mut self, location: u64) -> i64 {
+        if self.within_limits(location) {
+            *self.cells.entry(location).or_insert(0)
+        } else {
+            0
+        }
+    }
+
+    fn read(&mut self, location: u64) -> i64 {
+        if let Some(sanitizer) = self.sanitizer.as_mut() {
+            if location >= sanitizer.program_length
+                && !sanitizer.written.contains(&location)
+                && !sanitizer.patched.contains(&location)
+            {
+                sanitizer
+                    .pending
+                    .push(Suspicion::UninitialisedRead(location))
+            }
+        }
+        let value = self.fetch(location);
+        if let Some(observer) = self.observer.as_mut() {
+            observer.memory_read(location, value)
+        }
+        value
+    }
+
+    fn write(&mut self, location: u64, value: i64) {
+        if self.is_protected(location, Protection::ReadOnly) {
+            if self.fault.is_none() {
+                self.fault = Some(FaultKind::ProtectedWrite(location));
+            }
+        } else if self.within_limits(location) {
+            self.cells.insert(location, value);
+            if let Some(sanitizer) = self.sanitizer.as_mut() {
+                sanitizer.written.insert(location);
+            }
+            if let Some(observer) = self.observer.as_mut() {
+                observer.memory_write(location, value)
+            }
+        }
+    }
+
+    fn is_protected(&self, location: u64, protection: Protection) -> bool {
+        self.protected
+            .iter()
+            .any(|(range, p)| *p == protection && range.contains(&location))
+    }
+
+    fn within_limits(&mut self, location: u64) -> bool {
+        if self.fault.is_some() {
+            return false;
+        }
+        if let Some(max_address) = self.limits.max_address {
+            if location > max_address {
+                self.fault = Some(FaultKind::AddressOutOfRange(location));
+                return false;
+            }
+        }
+        if let Some(max_cells) = self.limits.max_cells {
+            if self.cells.len() >= max_cells && !self.cells.contains_key(&location) {
+                self.fault = Some(FaultKind::TooManyCells(max_cells));
+                return false;
+            }
+        }
+        true
+    }
+
+    fn take_fault(&mut self) -> Option<FaultKind> {
+        self.fault.take()
+    }
 }
 
 pub struct IntcodeComputer {
-    memory: HashMap<u64, i64>,
+    memory: Memory,
     output: Vec<i64>,
     instruction_pointer: u64,
     relative_base_offset: u64,
     state: ComputerState,
+    interrupt: Interrupt,
+    instruction_set: Option<InstructionSet>,
 }
 
 impl IntcodeComputer {
     pub fn new(instructions: &[i64]) -> IntcodeComputer {
-        let mut memory = HashMap::new();
-        for (idx, i) in instructions.iter().enumerate() {
-            memory.insert(idx as u64, *i);
-        }
+        IntcodeComputer::with_limits(instructions, Limits::default())
+    }
+
+    pub fn with_limits(instructions: &[i64], limits: Limits) -> IntcodeComputer {
         IntcodeComputer {
-            memory,
+            memory: Memory::new(instructions, limits),
             output: Vec::new(),
             instruction_pointer: 0,
             relative_base_offset: 0,
             state: ComputerState::Halted,
+            interrupt: Interrupt::default(),
+            instruction_set: None,
+        }
+    }
+
+    pub fn from_image(image: &ProgramImage) -> IntcodeComputer {
+        let mut computer = IntcodeComputer::new(&image.program);
+        computer.relative_base_offset = image.relative_base.unwrap_or(0);
+        computer
+    }
+
+    pub fn interrupt_handle(&self) -> Interrupt {
+        self.interrupt.clone()
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.memory.limits = limits
+    }
+
+    //instructions outside the set fault instead of running, unknown ones fault either way
+    pub fn restrict(&mut self, instruction_set: InstructionSet) {
+        self.instruction_set = Some(instruction_set)
+    }
+
+    pub fn instruction_set(&self) -> Option<InstructionSet> {
+        self.instruction_set
+    }
+
+    pub fn protect(&mut self, range: Range<u64>, protection: Protection) {
+        self.memory.protected.push((range, protection))
+    }
+
+    pub fn protect_program(&mut self) {
+        let program_length = self.memory.len() as u64;
+        self.protect(0..program_length, Protection::ReadOnly)
+    }
+
+    pub fn clear_protection(&mut self) {
+        self.memory.protected.clear()
+    }
+
+    pub fn patch(&mut self, address: u64, value: i64) {
+        self.memory.cells.insert(address, value);
+        if let Some(sanitizer) = self.memory.sanitizer.as_mut() {
+            sanitizer.patched.insert(address);
+        }
+    }
+
+    //checks for behaviour that is legal but almost always a bug
+    pub fn sanitize(&mut self, mode: SanitizerMode) {
+        let program_length = self.memory.len() as u64;
+        self.memory.sanitizer = Some(Sanitizer::new(mode, program_length))
+    }
+
+    pub fn findings(&self) -> &[Finding] {
+        self.memory
+            .sanitizer
+            .as_ref()
+            .map_or(&[], |sanitizer| &sanitizer.findings)
+    }
+
+    pub fn attach_observer(&mut self, observer: Box<dyn Observer>) {
+        self.memory.observer = Some(observer)
+    }
+
+    pub fn detach_observer(&mut self) -> Option<Box<dyn Observer>> {
+        self.memory.observer.take()
+    }
+
+    fn set_state(&mut self, state: ComputerState) {
+        if let Some(observer) = self.memory.observer.as_mut() {
+            if self.state != state {
+                observer.state_changed(&self.state, &state)
+            }
+        }
+        self.state = state
+    }
+
+    pub fn set_relative_base_offset(&mut self, relative_base_offset: u64) {
+        if let Some(observer) = self.memory.observer.as_mut() {
+            observer.relative_base_changed(self.relative_base_offset, relative_base_offset)
         }
+        self.relative_base_offset = relative_base_offset
     }
 
     fn is_waiting(&self) -> bool {
@@ -125,199 +658,406 @@ impl IntcodeComputer {
         }
     }
 
+    pub fn state(&self) -> &ComputerState {
+        &self.state
+    }
+
+    pub fn fault(&self) -> Option<&Fault> {
+        match &self.state {
+            ComputerState::Faulted(fault) => Some(fault),
+            _ => None,
+        }
+    }
+
     pub fn output(&self) -> &Vec<i64> {
         &self.output
     }
 
+    //the output read as consecutive records
+    pub fn records<R: Record>(&self) -> Records<'_, R> {
+        records(&self.output)
+    }
+
+    pub fn instruction_pointer(&self) -> u64 {
+        self.instruction_pointer
+    }
+
+    pub fn set_instruction_pointer(&mut self, instruction_pointer: u64) {
+        self.instruction_pointer = instruction_pointer
+    }
+
+    pub fn relative_base_offset(&self) -> u64 {
+        self.relative_base_offset
+    }
+
+    pub fn peek(&self, address: u64) -> i64 {
+        self.memory.cells.get(&address).copied().unwrap_or(0)
+    }
+
+    pub fn instruction(&self, address: u64) -> Option<Instruction> {
+        let cells: Vec<i64> = (address..address + INSTRUCTION_LENGTH)
+            .map(|a| self.peek(a))
+            .collect();
+        decode_instruction(&cells, 0)
+    }
+
+    //every cell that has been loaded or touched, in address order
+    pub fn memory(&self) -> BTreeMap<u64, i64> {
+        self.memory.cells.iter().map(|(&a, &v)| (a, v)).collect()
+    }
+
     pub fn load_new_instructions(&mut self, instructions: &[i64]) {
-        self.memory.clear();
-        for (idx, i) in instructions.iter().enumerate() {
-            self.memory.insert(idx as u64, *i);
-        }
+        self.memory.load(instructions);
         self.output.clear();
         self.instruction_pointer = 0;
         self.relative_base_offset = 0;
-        self.state = ComputerState::Halted
+        self.set_state(ComputerState::Halted)
+    }
+
+    fn stop_with_fault(&mut self, kind: FaultKind, instruction_pointer: u64) {
+        self.instruction_pointer = instruction_pointer;
+        self.set_state(ComputerState::Faulted(Fault {
+            kind,
+            instruction_pointer,
+        }));
     }
 
     pub fn run(&mut self, input: &mut Vec<i64>) {
-        self.state = ComputerState::Running;
-        loop {
-            let opcode_mode = process_opcode_and_param_mode(memory_access(
-                &mut self.memory,
-                self.instruction_pointer as i64,
-                0,
-            ));
-            let positions = determine_positions(self.instruction_pointer, &mut self.memory);
-            match opcode_mode.opcode() {
-                OpCode::Add => {
-                    run_instructions(
-                        &mut self.instruction_pointer,
-                        &mut self.memory,
-                        self.relative_base_offset,
-                        &positions,
-                        &opcode_mode,
-                        |x, y| x + y,
-                    );
+        self.set_state(ComputerState::Running);
+        while self.is_running() {
+            self.step_unless_interrupted(input)
+        }
+    }
+
+    //like run, but stops after `max_steps` instructions with the state left at running.
+    //returns how many instructions completed, one that waits or faults is not counted
+    pub fn run_for(&mut self, input: &mut Vec<i64>, max_steps: u64) -> u64 {
+        self.set_state(ComputerState::Running);
+        let mut steps = 0;
+        while steps < max_steps && self.is_running() {
+            self.step_unless_interrupted(input);
+            if let ComputerState::Running | ComputerState::Halted = self.state {
+                steps += 1;
+            }
+        }
+        steps
+    }
+
+    pub fn step(&mut self, input: &mut Vec<i64>) {
+        self.set_state(ComputerState::Running);
+        self.step_unless_interrupted(input)
+    }
+
+    fn step_unless_interrupted(&mut self, input: &mut Vec<i64>) {
+        if self.interrupt.take() {
+            self.set_state(ComputerState::Paused)
+        } else {
+            self.execute_instruction(input)
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        matches!(self.state, ComputerState::Running)
+    }
+
+    fn execute_instruction(&mut self, input: &mut Vec<i64>) {
+        let instruction_start = self.instruction_pointer;
+        if self
+            .memory
+            .is_protected(instruction_start, Protection::NoExecute)
+        {
+            self.stop_with_fault(
+                FaultKind::ProtectedExecute(instruction_start),
+                instruction_start,
+            );
+            return;
+        }
+        let instruction = self.memory.fetch(instruction_start);
+        if let Some(kind) = self.memory.take_fault() {
+            self.stop_with_fault(kind, instruction_start);
+            return;
+        }
+        if let Some(observer) = self.memory.observer.as_mut() {
+            observer.before_execute(instruction_start, instruction)
+        }
+        let instruction_set = self.instruction_set.unwrap_or(InstructionSet::Day9);
+        if !instruction_set.defines(instruction) {
+            self.stop_with_fault(
+                FaultKind::UndefinedInstruction(instruction),
+                instruction_start,
+            );
+            return;
+        }
+        let opcode_mode = process_opcode_and_param_mode(instruction);
+        let positions = determine_positions(self.instruction_pointer, &mut self.memory);
+        let operands = [
+            positions.first_param(),
+            positions.second_param(),
+            positions.answer(),
+        ];
+        if let Some(missing) =
+            (0..opcode_mode.opcode().parameter_count()).find(|&n| operands[n].is_none())
+        {
+            self.stop_with_fault(
+                FaultKind::MissingOperand(instruction_start + 1 + missing as u64),
+                instruction_start,
+            );
+            return;
+        }
+        if let Some(sanitizer) = self.memory.sanitizer.as_mut() {
+            sanitizer.pending.clear();
+            let writes_immediate = match opcode_mode.opcode() {
+                OpCode::Input => opcode_mode.first_parameter_mode() == ParameterMode::Immediate,
+                OpCode::Add | OpCode::Multiply | OpCode::LessThan | OpCode::Equals => {
+                    opcode_mode.answer_parameter_mode() == ParameterMode::Immediate
                 }
-                OpCode::Multiply => {
-                    run_instructions(
-                        &mut self.instruction_pointer,
-                        &mut self.memory,
-                        self.relative_base_offset,
-                        &positions,
-                        &opcode_mode,
-                        |x, y| x * y,
-                    );
+                _ => false,
+            };
+            if writes_immediate {
+                sanitizer.pending.push(Suspicion::WriteThroughImmediate);
+                //stop mode faults before the instruction runs. otherwise it runs as it would
+                //unsanitized: add, multiply and the comparisons drop the write, and input
+                //ignores immediate mode and writes to the cell its parameter names
+                if self.report_suspicions(instruction_start) {
+                    return;
                 }
-                OpCode::Input => {
-                    let first_param = positions
-                        .first_param()
-                        .expect("Expected to have the first parameter");
-                    match input.pop() {
-                        Some(i) => {
-                            match opcode_mode.first_parameter_mode() {
-                                ParameterMode::Position | ParameterMode::Immediate => {
-                                    memory_update(&mut self.memory, first_param, 0, i);
-                                }
-                                ParameterMode::Relative => memory_update(
-                                    &mut self.memory,
-                                    first_param,
-                                    self.relative_base_offset,
-                                    i,
-                                ),
-                            }
-                            self.instruction_pointer += INPUT_OUTPUT_INS_LENGTH;
+            }
+        }
+        match opcode_mode.opcode() {
+            OpCode::Add => {
+                run_instructions(
+                    &mut self.instruction_pointer,
+                    &mut self.memory,
+                    self.relative_base_offset,
+                    &positions,
+                    &opcode_mode,
+                    i64::checked_add,
+                );
+            }
+            OpCode::Multiply => {
+                run_instructions(
+                    &mut self.instruction_pointer,
+                    &mut self.memory,
+                    self.relative_base_offset,
+                    &positions,
+                    &opcode_mode,
+                    i64::checked_mul,
+                );
+            }
+            OpCode::Input => {
+                let first_param = positions
+                    .first_param()
+                    .expect("Expected to have the first parameter");
+                match input.pop() {
+                    Some(i) => {
+                        if let Some(observer) = self.memory.observer.as_mut() {
+                            observer.input_consumed(i)
                         }
-                        None => {
-                            self.state = ComputerState::Waiting;
-                            break;
+                        match opcode_mode.first_parameter_mode() {
+                            ParameterMode::Position | ParameterMode::Immediate => {
+                                memory_update(&mut self.memory, first_param, 0, i);
+                            }
+                            ParameterMode::Relative => memory_update(
+                                &mut self.memory,
+                                first_param,
+                                self.relative_base_offset,
+                                i,
+                            ),
                         }
+                        self.instruction_pointer += INPUT_OUTPUT_INS_LENGTH;
+                    }
+                    None => {
+                        self.set_state(ComputerState::Waiting);
+                        return;
                     }
                 }
-                OpCode::Output => {
-                    let first_param = positions
-                        .first_param()
-                        .expect("Expected to have the first parameter");
-                    match opcode_mode.first_parameter_mode() {
-                        ParameterMode::Position => {
-                            self.output
-                                .push(memory_access(&mut self.memory, first_param, 0))
-                        }
-                        ParameterMode::Immediate => self.output.push(first_param),
-                        ParameterMode::Relative => self.output.push(memory_access(
-                            &mut self.memory,
-                            first_param,
-                            self.relative_base_offset,
-                        )),
+            }
+            OpCode::Output => {
+                let first_param = positions
+                    .first_param()
+                    .expect("Expected to have the first parameter");
+                let value = match opcode_mode.first_parameter_mode() {
+                    ParameterMode::Position => memory_access(&mut self.memory, first_param, 0),
+                    ParameterMode::Immediate => first_param,
+                    ParameterMode::Relative => {
+                        memory_access(&mut self.memory, first_param, self.relative_base_offset)
                     }
-                    self.instruction_pointer += INPUT_OUTPUT_INS_LENGTH;
+                };
+                //the 0 a faulted read gives back is never printed
+                if let Some(kind) = self.memory.take_fault() {
+                    self.stop_with_fault(kind, instruction_start);
+                    return;
                 }
-                OpCode::JumpIfTrue => jump(
+                if let Some(max_output) = self.memory.limits.max_output {
+                    if self.output.len() >= max_output {
+                        self.stop_with_fault(
+                            FaultKind::OutputTooLong(max_output),
+                            instruction_start,
+                        );
+                        return;
+                    }
+                }
+                if let Some(observer) = self.memory.observer.as_mut() {
+                    observer.output_produced(value)
+                }
+                self.output.push(value);
+                self.instruction_pointer += INPUT_OUTPUT_INS_LENGTH;
+            }
+            OpCode::JumpIfTrue => jump(
+                positions.first_param(),
+                positions.second_param(),
+                &mut self.instruction_pointer,
+                self.relative_base_offset,
+                &mut self.memory,
+                &opcode_mode,
+                |x| x != 0,
+            ),
+            OpCode::JumpIfFalse => jump(
+                positions.first_param(),
+                positions.second_param(),
+                &mut self.instruction_pointer,
+                self.relative_base_offset,
+                &mut self.memory,
+                &opcode_mode,
+                |x| x == 0,
+            ),
+            OpCode::LessThan => {
+                comparison(
                     positions.first_param(),
                     positions.second_param(),
-                    &mut self.instruction_pointer,
+                    positions.answer(),
                     self.relative_base_offset,
                     &mut self.memory,
                     &opcode_mode,
-                    |x| x != 0,
-                ),
-                OpCode::JumpIfFalse => jump(
+                    |x, y| x < y,
+                );
+                self.instruction_pointer += INSTRUCTION_LENGTH;
+            }
+            OpCode::Equals => {
+                comparison(
                     positions.first_param(),
                     positions.second_param(),
-                    &mut self.instruction_pointer,
+                    positions.answer(),
                     self.relative_base_offset,
                     &mut self.memory,
                     &opcode_mode,
-                    |x| x == 0,
-                ),
-                OpCode::LessThan => {
-                    comparison(
-                        positions.first_param(),
-                        positions.second_param(),
-                        positions.answer(),
-                        self.relative_base_offset,
-                        &mut self.memory,
-                        &opcode_mode,
-                        |x, y| x < y,
-                    );
-                    self.instruction_pointer += INSTRUCTION_LENGTH;
-                }
-                OpCode::Equals => {
-                    comparison(
-                        positions.first_param(),
-                        positions.second_param(),
-                        positions.answer(),
-                        self.relative_base_offset,
-                        &mut self.memory,
-                        &opcode_mode,
-                        |x, y| x == y,
-                    );
-                    self.instruction_pointer += INSTRUCTION_LENGTH;
-                }
-                OpCode::AdjustRelativeBaseOffset => {
-                    let first_param = positions
-                        .first_param()
-                        .expect("Expected to have the first parameter");
-                    match opcode_mode.first_parameter_mode() {
-                        ParameterMode::Position => {
-                            self.relative_base_offset = adjusted_relative_base_offset(
-                                self.relative_base_offset,
-                                memory_access(&mut self.memory, first_param, 0),
-                            )
-                        }
-                        ParameterMode::Immediate => {
-                            self.relative_base_offset = adjusted_relative_base_offset(
-                                self.relative_base_offset,
-                                first_param,
-                            )
-                        }
-                        ParameterMode::Relative => {
-                            self.relative_base_offset = adjusted_relative_base_offset(
-                                self.relative_base_offset,
-                                memory_access(
-                                    &mut self.memory,
-                                    first_param,
-                                    self.relative_base_offset,
-                                ),
-                            )
-                        }
+                    |x, y| x == y,
+                );
+                self.instruction_pointer += INSTRUCTION_LENGTH;
+            }
+            OpCode::AdjustRelativeBaseOffset => {
+                let first_param = positions
+                    .first_param()
+                    .expect("Expected to have the first parameter");
+                let adjustment = match opcode_mode.first_parameter_mode() {
+                    ParameterMode::Position => memory_access(&mut self.memory, first_param, 0),
+                    ParameterMode::Immediate => first_param,
+                    ParameterMode::Relative => {
+                        memory_access(&mut self.memory, first_param, self.relative_base_offset)
+                    }
+                };
+                if let Some(sanitizer) = self.memory.sanitizer.as_mut() {
+                    if adjustment < 0 && adjustment.unsigned_abs() > self.relative_base_offset {
+                        sanitizer.pending.push(Suspicion::RelativeBaseSaturated {
+                            base: self.relative_base_offset,
+                            adjustment,
+                        })
                     }
-                    self.instruction_pointer += INPUT_OUTPUT_INS_LENGTH;
                 }
-                OpCode::Halt => {
-                    self.state = ComputerState::Halted;
-                    break;
+                if self.memory.fault.is_none() {
+                    match adjusted_relative_base_offset(self.relative_base_offset, adjustment) {
+                        Some(base) => self.set_relative_base_offset(base),
+                        None => self.memory.fault = Some(FaultKind::Overflow),
+                    }
                 }
+                self.instruction_pointer += INPUT_OUTPUT_INS_LENGTH;
             }
+            OpCode::Halt => {
+                self.set_state(ComputerState::Halted);
+                return;
+            }
+        }
+        if let Some(kind) = self.memory.take_fault() {
+            self.stop_with_fault(kind, instruction_start);
+            return;
+        }
+        let ip = self.instruction_pointer;
+        if let Some(sanitizer) = self.memory.sanitizer.as_mut() {
+            let length = 1 + opcode_mode.opcode().parameter_count() as u64;
+            let jumped = matches!(
+                opcode_mode.opcode(),
+                OpCode::JumpIfTrue | OpCode::JumpIfFalse
+            ) && ip != instruction_start + length;
+            if jumped && sanitizer.is_data(ip) {
+                sanitizer.pending.push(Suspicion::JumpIntoData(ip));
+            }
+            sanitizer.executed.insert(instruction_start);
+            sanitizer
+                .operands
+                .extend(instruction_start + 1..instruction_start + length);
+            self.report_suspicions(instruction_start);
+        }
+    }
+
+    //moves what the instruction did into the findings, true if that stopped the computer
+    fn report_suspicions(&mut self, instruction_start: u64) -> bool {
+        let sanitizer = match self.memory.sanitizer.as_mut() {
+            Some(sanitizer) => sanitizer,
+            None => return false,
+        };
+        let first = sanitizer.pending.first().cloned();
+        for suspicion in sanitizer.pending.drain(..) {
+            sanitizer.findings.push(Finding {
+                instruction_pointer: instruction_start,
+                suspicion,
+            })
+        }
+        match first {
+            Some(suspicion) if sanitizer.mode == SanitizerMode::Stop => {
+                self.stop_with_fault(FaultKind::Suspicious(suspicion), instruction_start);
+                true
+            }
+            _ => false,
         }
     }
 }
 
-fn memory_access(memory: &mut HashMap<u64, i64>, location_value: i64, offset: u64) -> i64 {
-    let location = convert_to_location(location_value, offset);
-    *memory.entry(location).or_insert(0)
+//a bad location faults, reading as 0
+fn memory_access(memory: &mut Memory, location_value: i64, offset: u64) -> i64 {
+    match convert_to_location(memory, location_value, offset) {
+        Some(location) => memory.read(location),
+        None => 0,
+    }
 }
 
-fn memory_update(memory: &mut HashMap<u64, i64>, location_value: i64, offset: u64, value: i64) {
-    let location = convert_to_location(location_value, offset);
-    memory.insert(location, value);
+//a bad location faults, writing nothing
+fn memory_update(memory: &mut Memory, location_value: i64, offset: u64, value: i64) {
+    if let Some(location) = convert_to_location(memory, location_value, offset) {
+        memory.write(location, value);
+    }
 }
 
-fn convert_to_location(value: i64, offset: u64) -> u64 {
-    let location = value + offset as i64;
-    if location.is_negative() {
-        panic!("Negative locations should not occur")
+fn convert_to_location(memory: &mut Memory, value: i64, offset: u64) -> Option<u64> {
+    let location = i128::from(value) + i128::from(offset);
+    let fault = if location.is_negative() {
+        FaultKind::NegativeAddress(location as i64)
     } else {
-        location as u64
+        match u64::try_from(location) {
+            Ok(location) => return Some(location),
+            Err(_) => FaultKind::AddressOutOfRange(u64::MAX),
+        }
+    };
+    if memory.fault.is_none() {
+        memory.fault = Some(fault);
     }
+    None
 }
 
-fn adjusted_relative_base_offset(current_base_offset: u64, adjustment: i64) -> u64 {
+fn adjusted_relative_base_offset(current_base_offset: u64, adjustment: i64) -> Option<u64> {
     if adjustment.is_negative() {
-        current_base_offset.saturating_sub(adjustment.abs() as u64)
+        Some(current_base_offset.saturating_sub(adjustment.unsigned_abs()))
     } else {
-        current_base_offset + adjustment as u64
+        current_base_offset.checked_add(adjustment as u64)
     }
 }
 
@@ -326,7 +1066,7 @@ fn jump(
     second_param: Option<i64>,
     instruction_pointer: &mut u64,
     relative_base_offset: u64,
-    memory: &mut HashMap<u64, i64>,
+    memory: &mut Memory,
     opcode_mode: &OpcodeMode,
     operation: fn(i64) -> bool,
 ) {
@@ -342,17 +1082,24 @@ fn jump(
             comparison_result = operation(memory_access(memory, first_param, relative_base_offset))
         }
     }
+    //the instruction pointer stays put, the fault is taken once the instruction returns
+    if memory.fault.is_some() {
+        return;
+    }
     if comparison_result {
-        match opcode_mode.second_parameter_mode() {
-            ParameterMode::Position => {
-                *instruction_pointer = memory_access(memory, second_param, 0) as u64
-            }
-            ParameterMode::Immediate => *instruction_pointer = second_param as u64,
-            ParameterMode::Relative => {
-                *instruction_pointer =
-                    memory_access(memory, second_param, relative_base_offset) as u64
-            }
+        let target = match opcode_mode.second_parameter_mode() {
+            ParameterMode::Position => memory_access(memory, second_param, 0),
+            ParameterMode::Immediate => second_param,
+            ParameterMode::Relative => memory_access(memory, second_param, relative_base_offset),
+        };
+        if memory.fault.is_some() {
+            return;
+        }
+        if target.is_negative() {
+            memory.fault = Some(FaultKind::NegativeAddress(target));
+            return;
         }
+        *instruction_pointer = target as u64;
     } else {
         *instruction_pointer += 3;
     }
@@ -363,7 +1110,7 @@ fn comparison(
     second_param: Option<i64>,
     answer: Option<i64>,
     relative_base_offset: u64,
-    memory: &mut HashMap<u64, i64>,
+    memory: &mut Memory,
     opcode_mode: &OpcodeMode,
     operation: fn(i64, i64) -> bool,
 ) {
@@ -422,6 +1169,9 @@ fn comparison(
             }
         },
     }
+    if memory.fault.is_some() {
+        return;
+    }
     match opcode_mode.answer_parameter_mode() {
         ParameterMode::Position => {
             if comparison_result {
@@ -430,7 +1180,8 @@ fn comparison(
                 memory_update(memory, answer, 0, 0)
             }
         }
-        ParameterMode::Immediate => panic!("Not supposed to be an immediate mode for writing"),
+        //there is nowhere to write to, the sanitizer reports it
+        ParameterMode::Immediate => {}
         ParameterMode::Relative => {
             if comparison_result {
                 memory_update(memory, answer, relative_base_offset, 1)
@@ -500,11 +1251,11 @@ impl OpcodeMode {
 
 fn run_instructions(
     instruction_pointer: &mut u64,
-    memory: &mut HashMap<u64, i64>,
+    memory: &mut Memory,
     relative_base_offset: u64,
     positions: &Positions,
     opcode_mode: &OpcodeMode,
-    operation: fn(i64, i64) -> i64,
+    operation: fn(i64, i64) -> Option<i64>,
 ) {
     let first_param = positions
         .first_param
@@ -526,17 +1277,20 @@ fn run_instructions(
         ParameterMode::Immediate => second_param,
         ParameterMode::Relative => memory_access(memory, second_param, relative_base_offset),
     };
-    match opcode_mode.answer_parameter_mode() {
-        ParameterMode::Position => {
-            memory_update(memory, answer, 0, operation(first_nmb, second_nmb))
+    let result = match operation(first_nmb, second_nmb) {
+        Some(result) => result,
+        None => {
+            if memory.fault.is_none() {
+                memory.fault = Some(FaultKind::Overflow);
+            }
+            return;
         }
-        ParameterMode::Immediate => panic!("wtf i thought there was no immediate mode for writing"),
-        ParameterMode::Relative => memory_update(
-            memory,
-            answer,
-            relative_base_offset,
-            operation(first_nmb, second_nmb),
-        ),
+    };
+    match opcode_mode.answer_parameter_mode() {
+        ParameterMode::Position => memory_update(memory, answer, 0, result),
+        //there is nowhere to write to, the sanitizer reports it
+        ParameterMode::Immediate => {}
+        ParameterMode::Relative => memory_update(memory, answer, relative_base_offset, result),
     }
     *instruction_pointer += INSTRUCTION_LENGTH;
 }
@@ -561,7 +1315,7 @@ impl Positions {
     }
 }
 
-fn determine_positions(instruction_pointer: u64, memory: &mut HashMap<u64, i64>) -> Positions {
+fn determine_positions(instruction_pointer: u64, memory: &mut Memory) -> Positions {
     let first_param = get_parameter_value(instruction_pointer, 1, memory);
     let second_param = get_parameter_value(instruction_pointer, 2, memory);
     let answer = get_parameter_value(instruction_pointer, 3, memory);
@@ -575,39 +1329,269 @@ fn determine_positions(instruction_pointer: u64, memory: &mut HashMap<u64, i64>)
 fn get_parameter_value(
     instruction_pointer: u64,
     position: u64,
-    memory: &mut HashMap<u64, i64>,
+    memory: &mut Memory,
 ) -> Option<i64> {
     let ins_length: u64 = memory
-        .keys()
         .len()
         .try_into()
         .expect("Expected the conversion to work");
     match instruction_pointer + position {
-        x if x < ins_length => Some(memory_access(memory, x as i64, 0)),
+        x if x < ins_length => Some(memory.fetch(x)),
         _ => None,
     }
 }
 
-pub fn load_program_input(file_name: &str) -> io::Result<Vec<i64>> {
-    let program_input = File::open(file_name)?;
-    let mut reader = BufReader::new(program_input);
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    opcode: OpCode,
+    parameters: Vec<(ParameterMode, i64)>,
+}
+
+impl Instruction {
+    pub(crate) fn new(opcode: OpCode, parameters: Vec<(ParameterMode, i64)>) -> Instruction {
+        Instruction { opcode, parameters }
+    }
+
+    pub(crate) fn opcode(&self) -> OpCode {
+        self.opcode
+    }
+
+    pub(crate) fn parameters(&self) -> &[(ParameterMode, i64)] {
+        &self.parameters
+    }
+
+    pub(crate) fn length(&self) -> u64 {
+        1 + self.parameters.len() as u64
+    }
+
+    //the cells decode_instruction would read this back from
+    pub(crate) fn encode(&self) -> Vec<i64> {
+        let mut code = self.opcode.number();
+        let mut place = 100;
+        for (mode, _) in &self.parameters {
+            code += place
+                * match mode {
+                    ParameterMode::Position => 0,
+                    ParameterMode::Immediate => 1,
+                    ParameterMode::Relative => 2,
+                };
+            place *= 10;
+        }
+        let mut cells = vec![code];
+        cells.extend(self.parameters.iter().map(|(_, value)| value));
+        cells
+    }
+}
+
+//position parameters in brackets, relative ones against rb
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.opcode.mnemonic())?;
+        for (idx, (mode, value)) in self.parameters.iter().enumerate() {
+            let separator = if idx == 0 { " " } else { ", " };
+            match mode {
+                ParameterMode::Position => write!(f, "{}[{}]", separator, value)?,
+                ParameterMode::Immediate => write!(f, "{}{}", separator, value)?,
+                ParameterMode::Relative if *value < 0 => write!(f, "{}[rb{}]", separator, value)?,
+                ParameterMode::Relative => write!(f, "{}[rb+{}]", separator, value)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn decode_instruction(program: &[i64], address: u64) -> Option<Instruction> {
+    let code = *program.get(address as usize)?;
+    if code.is_negative() {
+        return None;
+    }
+    let opcode = OpCode::from_number(code % 100)?;
+    let mut modes = code / 100;
+    let mut parameters = Vec::new();
+    for position in 1..=opcode.parameter_count() as u64 {
+        let mode = match modes % 10 {
+            0 => ParameterMode::Position,
+            1 => ParameterMode::Immediate,
+            2 => ParameterMode::Relative,
+            _ => return None,
+        };
+        modes /= 10;
+        parameters.push((mode, *program.get((address + position) as usize)?));
+    }
+    Some(Instruction { opcode, parameters })
+}
+
+//binary images start with the magic, then the version, the flags, the relative base
+//if the flags say there is one, the word count and a checksum of the words. all
+//numbers are varints, words are zigzag encoded first so small negatives stay short
+const IMAGE_MAGIC: &[u8] = b"ICIM";
+const IMAGE_VERSION: u8 = 1;
+const HAS_RELATIVE_BASE: u8 = 1;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ProgramImage {
+    pub program: Vec<i64>,
+    pub relative_base: Option<u64>,
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8)
+}
+
+fn read_varint(bytes: &[u8], position: &mut usize) -> Result<u64, String> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = *bytes.get(*position).ok_or("Truncated image")?;
+        *position += 1;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err("Varint longer than 64 bits".to_string())
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+//32 bit FNV-1a
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash: u32, &byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+impl ProgramImage {
+    pub fn is_image(bytes: &[u8]) -> bool {
+        bytes.starts_with(IMAGE_MAGIC)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut words = Vec::new();
+        for &value in &self.program {
+            write_varint(&mut words, zigzag(value));
+        }
+        let mut bytes = IMAGE_MAGIC.to_vec();
+        bytes.push(IMAGE_VERSION);
+        match self.relative_base {
+            Some(relative_base) => {
+                bytes.push(HAS_RELATIVE_BASE);
+                write_varint(&mut bytes, relative_base);
+            }
+            None => bytes.push(0),
+        }
+        write_varint(&mut bytes, self.program.len() as u64);
+        bytes.extend(&checksum(&words).to_le_bytes());
+        bytes.extend(words);
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<ProgramImage, String> {
+        if !ProgramImage::is_image(bytes) {
+            return Err("Not an intcode image".to_string());
+        }
+        let mut position = IMAGE_MAGIC.len();
+        let header = bytes.get(position..position + 2).ok_or("Truncated image")?;
+        let (version, flags) = (header[0], header[1]);
+        if version != IMAGE_VERSION {
+            return Err(format!("Unsupported image version {}", version));
+        }
+        if flags & !HAS_RELATIVE_BASE != 0 {
+            return Err(format!("Unknown image flags {:#x}", flags));
+        }
+        position += 2;
+        let relative_base = match flags & HAS_RELATIVE_BASE {
+            0 => None,
+            _ => Some(read_varint(bytes, &mut position)?),
+        };
+        let count = read_varint(bytes, &mut position)?;
+        let expected = bytes
+            .get(position..position + 4)
+            .ok_or("Truncated image")?
+            .try_into()
+            .map(u32::from_le_bytes)
+            .map_err(|_| "Truncated image")?;
+        position += 4;
+        if checksum(&bytes[position..]) != expected {
+            return Err("Image checksum mismatch".to_string());
+        }
+        //every word takes at least a byte, which bounds the allocation
+        if count > (bytes.len() - position) as u64 {
+            return Err("Truncated image".to_string());
+        }
+        let mut program = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            program.push(unzigzag(read_varint(bytes, &mut position)?));
+        }
+        if position != bytes.len() {
+            return Err("Trailing bytes after the image".to_string());
+        }
+        Ok(ProgramImage {
+            program,
+            relative_base,
+        })
+    }
+}
 
-    let mut buf = String::new();
-    reader.read_line(&mut buf)?;
-    let program = buf
+fn parse_program_text(text: &str) -> io::Result<Vec<i64>> {
+    text.lines()
+        .next()
+        .unwrap_or("")
         .trim()
         .split(',')
         .map(|s| {
-            s.parse::<i64>()
-                .unwrap_or_else(|_| panic!("Failed to parse: {}", s))
+            s.parse::<i64>().map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Failed to parse: {}", s),
+                )
+            })
         })
-        .collect();
-    Ok(program)
+        .collect()
+}
+
+//either format, text has no relative base
+pub fn load_image(file_name: &str) -> io::Result<ProgramImage> {
+    let bytes = fs::read(file_name)?;
+    if ProgramImage::is_image(&bytes) {
+        return ProgramImage::decode(&bytes)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err));
+    }
+    let text = String::from_utf8(bytes)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Not text or an intcode image"))?;
+    Ok(ProgramImage {
+        program: parse_program_text(&text)?,
+        relative_base: None,
+    })
+}
+
+pub fn write_image(file_name: &str, image: &ProgramImage) -> io::Result<()> {
+    fs::write(file_name, image.encode())
+}
+
+pub fn write_program_text(file_name: &str, program: &[i64]) -> io::Result<()> {
+    let program: Vec<String> = program.iter().map(|v| v.to_string()).collect();
+    fs::write(file_name, format!("{}\n", program.join(",")))
+}
+
+//reads binary images too, dropping their relative base
+pub fn load_program_input(file_name: &str) -> io::Result<Vec<i64>> {
+    load_image(file_name).map(|image| image.program)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::intcode_vectors::load_directory;
 
     #[test]
     fn test_process_opcode_and_param_mode() {
@@ -652,116 +1636,542 @@ mod tests {
     }
 
     #[test]
-    fn test_run_opcodes() {
-        let test_sets = [
-            (vec![3, 0, 4, 0, 99], vec![10]),
-            (vec![3, 3, 1101, 100, -1, 1, 4, 1, 99], vec![9]),
-        ];
+    fn test_decode_instruction() {
+        let program = vec![1002, 4, 3, 4, 33, 21107, -1, 8, 99];
+        let instruction = decode_instruction(&program, 0).unwrap();
+        assert_eq!(instruction.opcode(), OpCode::Multiply);
+        assert_eq!(
+            instruction.parameters(),
+            &[
+                (ParameterMode::Position, 4),
+                (ParameterMode::Immediate, 3),
+                (ParameterMode::Position, 4)
+            ]
+        );
+        assert_eq!(instruction.length(), 4);
 
-        for test_set in test_sets.iter() {
-            let mut comp = IntcodeComputer::new(&test_set.0);
-            comp.run(&mut vec![10]);
-            assert_eq!(comp.output(), &test_set.1)
+        let instruction = decode_instruction(&program, 5).unwrap();
+        assert_eq!(instruction.opcode(), OpCode::LessThan);
+        assert_eq!(instruction.parameters()[2].0, ParameterMode::Relative);
+
+        assert_eq!(decode_instruction(&program, 4), None);
+        assert_eq!(decode_instruction(&program, 7), None);
+        assert_eq!(decode_instruction(&program, 8).unwrap().length(), 1);
+
+        assert_eq!(
+            decode_instruction(&program, 0).unwrap().to_string(),
+            "mul [4], 3, [4]"
+        );
+        assert_eq!(
+            decode_instruction(&program, 5).unwrap().to_string(),
+            "lt -1, 8, [rb+99]"
+        );
+        assert_eq!(decode_instruction(&program, 8).unwrap().to_string(), "hlt");
+
+        assert_eq!(instruction.encode(), vec![21107, -1, 8, 99]);
+        assert_eq!(
+            decode_instruction(&[1001099], 0).unwrap().encode(),
+            vec![99]
+        );
+    }
+
+    #[test]
+    fn test_vectors() {
+        for (file, bundle) in load_directory("test_vectors").unwrap() {
+            let report = bundle.run();
+            assert!(report.is_success(), "{}\n{}", file, report);
         }
     }
 
     #[test]
-    fn test_conditional_opcodes() {
-        let eq_to = vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
-        let mut comp = IntcodeComputer::new(&eq_to);
-        comp.run(&mut vec![8]);
-        assert_eq!(comp.output(), &vec![1]);
+    fn test_step() {
+        let instructions = vec![1101, 2, 3, 7, 109, 4, 99, 0];
+        let mut comp = IntcodeComputer::new(&instructions);
+        comp.step(&mut vec![]);
+        assert_eq!(comp.instruction_pointer(), 4);
+        assert_eq!(comp.peek(7), 5);
+        assert_eq!(comp.state(), &ComputerState::Running);
 
-        comp.load_new_instructions(&eq_to);
-        comp.run(&mut vec![4]);
-        assert_eq!(comp.output(), &vec![0]);
+        comp.step(&mut vec![]);
+        assert_eq!(comp.instruction_pointer(), 6);
+        assert_eq!(comp.relative_base_offset(), 4);
 
-        let less_than = vec![3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8];
-        comp.load_new_instructions(&less_than);
-        comp.run(&mut vec![3]);
-        assert_eq!(comp.output(), &vec![1]);
+        comp.set_instruction_pointer(0);
+        comp.patch(1, 40);
+        comp.step(&mut vec![]);
+        assert_eq!(comp.peek(7), 43);
 
-        comp.load_new_instructions(&less_than);
-        comp.run(&mut vec![10]);
-        assert_eq!(comp.output(), &vec![0]);
+        comp.set_instruction_pointer(6);
+        comp.step(&mut vec![]);
+        assert_eq!(comp.state(), &ComputerState::Halted);
+        assert_eq!(comp.instruction_pointer(), 6);
+    }
 
-        let eq_to = vec![3, 3, 1108, -1, 8, 3, 4, 3, 99];
-        comp.load_new_instructions(&eq_to);
-        comp.run(&mut vec![8]);
-        assert_eq!(comp.output(), &vec![1]);
+    #[test]
+    fn test_run_for() {
+        let counter = vec![1001, 9, 1, 9, 1105, 1, 0, 99, 0, 0];
+        let mut comp = IntcodeComputer::new(&counter);
+        assert_eq!(comp.run_for(&mut vec![], 10), 10);
+        assert_eq!(comp.state(), &ComputerState::Running);
+        assert_eq!(comp.peek(9), 5);
 
-        comp.load_new_instructions(&eq_to);
-        comp.run(&mut vec![4]);
-        assert_eq!(comp.output(), &vec![0]);
+        comp.patch(5, 0);
+        assert_eq!(comp.run_for(&mut vec![], 10), 3);
+        assert_eq!(comp.state(), &ComputerState::Halted);
 
-        let less_than = vec![3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8];
-        comp.load_new_instructions(&less_than);
-        comp.run(&mut vec![3]);
-        assert_eq!(comp.output(), &vec![1]);
+        let echo = vec![3, 5, 4, 5, 99, 0];
+        let mut comp = IntcodeComputer::new(&echo);
+        assert_eq!(comp.run_for(&mut vec![], 10), 0);
+        assert_eq!(comp.state(), &ComputerState::Waiting);
+        assert_eq!(comp.run_for(&mut vec![8], 10), 3);
+        assert_eq!(comp.output(), &vec![8]);
+    }
 
-        comp.load_new_instructions(&less_than);
-        comp.run(&mut vec![10]);
-        assert_eq!(comp.output(), &vec![0]);
+    #[test]
+    fn test_malformed_programs() {
+        let fault = |program: &[i64]| {
+            let mut comp = IntcodeComputer::new(program);
+            comp.run(&mut vec![]);
+            comp.fault()
+                .map(|f| (f.kind().clone(), f.instruction_pointer()))
+        };
+        use FaultKind::*;
+        assert_eq!(fault(&[1101, 1]), Some((MissingOperand(2), 0)));
+        assert_eq!(fault(&[104, 1, 4]), Some((MissingOperand(3), 2)));
+        assert_eq!(fault(&[104, 1, 42]), Some((UndefinedInstruction(42), 2)));
+        assert_eq!(fault(&[-3]), Some((UndefinedInstruction(-3), 0)));
+        //halt takes no parameters, but a mode digit of 3 is still not a mode
+        assert_eq!(fault(&[399]), Some((UndefinedInstruction(399), 0)));
+        assert_eq!(fault(&[1, -1, 0, 0, 99]), Some((NegativeAddress(-1), 0)));
+        assert_eq!(
+            fault(&[109, -5, 204, -3, 99]),
+            Some((NegativeAddress(-3), 2))
+        );
+        assert_eq!(fault(&[1105, 1, -7, 99]), Some((NegativeAddress(-7), 0)));
+        assert_eq!(fault(&[1102, i64::MAX, 2, 0, 99]), Some((Overflow, 0)));
+        assert_eq!(
+            fault(&[109, i64::MAX, 109, i64::MAX, 109, 2, 99]),
+            Some((Overflow, 4))
+        );
+
+        //nothing is written by the instruction that faulted
+        let mut comp = IntcodeComputer::new(&[1101, i64::MAX, 1, 5, 99, 7]);
+        comp.run(&mut vec![]);
+        assert_eq!(comp.fault().map(Fault::kind), Some(&Overflow));
+        assert_eq!(comp.peek(5), 7);
     }
 
     #[test]
-    fn test_jump_opcodes() {
-        let jump = vec![3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
-        let mut comp = IntcodeComputer::new(&jump);
-        comp.run(&mut vec![8]);
-        assert_eq!(comp.output(), &vec![1]);
+    fn test_interrupt() {
+        //counts in cell 9 forever
+        let counter = vec![1001, 9, 1, 9, 1105, 1, 0, 99, 0, 0];
+        let mut comp = IntcodeComputer::new(&counter);
+        let interrupt = comp.interrupt_handle();
+        let running = std::thread::spawn(move || {
+            comp.run(&mut vec![]);
+            comp
+        });
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        interrupt.request();
+        let mut comp = running.join().unwrap();
+        assert_eq!(comp.state(), &ComputerState::Paused);
+        assert!(!interrupt.is_requested());
+        let count = comp.peek(9);
+        assert!(count > 0);
 
-        comp.load_new_instructions(&jump);
-        comp.run(&mut vec![0]);
-        assert_eq!(comp.output(), &vec![0]);
+        //resumes where it stopped, now falling through to the halt
+        comp.patch(5, 0);
+        comp.run(&mut vec![]);
+        assert_eq!(comp.state(), &ComputerState::Halted);
+        assert!(comp.peek(9) - count <= 1);
 
-        let jump = vec![3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1];
-        comp.load_new_instructions(&jump);
-        comp.run(&mut vec![8]);
-        assert_eq!(comp.output(), &vec![1]);
+        //a request made between runs pauses before the first instruction
+        comp.load_new_instructions(&counter);
+        interrupt.request();
+        comp.step(&mut vec![]);
+        assert_eq!(comp.state(), &ComputerState::Paused);
+        assert_eq!(comp.peek(9), 0);
+        comp.step(&mut vec![]);
+        assert_eq!(comp.peek(9), 1);
+    }
 
-        comp.load_new_instructions(&jump);
-        comp.run(&mut vec![0]);
-        assert_eq!(comp.output(), &vec![0]);
+    #[test]
+    fn test_instruction_set() {
+        use InstructionSet::*;
+        assert!(Day2.defines(1) && Day2.defines(2) && Day2.defines(99));
+        assert!(!Day2.defines(3) && !Day2.defines(1002) && !Day2.defines(0));
+        assert!(Day5.defines(1002) && Day5.defines(1105) && Day5.defines(8));
+        assert!(!Day5.defines(9) && !Day5.defines(204) && !Day5.defines(-1));
+        assert!(Day9.defines(109) && Day9.defines(21_107) && !Day9.defines(302));
+
+        let boost = load_program_input("boost_program.txt").unwrap();
+        let mut comp = IntcodeComputer::new(&boost);
+        comp.restrict(Day5);
+        comp.run(&mut vec![1]);
+        let fault = comp.fault().unwrap();
+        assert_eq!(
+            fault.kind(),
+            &FaultKind::UndefinedInstruction(comp.peek(fault.instruction_pointer()))
+        );
+        comp.load_new_instructions(&boost);
+        comp.restrict(Day9);
+        comp.run(&mut vec![1]);
+        assert_eq!(comp.output(), &vec![3_546_494_377]);
     }
 
     #[test]
-    fn test_all_opcodes() {
-        let prog = vec![
-            3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0,
-            0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
-            20, 1105, 1, 46, 98, 99,
+    fn test_sanitizer() {
+        let findings = |program: &[i64], input: &[i64]| {
+            let mut comp = IntcodeComputer::new(program);
+            comp.sanitize(SanitizerMode::Report);
+            comp.run(&mut input.iter().rev().copied().collect());
+            assert_eq!(comp.state(), &ComputerState::Halted);
+            comp.findings().to_vec()
+        };
+        let finding = |instruction_pointer, suspicion| Finding {
+            instruction_pointer,
+            suspicion,
+        };
+
+        let boost = load_program_input("boost_program.txt").unwrap();
+        assert_eq!(findings(&boost, &[1]), vec![]);
+        let diagnostic = load_program_input("diagnostic_program.txt").unwrap();
+        assert_eq!(findings(&diagnostic, &[5]), vec![]);
+
+        assert_eq!(
+            findings(&[4, 100, 99], &[]),
+            vec![finding(0, Suspicion::UninitialisedRead(100))]
+        );
+        //99 is stored at 8 as data, then jumped to
+        assert_eq!(
+            findings(&[1101, 0, 99, 8, 1105, 1, 8, 0, 0], &[]),
+            vec![finding(4, Suspicion::JumpIntoData(8))]
+        );
+        assert_eq!(
+            findings(&[109, 2, 109, -3, 99], &[]),
+            vec![finding(
+                2,
+                Suspicion::RelativeBaseSaturated {
+                    base: 2,
+                    adjustment: -3
+                }
+            )]
+        );
+        assert_eq!(
+            findings(&[103, 3, 4, 3, 99], &[4]),
+            vec![finding(0, Suspicion::WriteThroughImmediate)]
+        );
+
+        //reporting changes nothing about how the program runs: the arithmetic drops
+        //the write, input writes to the cell its parameter names
+        let immediate_writes = [
+            (vec![11101, 1, 1, 3, 104, 7, 99], vec![], (3, 3)),
+            (vec![11107, 1, 1, 3, 104, 7, 99], vec![], (3, 3)),
+            (vec![11108, 1, 1, 3, 104, 7, 99], vec![], (3, 3)),
+            (vec![103, 6, 104, 7, 99, 0, 0], vec![5], (6, 5)),
         ];
-        let mut comp = IntcodeComputer::new(&prog);
-        comp.run(&mut vec![4]);
-        assert_eq!(comp.output(), &vec![999]);
+        for (program, input, (address, stored)) in &immediate_writes {
+            for &sanitized in &[false, true] {
+                let mut comp = IntcodeComputer::new(program);
+                if sanitized {
+                    comp.sanitize(SanitizerMode::Report);
+                }
+                comp.run(&mut input.clone());
+                assert_eq!(comp.state(), &ComputerState::Halted);
+                assert_eq!(comp.output(), &vec![7]);
+                assert_eq!(comp.peek(*address), *stored);
+                if sanitized {
+                    assert_eq!(
+                        comp.findings(),
+                        &[finding(0, Suspicion::WriteThroughImmediate)]
+                    );
+                }
+            }
+        }
+
+        let mut comp = IntcodeComputer::new(&[104, 1, 11101, 1, 2, 3, 99]);
+        comp.sanitize(SanitizerMode::Stop);
+        comp.run(&mut vec![]);
+        assert_eq!(
+            comp.fault()
+                .map(|f| (f.kind().clone(), f.instruction_pointer())),
+            Some((FaultKind::Suspicious(Suspicion::WriteThroughImmediate), 2))
+        );
+        assert_eq!(comp.output(), &vec![1]);
+
+        let mut comp = IntcodeComputer::new(&[4, 100, 99]);
+        comp.sanitize(SanitizerMode::Stop);
+        comp.patch(100, 7);
+        comp.run(&mut vec![]);
+        assert_eq!(comp.state(), &ComputerState::Halted);
+        assert_eq!(comp.output(), &vec![7]);
+    }
+
+    #[test]
+    fn test_program_image() {
+        let boost = load_program_input("boost_program.txt").unwrap();
+        let image = ProgramImage {
+            program: boost.clone(),
+            relative_base: None,
+        };
+        let bytes = image.encode();
+        assert!(bytes.len() < fs::read("boost_program.txt").unwrap().len() / 2);
+        assert_eq!(ProgramImage::decode(&bytes), Ok(image));
 
-        comp.load_new_instructions(&prog);
-        comp.run(&mut vec![8]);
-        assert_eq!(comp.output(), &vec![1000]);
+        let image = ProgramImage {
+            program: vec![204, -1, 99, i64::MIN, i64::MAX],
+            relative_base: Some(3),
+        };
+        let bytes = image.encode();
+        assert_eq!(ProgramImage::decode(&bytes).as_ref(), Ok(&image));
+        let mut comp = IntcodeComputer::from_image(&ProgramImage {
+            program: vec![204, -1, 99, 42],
+            relative_base: Some(4),
+        });
+        comp.run(&mut vec![]);
+        assert_eq!(comp.output(), &vec![42]);
+
+        let mut corrupt = bytes.clone();
+        *corrupt.last_mut().unwrap() ^= 1;
+        assert_eq!(
+            ProgramImage::decode(&corrupt),
+            Err("Image checksum mismatch".to_string())
+        );
+        let mut future = bytes.clone();
+        future[4] = 2;
+        assert_eq!(
+            ProgramImage::decode(&future),
+            Err("Unsupported image version 2".to_string())
+        );
+        assert_eq!(
+            ProgramImage::decode(&bytes[..8]),
+            Err("Truncated image".to_string())
+        );
+        assert!(ProgramImage::decode(b"1,2,3").is_err());
+
+        let file = std::env::temp_dir().join("intcode_test_program_image.icim");
+        let file = file.to_str().unwrap();
+        write_image(file, &image).unwrap();
+        assert_eq!(load_image(file).unwrap(), image);
+        assert_eq!(load_program_input(file).unwrap(), image.program);
+        fs::remove_file(file).unwrap();
 
-        comp.load_new_instructions(&prog);
-        comp.run(&mut vec![11]);
-        assert_eq!(comp.output(), &vec![1001]);
+        let file = std::env::temp_dir().join("intcode_test_bad_program.txt");
+        let file = file.to_str().unwrap();
+        fs::write(file, "x,2\n").unwrap();
+        let err = load_image(file).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "Failed to parse: x");
+        fs::remove_file(file).unwrap();
     }
 
     #[test]
-    fn test_relative_base_offset() {
-        let instructions = vec![
-            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+    fn test_address_limit() {
+        let runaway_write = vec![109, 1_000_000, 21101, 1, 1, 0, 99];
+        let limits = Limits {
+            max_address: Some(10_000),
+            ..Limits::default()
+        };
+        let mut comp = IntcodeComputer::with_limits(&runaway_write, limits);
+        comp.run(&mut vec![]);
+        let fault = comp.fault().expect("Expected the computer to fault");
+        assert_eq!(fault.kind(), &FaultKind::AddressOutOfRange(1_000_000));
+        assert_eq!(fault.instruction_pointer(), 2);
+
+        comp.set_limits(Limits::default());
+        comp.load_new_instructions(&runaway_write);
+        comp.run(&mut vec![]);
+        assert_eq!(comp.state(), &ComputerState::Halted);
+
+        //the fetch faults, restricted or not
+        let limits = Limits {
+            max_address: Some(100),
+            ..Limits::default()
+        };
+        for &restricted in &[false, true] {
+            let mut comp = IntcodeComputer::with_limits(&[1105, 1, 500, 99], limits);
+            if restricted {
+                comp.restrict(InstructionSet::Day9);
+            }
+            comp.run(&mut vec![]);
+            let fault = comp.fault().expect("Expected the computer to fault");
+            assert_eq!(fault.kind(), &FaultKind::AddressOutOfRange(500));
+            assert_eq!(fault.instruction_pointer(), 500);
+        }
+        let mut comp = IntcodeComputer::with_limits(&[1105, 1, -1, 99], limits);
+        comp.run(&mut vec![]);
+        assert_eq!(
+            comp.fault().map(Fault::kind),
+            Some(&FaultKind::NegativeAddress(-1))
+        );
+
+        //operands read out of range are not acted on
+        let out_of_range_operands: [&[i64]; 4] = [
+            &[4, 500, 99],
+            &[1005, 500, 0, 99],
+            &[105, 1, 500, 99],
+            &[1007, 500, 1, 0, 99],
         ];
-        let mut comp = IntcodeComputer::new(&instructions);
+        for program in &out_of_range_operands {
+            let observed = Arc::new(Mutex::new(Recorder::default()));
+            let mut comp = IntcodeComputer::with_limits(program, limits);
+            comp.attach_observer(Box::new(observed.clone()));
+            comp.run(&mut vec![]);
+            let fault = comp.fault().expect("Expected the computer to fault");
+            assert_eq!(fault.kind(), &FaultKind::AddressOutOfRange(500));
+            assert_eq!(fault.instruction_pointer(), 0);
+            assert_eq!(comp.output(), &vec![]);
+            assert_eq!(comp.peek(0), program[0]);
+            let observed = observed.lock().unwrap();
+            assert!(observed.outputs.is_empty() && observed.writes.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_cell_limit() {
+        let fill_memory = vec![109, 1, 21101, 0, 0, 100, 1105, 1, 0];
+        let limits = Limits {
+            max_cells: Some(20),
+            ..Limits::default()
+        };
+        let mut comp = IntcodeComputer::with_limits(&fill_memory, limits);
         comp.run(&mut vec![]);
-        assert_eq!(comp.output(), &instructions);
+        let fault = comp.fault().expect("Expected the computer to fault");
+        assert_eq!(fault.kind(), &FaultKind::TooManyCells(20));
+        assert_eq!(fault.instruction_pointer(), 2);
 
-        let instructions = vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0];
-        comp.load_new_instructions(&instructions);
+        let jump_to_new_cell = vec![1105, 1, 10, 99];
+        let limits = Limits {
+            max_cells: Some(4),
+            ..Limits::default()
+        };
+        let mut comp = IntcodeComputer::with_limits(&jump_to_new_cell, limits);
+        comp.run(&mut vec![]);
+        let fault = comp.fault().expect("Expected the computer to fault");
+        assert_eq!(fault.kind(), &FaultKind::TooManyCells(4));
+        assert_eq!(fault.instruction_pointer(), 10);
+    }
+
+    #[test]
+    fn test_output_limit() {
+        let endless_output = vec![104, 7, 1105, 1, 0];
+        let limits = Limits {
+            max_output: Some(3),
+            ..Limits::default()
+        };
+        let mut comp = IntcodeComputer::with_limits(&endless_output, limits);
+        comp.run(&mut vec![]);
+        let fault = comp.fault().expect("Expected the computer to fault");
+        assert_eq!(fault.kind(), &FaultKind::OutputTooLong(3));
+        assert_eq!(fault.instruction_pointer(), 0);
+        assert_eq!(comp.output(), &vec![7, 7, 7]);
+    }
+
+    #[test]
+    fn test_write_protection() {
+        let self_modifying = vec![1101, 1, 1, 5, 104, 0, 99];
+        let mut comp = IntcodeComputer::new(&self_modifying);
+        comp.protect_program();
         comp.run(&mut vec![]);
-        assert_eq!(comp.output(), &vec![34915192 * 34915192]);
+        let fault = comp.fault().expect("Expected the computer to fault");
+        assert_eq!(fault.kind(), &FaultKind::ProtectedWrite(5));
+        assert_eq!(fault.instruction_pointer(), 0);
+        assert_eq!(comp.output(), &vec![]);
 
-        let instructions = vec![104, 1125899906842624, 99];
-        comp.load_new_instructions(&instructions);
+        comp.clear_protection();
+        comp.load_new_instructions(&self_modifying);
+        comp.protect(6..7, Protection::ReadOnly);
         comp.run(&mut vec![]);
-        assert_eq!(comp.output(), &vec![1125899906842624])
+        assert_eq!(comp.state(), &ComputerState::Halted);
+        assert_eq!(comp.output(), &vec![2]);
+
+        let gravity_assist = vec![1, 0, 0, 0, 99];
+        comp.clear_protection();
+        comp.load_new_instructions(&gravity_assist);
+        comp.protect(1..3, Protection::ReadOnly);
+        comp.patch(1, 4);
+        comp.patch(2, 4);
+        comp.run(&mut vec![]);
+        assert_eq!(comp.state(), &ComputerState::Halted);
+    }
+
+    #[test]
+    fn test_no_execute_protection() {
+        let jump_into_data = vec![1105, 1, 4, 99, 104, 5, 99];
+        let mut comp = IntcodeComputer::new(&jump_into_data);
+        comp.protect(4..7, Protection::NoExecute);
+        comp.run(&mut vec![]);
+        let fault = comp.fault().expect("Expected the computer to fault");
+        assert_eq!(fault.kind(), &FaultKind::ProtectedExecute(4));
+        assert_eq!(fault.instruction_pointer(), 4);
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        executed: Vec<u64>,
+        reads: Vec<(u64, i64)>,
+        writes: Vec<(u64, i64)>,
+        inputs: Vec<i64>,
+        outputs: Vec<i64>,
+        bases: Vec<(u64, u64)>,
+        states: Vec<ComputerState>,
+    }
+
+    impl Observer for Recorder {
+        fn before_execute(&mut self, instruction_pointer: u64, _instruction: i64) {
+            self.executed.push(instruction_pointer)
+        }
+
+        fn memory_read(&mut self, address: u64, value: i64) {
+            self.reads.push((address, value))
+        }
+
+        fn memory_write(&mut self, address: u64, value: i64) {
+            self.writes.push((address, value))
+        }
+
+        fn input_consumed(&mut self, value: i64) {
+            self.inputs.push(value)
+        }
+
+        fn output_produced(&mut self, value: i64) {
+            self.outputs.push(value)
+        }
+
+        fn relative_base_changed(&mut self, old_base: u64, new_base: u64) {
+            self.bases.push((old_base, new_base))
+        }
+
+        fn state_changed(&mut self, _old_state: &ComputerState, new_state: &ComputerState) {
+            self.states.push(new_state.clone())
+        }
+    }
+
+    #[test]
+    fn test_observer() {
+        let instructions = vec![109, 5, 203, 3, 204, 3, 99];
+        let recorder = Arc::new(Mutex::new(Recorder::default()));
+        let mut comp = IntcodeComputer::new(&instructions);
+        comp.attach_observer(Box::new(recorder.clone()));
+        comp.run(&mut vec![]);
+        comp.run(&mut vec![42]);
+        assert!(comp.detach_observer().is_some());
+
+        let recorder = recorder.lock().unwrap();
+        assert_eq!(recorder.executed, vec![0, 2, 2, 4, 6]);
+        assert_eq!(recorder.reads, vec![(8, 42)]);
+        assert_eq!(recorder.writes, vec![(8, 42)]);
+        assert_eq!(recorder.inputs, vec![42]);
+        assert_eq!(recorder.outputs, vec![42]);
+        assert_eq!(recorder.bases, vec![(0, 5)]);
+        assert_eq!(
+            recorder.states,
+            vec![
+                ComputerState::Running,
+                ComputerState::Waiting,
+                ComputerState::Running,
+                ComputerState::Halted
+            ]
+        );
     }
 }
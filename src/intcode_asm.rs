@@ -0,0 +1,389 @@
+use crate::intcode::{decode_instruction, Instruction, OpCode, ParameterMode};
+use std::{collections::HashMap, fmt, fmt::Write, fs, io};
+
+const OPCODES: [OpCode; 10] = [
+    OpCode::Add,
+    OpCode::Multiply,
+    OpCode::Input,
+    OpCode::Output,
+    OpCode::JumpIfTrue,
+    OpCode::JumpIfFalse,
+    OpCode::LessThan,
+    OpCode::Equals,
+    OpCode::AdjustRelativeBaseOffset,
+    OpCode::Halt,
+];
+const DATA_PER_LINE: usize = 8;
+
+#[derive(Debug, PartialEq)]
+pub struct AsmError {
+    line: usize,
+    message: String,
+}
+
+impl AsmError {
+    fn new(line: usize, message: &str) -> AsmError {
+        AsmError {
+            line,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)?;
+        Ok(())
+    }
+}
+
+enum Operand<'a> {
+    Position(&'a str),
+    Immediate(&'a str),
+    //the offset from rb, sign included
+    Relative(&'a str),
+}
+
+enum Statement<'a> {
+    Instruction(OpCode, Vec<Operand<'a>>),
+    Data(Vec<&'a str>),
+}
+
+impl<'a> Statement<'a> {
+    fn length(&self) -> u64 {
+        match self {
+            Statement::Instruction(_, operands) => 1 + operands.len() as u64,
+            Statement::Data(values) => values.len() as u64,
+        }
+    }
+}
+
+fn is_label(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && name != "rb"
+        && name != "data"
+}
+
+//the operand an instruction stores its result through
+fn write_operand(opcode: OpCode) -> Option<usize> {
+    match opcode {
+        OpCode::Add | OpCode::Multiply | OpCode::LessThan | OpCode::Equals => Some(2),
+        OpCode::Input => Some(0),
+        _ => None,
+    }
+}
+
+fn parse_operand(text: &str) -> Result<Operand<'_>, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err("Missing operand".to_string());
+    }
+    let inner = match text.strip_prefix('[') {
+        Some(rest) => rest
+            .strip_suffix(']')
+            .ok_or_else(|| format!("Unclosed bracket in '{}'", text))?
+            .trim(),
+        None => return Ok(Operand::Immediate(text)),
+    };
+    match inner.strip_prefix("rb") {
+        Some(offset) => {
+            let offset = offset.trim();
+            if offset.is_empty() {
+                Ok(Operand::Relative("0"))
+            } else if offset.starts_with('+') || offset.starts_with('-') {
+                Ok(Operand::Relative(offset))
+            } else {
+                Ok(Operand::Position(inner))
+            }
+        }
+        None => Ok(Operand::Position(inner)),
+    }
+}
+
+//numbers and labels joined by + and -
+fn evaluate(expression: &str, labels: &HashMap<&str, u64>) -> Result<i64, String> {
+    let mut total: i64 = 0;
+    let mut sign = 1;
+    let mut rest = expression.trim();
+    if let Some(stripped) = rest.strip_prefix('-') {
+        sign = -1;
+        rest = stripped;
+    } else if let Some(stripped) = rest.strip_prefix('+') {
+        rest = stripped;
+    }
+    loop {
+        let end = rest.find(['+', '-']).unwrap_or(rest.len());
+        let term = rest[..end].trim();
+        let value = match term.parse::<i64>() {
+            Ok(value) => value,
+            Err(_) if is_label(term) => match labels.get(term) {
+                Some(&address) => address as i64,
+                None => return Err(format!("Unknown label '{}'", term)),
+            },
+            Err(_) => return Err(format!("Bad value '{}' in '{}'", term, expression.trim())),
+        };
+        total = value
+            .checked_mul(sign)
+            .and_then(|v| total.checked_add(v))
+            .ok_or_else(|| format!("'{}' overflows", expression.trim()))?;
+        if end == rest.len() {
+            return Ok(total);
+        }
+        sign = if rest[end..].starts_with('-') { -1 } else { 1 };
+        rest = &rest[end + 1..];
+    }
+}
+
+fn parse_statement(text: &str) -> Result<Statement<'_>, String> {
+    let (word, rest) = match text.find(char::is_whitespace) {
+        Some(idx) => (&text[..idx], text[idx..].trim()),
+        None => (text, ""),
+    };
+    let operands: Vec<&str> = if rest.is_empty() {
+        Vec::new()
+    } else {
+        rest.split(',').map(str::trim).collect()
+    };
+    if word == "data" {
+        if operands.is_empty() {
+            return Err("data needs at least one value".to_string());
+        }
+        return Ok(Statement::Data(operands));
+    }
+    let opcode = OPCODES
+        .iter()
+        .find(|o| o.mnemonic() == word)
+        .copied()
+        .ok_or_else(|| format!("Unknown mnemonic '{}'", word))?;
+    if operands.len() != opcode.parameter_count() {
+        return Err(format!(
+            "{} takes {} operands, got {}",
+            word,
+            opcode.parameter_count(),
+            operands.len()
+        ));
+    }
+    if let Some(slot) = write_operand(opcode) {
+        if let Operand::Immediate(text) = parse_operand(operands[slot])? {
+            return Err(format!(
+                "Write operand must be [addr] or [rb±n], got '{}'",
+                text
+            ));
+        }
+    }
+    let operands = operands
+        .into_iter()
+        .map(parse_operand)
+        .collect::<Result<_, _>>()?;
+    Ok(Statement::Instruction(opcode, operands))
+}
+
+//one instruction or data line each, in the syntax instructions are displayed in.
+//lines can start with labels like `loop:` and the address the disassembler puts there,
+//anything after # is a comment
+pub fn assemble(source: &str) -> Result<Vec<i64>, AsmError> {
+    let mut labels = HashMap::new();
+    let mut statements = Vec::new();
+    let mut address = 0;
+    for (idx, line) in source.lines().enumerate() {
+        let number = idx + 1;
+        let mut text = line.split('#').next().unwrap_or("").trim();
+        while let Some(colon) = text.find(':') {
+            let prefix = text[..colon].trim();
+            if let Ok(expected) = prefix.parse::<u64>() {
+                if expected != address {
+                    return Err(AsmError::new(
+                        number,
+                        &format!("Expected address {}, this is {}", expected, address),
+                    ));
+                }
+            } else if is_label(prefix) {
+                if labels.insert(prefix, address).is_some() {
+                    return Err(AsmError::new(
+                        number,
+                        &format!("Label '{}' is defined twice", prefix),
+                    ));
+                }
+            } else {
+                return Err(AsmError::new(number, &format!("Bad label '{}'", prefix)));
+            }
+            text = text[colon + 1..].trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+        let statement = parse_statement(text).map_err(|err| AsmError::new(number, &err))?;
+        address += statement.length();
+        statements.push((number, statement));
+    }
+
+    let mut program = Vec::new();
+    for (number, statement) in statements {
+        let value = |expression: &str| {
+            evaluate(expression, &labels).map_err(|err| AsmError::new(number, &err))
+        };
+        match statement {
+            Statement::Data(values) => {
+                for v in values {
+                    program.push(value(v)?);
+                }
+            }
+            Statement::Instruction(opcode, operands) => {
+                let parameters = operands
+                    .iter()
+                    .map(|operand| {
+                        Ok(match operand {
+                            Operand::Position(e) => (ParameterMode::Position, value(e)?),
+                            Operand::Immediate(e) => (ParameterMode::Immediate, value(e)?),
+                            Operand::Relative(e) => (ParameterMode::Relative, value(e)?),
+                        })
+                    })
+                    .collect::<Result<_, AsmError>>()?;
+                program.extend(Instruction::new(opcode, parameters).encode());
+            }
+        }
+    }
+    Ok(program)
+}
+
+pub fn assemble_file(source_file: &str) -> io::Result<Vec<i64>> {
+    let source = fs::read_to_string(source_file)?;
+    assemble(&source).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))
+}
+
+fn write_data(text: &mut String, address: usize, values: &[i64], width: usize) {
+    if values.is_empty() {
+        return;
+    }
+    let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+    writeln!(
+        text,
+        "{:0>w$}: data {}",
+        address,
+        values.join(", "),
+        w = width
+    )
+    .expect("Writing to a string cannot fail");
+}
+
+//a linear sweep, cells that would not assemble back to the same values are left as data
+pub fn disassemble(program: &[i64]) -> String {
+    let width = program.len().to_string().len().max(4);
+    let mut text = String::new();
+    let mut data_start = 0;
+    let mut data = Vec::new();
+    let mut address = 0;
+    while address < program.len() {
+        let instruction = decode_instruction(program, address as u64)
+            .filter(|i| program[address..address + i.length() as usize] == i.encode()[..])
+            .filter(|i| {
+                write_operand(i.opcode())
+                    .is_none_or(|slot| i.parameters()[slot].0 != ParameterMode::Immediate)
+            });
+        match instruction {
+            Some(instruction) => {
+                write_data(&mut text, data_start, &data, width);
+                data.clear();
+                writeln!(text, "{:0>w$}: {}", address, instruction, w = width)
+                    .expect("Writing to a string cannot fail");
+                address += instruction.length() as usize;
+            }
+            None => {
+                if data.is_empty() {
+                    data_start = address;
+                }
+                data.push(program[address]);
+                if data.len() == DATA_PER_LINE {
+                    write_data(&mut text, data_start, &data, width);
+                    data.clear();
+                }
+                address += 1;
+            }
+        }
+    }
+    write_data(&mut text, data_start, &data, width);
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{load_program_input, IntcodeComputer};
+
+    #[test]
+    fn test_assemble() {
+        let source = "
+            # counts down from the input
+                in [count]
+            loop:
+                out [count]
+                add [count], -1, [count]
+                jnz [count], loop
+                hlt
+            count: data 0
+        ";
+        let program = assemble(source).unwrap();
+        assert_eq!(
+            program,
+            vec![3, 12, 4, 12, 1001, 12, -1, 12, 1005, 12, 2, 99, 0]
+        );
+        let mut comp = IntcodeComputer::new(&program);
+        comp.run(&mut vec![3]);
+        assert_eq!(comp.output(), &vec![3, 2, 1]);
+
+        assert_eq!(
+            assemble("arb 5\nout [rb-2]\nadd [rb], [end+1], [7]\nend: hlt").unwrap(),
+            vec![109, 5, 204, -2, 201, 0, 9, 7, 99]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |source| assemble(source).unwrap_err().to_string();
+        assert_eq!(error("hlt\nnop"), "line 2: Unknown mnemonic 'nop'");
+        assert_eq!(error("add 1, 2"), "line 1: add takes 3 operands, got 2");
+        assert_eq!(error("jz 0, nowhere"), "line 1: Unknown label 'nowhere'");
+        assert_eq!(
+            error("a: hlt\na: hlt"),
+            "line 2: Label 'a' is defined twice"
+        );
+        assert_eq!(error("0002: hlt"), "line 1: Expected address 2, this is 0");
+        assert_eq!(error("out [4"), "line 1: Unclosed bracket in '[4'");
+        assert_eq!(
+            error("add 1, 2, 3"),
+            "line 1: Write operand must be [addr] or [rb±n], got '3'"
+        );
+        assert_eq!(
+            error("hlt\nlt [1], [2], end"),
+            "line 2: Write operand must be [addr] or [rb±n], got 'end'"
+        );
+        for source in &["mul 1, 2, -1", "eq [0], 0, 4", "in 7"] {
+            assert!(error(source).starts_with("line 1: Write operand must be"));
+        }
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let program = vec![1101, 2, 3, 12, 4, 12, 99, 72, 33, 1001099, 0, 0, 0];
+        assert_eq!(
+            disassemble(&program),
+            "0000: add 2, 3, [12]\n\
+             0004: out [12]\n\
+             0006: hlt\n\
+             0007: data 72, 33, 1001099, 0, 0, 0\n"
+        );
+        //immediate writes would not assemble, so they stay data
+        assert_eq!(
+            disassemble(&[11101, 1, 1, 3]),
+            "0000: data 11101, 1, 1, 3\n"
+        );
+        assert_eq!(disassemble(&[103, 5]), "0000: data 103, 5\n");
+
+        let boost = load_program_input("boost_program.txt").unwrap();
+        assert_eq!(assemble(&disassemble(&boost)).unwrap(), boost);
+    }
+}
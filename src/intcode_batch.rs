@@ -0,0 +1,326 @@
+use crate::intcode::{ComputerState, IntcodeComputer};
+use std::{
+    fmt::Write,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::channel,
+        Arc,
+    },
+    thread,
+};
+
+//a row that runs this long is taken to be stuck in a loop
+const MAX_STEPS: u64 = 10_000_000;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct BatchRow {
+    pub input: Vec<i64>,
+    pub patches: Vec<(u64, i64)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchResult {
+    pub output: Vec<i64>,
+    //running means the row was stopped at the step limit
+    pub state: ComputerState,
+    pub steps: u64,
+    //the watched cells, in the order they were given
+    pub memory: Vec<i64>,
+}
+
+enum Column {
+    Input,
+    Patch(u64),
+}
+
+fn parse_column(name: &str) -> Column {
+    name.strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+        .and_then(|address| address.trim().parse().ok())
+        .map_or(Column::Input, Column::Patch)
+}
+
+//one row of comma separated input values per line. if the first line is not all
+//numbers it is a header, where a `[N]` column patches cell N instead of being input
+pub fn parse_rows(csv: &str) -> Result<Vec<BatchRow>, String> {
+    let mut lines = csv
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .peekable();
+    let mut columns = None;
+    if let Some((_, first)) = lines.peek() {
+        if first.split(',').any(|v| v.trim().parse::<i64>().is_err()) {
+            columns = Some(first.split(',').map(|c| parse_column(c.trim())).collect());
+            lines.next();
+        }
+    }
+    let columns: Option<Vec<Column>> = columns;
+    lines
+        .map(|(idx, line)| {
+            let values = line
+                .split(',')
+                .map(|v| {
+                    v.trim()
+                        .parse::<i64>()
+                        .map_err(|_| format!("line {}: Bad value '{}'", idx + 1, v.trim()))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let columns = match &columns {
+                None => {
+                    return Ok(BatchRow {
+                        input: values,
+                        patches: Vec::new(),
+                    })
+                }
+                Some(columns) => columns,
+            };
+            if values.len() != columns.len() {
+                return Err(format!(
+                    "line {}: Expected {} values, got {}",
+                    idx + 1,
+                    columns.len(),
+                    values.len()
+                ));
+            }
+            let mut row = BatchRow::default();
+            for (column, value) in columns.iter().zip(values) {
+                match column {
+                    Column::Input => row.input.push(value),
+                    Column::Patch(address) => row.patches.push((*address, value)),
+                }
+            }
+            Ok(row)
+        })
+        .collect()
+}
+
+pub struct Batch {
+    program: Arc<Vec<i64>>,
+    workers: usize,
+    max_steps: u64,
+    watch: Vec<u64>,
+}
+
+impl Batch {
+    pub fn new(program: &[i64]) -> Batch {
+        Batch {
+            program: Arc::new(program.to_vec()),
+            workers: thread::available_parallelism().map_or(1, |n| n.get()),
+            max_steps: MAX_STEPS,
+            watch: Vec::new(),
+        }
+    }
+
+    pub fn set_workers(&mut self, workers: usize) {
+        self.workers = workers.max(1);
+    }
+
+    pub fn set_max_steps(&mut self, max_steps: u64) {
+        self.max_steps = max_steps;
+    }
+
+    //cells whose final values are reported for every row
+    pub fn watch(&mut self, address: u64) {
+        self.watch.push(address);
+    }
+
+    pub fn watched(&self) -> &[u64] {
+        &self.watch
+    }
+
+    //each row runs on a fresh computer, the results come back in row order
+    pub fn run(&self, rows: &[BatchRow]) -> Vec<BatchResult> {
+        let rows = Arc::new(rows.to_vec());
+        let next = Arc::new(AtomicUsize::new(0));
+        let (sender, receiver) = channel();
+        let handles: Vec<_> = (0..self.workers.min(rows.len()))
+            .map(|_| {
+                let program = Arc::clone(&self.program);
+                let rows = Arc::clone(&rows);
+                let next = Arc::clone(&next);
+                let sender = sender.clone();
+                let max_steps = self.max_steps;
+                let watch = self.watch.clone();
+                thread::spawn(move || loop {
+                    let idx = next.fetch_add(1, Ordering::Relaxed);
+                    let row = match rows.get(idx) {
+                        Some(row) => row,
+                        None => break,
+                    };
+                    let result = run_row(&program, row, max_steps, &watch);
+                    if sender.send((idx, result)).is_err() {
+                        break;
+                    }
+                })
+            })
+            .collect();
+        drop(sender);
+
+        let mut results: Vec<Option<BatchResult>> = vec![None; rows.len()];
+        for (idx, result) in receiver {
+            results[idx] = Some(result);
+        }
+        for handle in handles {
+            handle.join().expect("Batch worker panicked");
+        }
+        results
+            .into_iter()
+            .map(|r| r.expect("Every row has a result"))
+            .collect()
+    }
+}
+
+fn run_row(program: &[i64], row: &BatchRow, max_steps: u64, watch: &[u64]) -> BatchResult {
+    let mut comp = IntcodeComputer::new(program);
+    for &(address, value) in &row.patches {
+        comp.patch(address, value);
+    }
+    let mut input: Vec<i64> = row.input.iter().rev().copied().collect();
+    let steps = comp.run_for(&mut input, max_steps);
+    BatchResult {
+        output: comp.output().clone(),
+        state: comp.state().clone(),
+        steps,
+        memory: watch.iter().map(|&address| comp.peek(address)).collect(),
+    }
+}
+
+fn join<T: ToString>(values: &[T]) -> String {
+    let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+    values.join(" ")
+}
+
+fn state_name(state: &ComputerState) -> String {
+    match state {
+        ComputerState::Halted => "halted".to_string(),
+        ComputerState::Waiting => "waiting".to_string(),
+        ComputerState::Paused => "paused".to_string(),
+        ComputerState::Running => "stopped".to_string(),
+        ComputerState::Faulted(fault) => format!(
+            "fault {:?} at {}",
+            fault.kind(),
+            fault.instruction_pointer()
+        ),
+    }
+}
+
+//lists inside a field are separated by spaces, so no field ever needs quoting
+pub fn write_csv(rows: &[BatchRow], results: &[BatchResult], watch: &[u64]) -> String {
+    let mut csv = String::from("input,patches,output,state,steps");
+    for address in watch {
+        write!(csv, ",[{}]", address).expect("Writing to a string cannot fail");
+    }
+    csv.push('\n');
+    for (row, result) in rows.iter().zip(results) {
+        let patches: Vec<String> = row
+            .patches
+            .iter()
+            .map(|(address, value)| format!("{}={}", address, value))
+            .collect();
+        write!(
+            csv,
+            "{},{},{},{},{}",
+            join(&row.input),
+            patches.join(" "),
+            join(&result.output),
+            state_name(&result.state),
+            result.steps
+        )
+        .expect("Writing to a string cannot fail");
+        for value in &result.memory {
+            write!(csv, ",{}", value).expect("Writing to a string cannot fail");
+        }
+        csv.push('\n');
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::load_program_input;
+
+    #[test]
+    fn test_parse_rows() {
+        let rows = parse_rows("1,2\n\n3,4\n").unwrap();
+        assert_eq!(rows[1].input, vec![3, 4]);
+        assert!(rows[1].patches.is_empty());
+
+        let rows = parse_rows("[1], [2], phase\n12,2,5\n").unwrap();
+        assert_eq!(
+            rows,
+            vec![BatchRow {
+                input: vec![5],
+                patches: vec![(1, 12), (2, 2)],
+            }]
+        );
+
+        assert_eq!(
+            parse_rows("a,b\n1,2\n3\n").unwrap_err(),
+            "line 3: Expected 2 values, got 1"
+        );
+        assert_eq!(parse_rows("1,2\n3,x").unwrap_err(), "line 2: Bad value 'x'");
+    }
+
+    #[test]
+    fn test_noun_verb_sweep() {
+        let program = load_program_input("program.txt").unwrap();
+        let mut csv = String::from("[1],[2]\n");
+        for noun in 0..100 {
+            for verb in 0..100 {
+                writeln!(csv, "{},{}", noun, verb).unwrap();
+            }
+        }
+        let rows = parse_rows(&csv).unwrap();
+        let mut batch = Batch::new(&program);
+        batch.set_workers(4);
+        batch.watch(0);
+        let results = batch.run(&rows);
+        assert_eq!(results.len(), 10_000);
+        let found: Vec<&BatchRow> = rows
+            .iter()
+            .zip(&results)
+            .filter(|(_, result)| result.memory == vec![19_690_720])
+            .map(|(row, _)| row)
+            .collect();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].patches, vec![(1, 69), (2, 79)]);
+    }
+
+    #[test]
+    fn test_write_csv() {
+        //echoes one input, then loops on the second
+        let program = vec![3, 20, 4, 20, 3, 20, 1005, 20, 6, 99];
+        let rows = parse_rows("5\n5,0\n5,1\n").unwrap();
+        let mut batch = Batch::new(&program);
+        batch.set_max_steps(50);
+        batch.watch(20);
+        let results = batch.run(&rows);
+        assert_eq!(
+            write_csv(&rows, &results, batch.watched()),
+            "input,patches,output,state,steps,[20]\n\
+             5,,5,waiting,2,5\n\
+             5 0,,5,halted,5,0\n\
+             5 1,,5,stopped,50,1\n"
+        );
+    }
+
+    #[test]
+    fn test_faulting_row() {
+        //prints the input, then reads a negative address unless it was 0
+        let program = vec![3, 20, 4, 20, 1006, 20, 11, 1, -1, 1, 3, 99];
+        let rows = parse_rows("0\n1\n0\n").unwrap();
+        let mut batch = Batch::new(&program);
+        batch.set_workers(2);
+        let results = batch.run(&rows);
+        assert_eq!(results[1].output, vec![1]);
+        assert_eq!(
+            write_csv(&rows, &results, batch.watched()),
+            "input,patches,output,state,steps\n\
+             0,,0,halted,4\n\
+             1,,1,fault NegativeAddress(-1) at 7,3\n\
+             0,,0,halted,4\n"
+        );
+    }
+}
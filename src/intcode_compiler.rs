@@ -0,0 +1,972 @@
+use std::{collections::HashMap, fmt, fs, io};
+
+const RETURN_ADDRESS_SLOT: i64 = 0;
+const RETURN_VALUE_SLOT: i64 = 1;
+const FIRST_PARAMETER_SLOT: i64 = 2;
+
+#[derive(Debug, PartialEq)]
+pub struct CompileError {
+    line: usize,
+    message: String,
+}
+
+impl CompileError {
+    fn new(line: usize, message: &str) -> CompileError {
+        CompileError {
+            line,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Identifier(String),
+    Symbol(&'static str),
+}
+
+const SYMBOLS: [&str; 17] = [
+    "==", "!=", "<=", ">=", "<", ">", "=", "+", "-", "*", "(", ")", "{", "}", ",", ";", "!",
+];
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, CompileError> {
+    let mut tokens = Vec::new();
+    for (idx, line) in source.lines().enumerate() {
+        let line_number = idx + 1;
+        let line = match line.find("//") {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+        let chars: Vec<char> = line.chars().collect();
+        let mut position = 0;
+        while position < chars.len() {
+            let ch = chars[position];
+            if ch.is_whitespace() {
+                position += 1;
+            } else if ch.is_ascii_digit() {
+                let start = position;
+                while position < chars.len() && chars[position].is_ascii_digit() {
+                    position += 1;
+                }
+                let digits: String = chars[start..position].iter().collect();
+                let number = digits
+                    .parse::<i64>()
+                    .map_err(|_| CompileError::new(line_number, "Number is too large"))?;
+                tokens.push((Token::Number(number), line_number));
+            } else if ch.is_alphabetic() || ch == '_' {
+                let start = position;
+                while position < chars.len()
+                    && (chars[position].is_alphanumeric() || chars[position] == '_')
+                {
+                    position += 1;
+                }
+                let name: String = chars[start..position].iter().collect();
+                tokens.push((Token::Identifier(name), line_number));
+            } else {
+                let rest: String = chars[position..].iter().collect();
+                match SYMBOLS.iter().find(|symbol| rest.starts_with(*symbol)) {
+                    Some(symbol) => {
+                        tokens.push((Token::Symbol(symbol), line_number));
+                        position += symbol.len();
+                    }
+                    None => {
+                        return Err(CompileError::new(
+                            line_number,
+                            &format!("Unexpected character '{}'", ch),
+                        ))
+                    }
+                }
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+    LessOrEqual,
+    GreaterOrEqual,
+}
+
+#[derive(Debug, PartialEq)]
+enum Expression {
+    Number(i64),
+    Variable(String, usize),
+    Input,
+    Call(String, Vec<Expression>, usize),
+    Negate(Box<Expression>),
+    Not(Box<Expression>),
+    Binary(BinaryOperator, Box<Expression>, Box<Expression>),
+}
+
+#[derive(Debug, PartialEq)]
+enum Statement {
+    Let(String, Expression, usize),
+    Assign(String, Expression, usize),
+    If(Expression, Vec<Statement>, Vec<Statement>),
+    While(Expression, Vec<Statement>),
+    Output(Expression),
+    Return(Option<Expression>),
+    Expression(Expression),
+}
+
+#[derive(Debug)]
+struct Function {
+    name: String,
+    parameters: Vec<String>,
+    body: Vec<Statement>,
+    line: usize,
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+}
+
+impl Parser {
+    fn line(&self) -> usize {
+        match self.tokens.get(self.position) {
+            Some((_, line)) => *line,
+            None => self.tokens.last().map(|(_, line)| *line).unwrap_or(1),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.position += 1;
+        token
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T, CompileError> {
+        Err(CompileError::new(self.line(), message))
+    }
+
+    fn at_symbol(&self, symbol: &str) -> bool {
+        self.peek() == Some(&Token::Symbol(symbol_str(symbol)))
+    }
+
+    fn at_keyword(&self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Identifier(name)) => name == keyword,
+            _ => false,
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), CompileError> {
+        if self.at_symbol(symbol) {
+            self.position += 1;
+            Ok(())
+        } else {
+            self.error(&format!("Expected '{}'", symbol))
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), CompileError> {
+        if self.at_keyword(keyword) {
+            self.position += 1;
+            Ok(())
+        } else {
+            self.error(&format!("Expected '{}'", keyword))
+        }
+    }
+
+    fn expect_identifier(&mut self) -> Result<String, CompileError> {
+        match self.peek() {
+            Some(Token::Identifier(name)) if !is_keyword(name) => {
+                let name = name.clone();
+                self.position += 1;
+                Ok(name)
+            }
+            _ => self.error("Expected a name"),
+        }
+    }
+
+    fn functions(&mut self) -> Result<Vec<Function>, CompileError> {
+        let mut functions = Vec::new();
+        while self.peek().is_some() {
+            let line = self.line();
+            self.expect_keyword("fn")?;
+            let name = self.expect_identifier()?;
+            self.expect_symbol("(")?;
+            let mut parameters = Vec::new();
+            while !self.at_symbol(")") {
+                if !parameters.is_empty() {
+                    self.expect_symbol(",")?;
+                }
+                parameters.push(self.expect_identifier()?);
+            }
+            self.expect_symbol(")")?;
+            let body = self.block()?;
+            functions.push(Function {
+                name,
+                parameters,
+                body,
+                line,
+            });
+        }
+        Ok(functions)
+    }
+
+    fn block(&mut self) -> Result<Vec<Statement>, CompileError> {
+        self.expect_symbol("{")?;
+        let mut statements = Vec::new();
+        while !self.at_symbol("}") {
+            if self.peek().is_none() {
+                return self.error("Expected '}'");
+            }
+            statements.push(self.statement()?);
+        }
+        self.expect_symbol("}")?;
+        Ok(statements)
+    }
+
+    fn statement(&mut self) -> Result<Statement, CompileError> {
+        let line = self.line();
+        if self.at_keyword("let") {
+            self.position += 1;
+            let name = self.expect_identifier()?;
+            self.expect_symbol("=")?;
+            let value = self.expression()?;
+            self.expect_symbol(";")?;
+            Ok(Statement::Let(name, value, line))
+        } else if self.at_keyword("if") {
+            self.position += 1;
+            let condition = self.expression()?;
+            let then_block = self.block()?;
+            let else_block = if self.at_keyword("else") {
+                self.position += 1;
+                if self.at_keyword("if") {
+                    vec![self.statement()?]
+                } else {
+                    self.block()?
+                }
+            } else {
+                Vec::new()
+            };
+            Ok(Statement::If(condition, then_block, else_block))
+        } else if self.at_keyword("while") {
+            self.position += 1;
+            let condition = self.expression()?;
+            let body = self.block()?;
+            Ok(Statement::While(condition, body))
+        } else if self.at_keyword("output") {
+            self.position += 1;
+            self.expect_symbol("(")?;
+            let value = self.expression()?;
+            self.expect_symbol(")")?;
+            self.expect_symbol(";")?;
+            Ok(Statement::Output(value))
+        } else if self.at_keyword("return") {
+            self.position += 1;
+            let value = if self.at_symbol(";") {
+                None
+            } else {
+                Some(self.expression()?)
+            };
+            self.expect_symbol(";")?;
+            Ok(Statement::Return(value))
+        } else {
+            let assignment = match (self.peek(), self.tokens.get(self.position + 1)) {
+                (Some(Token::Identifier(name)), Some((Token::Symbol("="), _))) => {
+                    Some(name.clone())
+                }
+                _ => None,
+            };
+            let statement = match assignment {
+                Some(name) => {
+                    self.position += 2;
+                    Statement::Assign(name, self.expression()?, line)
+                }
+                None => Statement::Expression(self.expression()?),
+            };
+            self.expect_symbol(";")?;
+            Ok(statement)
+        }
+    }
+
+    fn expression(&mut self) -> Result<Expression, CompileError> {
+        let left = self.sum()?;
+        let operator = match self.peek() {
+            Some(Token::Symbol("==")) => BinaryOperator::Equal,
+            Some(Token::Symbol("!=")) => BinaryOperator::NotEqual,
+            Some(Token::Symbol("<")) => BinaryOperator::Less,
+            Some(Token::Symbol(">")) => BinaryOperator::Greater,
+            Some(Token::Symbol("<=")) => BinaryOperator::LessOrEqual,
+            Some(Token::Symbol(">=")) => BinaryOperator::GreaterOrEqual,
+            _ => return Ok(left),
+        };
+        self.position += 1;
+        let right = self.sum()?;
+        Ok(Expression::Binary(
+            operator,
+            Box::new(left),
+            Box::new(right),
+        ))
+    }
+
+    fn sum(&mut self) -> Result<Expression, CompileError> {
+        let mut left = self.product()?;
+        loop {
+            let operator = match self.peek() {
+                Some(Token::Symbol("+")) => BinaryOperator::Add,
+                Some(Token::Symbol("-")) => BinaryOperator::Subtract,
+                _ => return Ok(left),
+            };
+            self.position += 1;
+            let right = self.product()?;
+            left = Expression::Binary(operator, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expression, CompileError> {
+        let mut left = self.unary()?;
+        while self.at_symbol("*") {
+            self.position += 1;
+            let right = self.unary()?;
+            left = Expression::Binary(BinaryOperator::Multiply, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expression, CompileError> {
+        if self.at_symbol("-") {
+            self.position += 1;
+            return match self.unary()? {
+                Expression::Number(n) => Ok(Expression::Number(-n)),
+                expression => Ok(Expression::Negate(Box::new(expression))),
+            };
+        }
+        if self.at_symbol("!") {
+            self.position += 1;
+            return Ok(Expression::Not(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expression, CompileError> {
+        let line = self.line();
+        match self.next() {
+            Some(Token::Number(n)) => Ok(Expression::Number(n)),
+            Some(Token::Symbol("(")) => {
+                let expression = self.expression()?;
+                self.expect_symbol(")")?;
+                Ok(expression)
+            }
+            Some(Token::Identifier(name)) if name == "input" => {
+                self.expect_symbol("(")?;
+                self.expect_symbol(")")?;
+                Ok(Expression::Input)
+            }
+            Some(Token::Identifier(name)) if !is_keyword(&name) => {
+                if !self.at_symbol("(") {
+                    return Ok(Expression::Variable(name, line));
+                }
+                self.position += 1;
+                let mut arguments = Vec::new();
+                while !self.at_symbol(")") {
+                    if !arguments.is_empty() {
+                        self.expect_symbol(",")?;
+                    }
+                    arguments.push(self.expression()?);
+                }
+                self.expect_symbol(")")?;
+                Ok(Expression::Call(name, arguments, line))
+            }
+            _ => Err(CompileError::new(line, "Expected an expression")),
+        }
+    }
+}
+
+fn symbol_str(symbol: &str) -> &'static str {
+    SYMBOLS
+        .iter()
+        .find(|s| **s == symbol)
+        .expect("Only known symbols are looked up")
+}
+
+fn is_keyword(name: &str) -> bool {
+    matches!(
+        name,
+        "fn" | "let" | "if" | "else" | "while" | "return" | "output" | "input"
+    )
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Word {
+    Value(i64),
+    Label(usize),
+    Frame(i64, i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operand {
+    Immediate(i64),
+    Slot(i64),
+}
+
+impl Operand {
+    fn mode(self) -> i64 {
+        match self {
+            Operand::Immediate(_) => 1,
+            Operand::Slot(_) => 2,
+        }
+    }
+
+    fn word(self) -> Word {
+        match self {
+            Operand::Immediate(value) | Operand::Slot(value) => Word::Value(value),
+        }
+    }
+}
+
+struct Generator<'a> {
+    functions: &'a HashMap<String, (usize, usize)>,
+    code: Vec<Word>,
+    labels: Vec<Option<usize>>,
+    variables: HashMap<String, i64>,
+    next_slot: i64,
+    frame_size: i64,
+}
+
+impl<'a> Generator<'a> {
+    fn new_label(&mut self) -> usize {
+        self.labels.push(None);
+        self.labels.len() - 1
+    }
+
+    fn place_label(&mut self, label: usize) {
+        self.labels[label] = Some(self.code.len());
+    }
+
+    fn emit(&mut self, words: &[Word]) {
+        self.code.extend_from_slice(words)
+    }
+
+    fn allocate_slot(&mut self) -> i64 {
+        let slot = self.next_slot;
+        self.next_slot += 1;
+        if self.next_slot > self.frame_size {
+            self.frame_size = self.next_slot
+        }
+        slot
+    }
+
+    fn emit_operation(&mut self, opcode: i64, first: Operand, second: Operand, destination: i64) {
+        self.emit_operation_to(opcode, first, second, Word::Value(destination))
+    }
+
+    fn emit_operation_to(
+        &mut self,
+        opcode: i64,
+        first: Operand,
+        second: Operand,
+        destination: Word,
+    ) {
+        let instruction = opcode + 100 * first.mode() + 1000 * second.mode() + 20000;
+        self.emit(&[
+            Word::Value(instruction),
+            first.word(),
+            second.word(),
+            destination,
+        ]);
+    }
+
+    fn emit_move(&mut self, source: Operand, destination: i64) {
+        self.emit_operation(1, source, Operand::Immediate(0), destination)
+    }
+
+    fn emit_jump(&mut self, opcode: i64, condition: Operand, label: usize) {
+        self.emit(&[
+            Word::Value(opcode + 100 * condition.mode() + 1000),
+            condition.word(),
+            Word::Label(label),
+        ])
+    }
+
+    fn function(&mut self, function: &Function) -> Result<(), CompileError> {
+        self.variables.clear();
+        for (idx, parameter) in function.parameters.iter().enumerate() {
+            if self.variables.contains_key(parameter) {
+                return Err(CompileError::new(
+                    function.line,
+                    &format!("Parameter '{}' is declared twice", parameter),
+                ));
+            }
+            self.variables
+                .insert(parameter.clone(), FIRST_PARAMETER_SLOT + idx as i64);
+        }
+        self.next_slot = FIRST_PARAMETER_SLOT + function.parameters.len() as i64;
+        self.frame_size = self.next_slot;
+        let start = self.code.len();
+        let (label, _) = self.functions[&function.name];
+        self.place_label(label);
+        self.statements(&function.body)?;
+        self.emit_return(Operand::Immediate(0));
+        let frame_size = self.frame_size;
+        for word in &mut self.code[start..] {
+            if let Word::Frame(sign, offset) = *word {
+                *word = Word::Value(sign * frame_size + offset)
+            }
+        }
+        Ok(())
+    }
+
+    fn emit_return(&mut self, value: Operand) {
+        self.emit_move(value, RETURN_VALUE_SLOT);
+        self.emit(&[
+            Word::Value(2106),
+            Word::Value(0),
+            Word::Value(RETURN_ADDRESS_SLOT),
+        ]);
+    }
+
+    fn statements(&mut self, statements: &[Statement]) -> Result<(), CompileError> {
+        for statement in statements {
+            self.statement(statement)?;
+        }
+        Ok(())
+    }
+
+    fn scoped_statements(&mut self, statements: &[Statement]) -> Result<(), CompileError> {
+        let variables = self.variables.clone();
+        let next_slot = self.next_slot;
+        self.statements(statements)?;
+        self.variables = variables;
+        self.next_slot = next_slot;
+        Ok(())
+    }
+
+    fn statement(&mut self, statement: &Statement) -> Result<(), CompileError> {
+        let scratch_start = self.next_slot;
+        match statement {
+            Statement::Let(name, value, line) => {
+                if self.variables.contains_key(name) {
+                    return Err(CompileError::new(
+                        *line,
+                        &format!("Variable '{}' is already declared", name),
+                    ));
+                }
+                let slot = self.allocate_slot();
+                self.expression_into(value, slot)?;
+                self.variables.insert(name.clone(), slot);
+                self.next_slot = slot + 1;
+                return Ok(());
+            }
+            Statement::Assign(name, value, line) => {
+                let slot = self.variable(name, *line)?;
+                self.expression_into(value, slot)?;
+            }
+            Statement::If(condition, then_block, else_block) => {
+                let else_label = self.new_label();
+                let end_label = self.new_label();
+                let condition = self.operand(condition)?;
+                self.emit_jump(6, condition, else_label);
+                self.next_slot = scratch_start;
+                self.scoped_statements(then_block)?;
+                self.emit_jump(5, Operand::Immediate(1), end_label);
+                self.place_label(else_label);
+                self.scoped_statements(else_block)?;
+                self.place_label(end_label);
+            }
+            Statement::While(condition, body) => {
+                let start_label = self.new_label();
+                let end_label = self.new_label();
+                self.place_label(start_label);
+                let condition = self.operand(condition)?;
+                self.emit_jump(6, condition, end_label);
+                self.next_slot = scratch_start;
+                self.scoped_statements(body)?;
+                self.emit_jump(5, Operand::Immediate(1), start_label);
+                self.place_label(end_label);
+            }
+            Statement::Output(value) => {
+                let value = self.operand(value)?;
+                self.emit(&[Word::Value(4 + 100 * value.mode()), value.word()]);
+            }
+            Statement::Return(value) => {
+                let value = match value {
+                    Some(value) => self.operand(value)?,
+                    None => Operand::Immediate(0),
+                };
+                self.emit_return(value);
+            }
+            Statement::Expression(value) => {
+                self.operand(value)?;
+            }
+        }
+        self.next_slot = scratch_start;
+        Ok(())
+    }
+
+    fn variable(&self, name: &str, line: usize) -> Result<i64, CompileError> {
+        match self.variables.get(name) {
+            Some(slot) => Ok(*slot),
+            None => Err(CompileError::new(
+                line,
+                &format!("Variable '{}' is not declared", name),
+            )),
+        }
+    }
+
+    fn operand(&mut self, expression: &Expression) -> Result<Operand, CompileError> {
+        match expression {
+            Expression::Number(n) => Ok(Operand::Immediate(*n)),
+            Expression::Variable(name, line) => Ok(Operand::Slot(self.variable(name, *line)?)),
+            _ => {
+                let slot = self.allocate_slot();
+                self.expression_into(expression, slot)?;
+                Ok(Operand::Slot(slot))
+            }
+        }
+    }
+
+    fn expression_into(
+        &mut self,
+        expression: &Expression,
+        destination: i64,
+    ) -> Result<(), CompileError> {
+        match expression {
+            Expression::Number(_) | Expression::Variable(_, _) => {
+                let source = self.operand(expression)?;
+                self.emit_move(source, destination);
+            }
+            Expression::Input => self.emit(&[Word::Value(203), Word::Value(destination)]),
+            Expression::Negate(value) => {
+                let value = self.operand(value)?;
+                self.emit_operation(2, value, Operand::Immediate(-1), destination);
+            }
+            Expression::Not(value) => {
+                let value = self.operand(value)?;
+                self.emit_operation(8, value, Operand::Immediate(0), destination);
+            }
+            Expression::Binary(operator, left, right) => {
+                let left = self.operand(left)?;
+                let right = self.operand(right)?;
+                self.binary(*operator, left, right, destination);
+            }
+            Expression::Call(name, arguments, line) => {
+                self.call(name, arguments, *line, destination)?
+            }
+        }
+        Ok(())
+    }
+
+    fn binary(
+        &mut self,
+        operator: BinaryOperator,
+        left: Operand,
+        right: Operand,
+        destination: i64,
+    ) {
+        match operator {
+            BinaryOperator::Add => self.emit_operation(1, left, right, destination),
+            BinaryOperator::Multiply => self.emit_operation(2, left, right, destination),
+            BinaryOperator::Less => self.emit_operation(7, left, right, destination),
+            BinaryOperator::Greater => self.emit_operation(7, right, left, destination),
+            BinaryOperator::Equal => self.emit_operation(8, left, right, destination),
+            BinaryOperator::Subtract => {
+                let negated = self.allocate_slot();
+                self.emit_operation(2, right, Operand::Immediate(-1), negated);
+                self.emit_operation(1, left, Operand::Slot(negated), destination);
+            }
+            BinaryOperator::NotEqual
+            | BinaryOperator::LessOrEqual
+            | BinaryOperator::GreaterOrEqual => {
+                let opposite = self.allocate_slot();
+                match operator {
+                    BinaryOperator::NotEqual => self.emit_operation(8, left, right, opposite),
+                    BinaryOperator::LessOrEqual => self.emit_operation(7, right, left, opposite),
+                    _ => self.emit_operation(7, left, right, opposite),
+                }
+                self.emit_operation(
+                    8,
+                    Operand::Slot(opposite),
+                    Operand::Immediate(0),
+                    destination,
+                );
+            }
+        }
+    }
+
+    fn call(
+        &mut self,
+        name: &str,
+        arguments: &[Expression],
+        line: usize,
+        destination: i64,
+    ) -> Result<(), CompileError> {
+        let (label, arity) = match self.functions.get(name) {
+            Some(function) => *function,
+            None => {
+                return Err(CompileError::new(
+                    line,
+                    &format!("Function '{}' is not defined", name),
+                ))
+            }
+        };
+        if arity != arguments.len() {
+            return Err(CompileError::new(
+                line,
+                &format!("Function '{}' takes {} arguments", name, arity),
+            ));
+        }
+        let mut values = Vec::new();
+        for argument in arguments {
+            values.push(self.operand(argument)?);
+        }
+        //the callee frame starts after this one, its size is only known once the whole
+        //function has been generated
+        for (idx, value) in values.iter().enumerate() {
+            self.emit_operation_to(
+                1,
+                *value,
+                Operand::Immediate(0),
+                Word::Frame(1, FIRST_PARAMETER_SLOT + idx as i64),
+            );
+        }
+        let return_label = self.new_label();
+        self.emit(&[
+            Word::Value(21101),
+            Word::Label(return_label),
+            Word::Value(0),
+            Word::Frame(1, RETURN_ADDRESS_SLOT),
+        ]);
+        self.emit(&[Word::Value(109), Word::Frame(1, 0)]);
+        self.emit(&[Word::Value(1105), Word::Value(1), Word::Label(label)]);
+        self.place_label(return_label);
+        self.emit(&[Word::Value(109), Word::Frame(-1, 0)]);
+        self.emit(&[
+            Word::Value(22101),
+            Word::Value(0),
+            Word::Frame(1, RETURN_VALUE_SLOT),
+            Word::Value(destination),
+        ]);
+        Ok(())
+    }
+}
+
+pub fn compile(source: &str) -> Result<Vec<i64>, CompileError> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        position: 0,
+    };
+    let functions = parser.functions()?;
+    let mut function_table = HashMap::new();
+    for (idx, function) in functions.iter().enumerate() {
+        if function_table
+            .insert(function.name.clone(), (idx, function.parameters.len()))
+            .is_some()
+        {
+            return Err(CompileError::new(
+                function.line,
+                &format!("Function '{}' is defined twice", function.name),
+            ));
+        }
+    }
+    let main_label = match function_table.get("main") {
+        Some((label, 0)) => *label,
+        Some(_) => return Err(CompileError::new(1, "Function 'main' takes no arguments")),
+        None => return Err(CompileError::new(1, "Function 'main' is not defined")),
+    };
+
+    let mut generator = Generator {
+        functions: &function_table,
+        code: Vec::new(),
+        labels: vec![None; functions.len()],
+        variables: HashMap::new(),
+        next_slot: 0,
+        frame_size: 0,
+    };
+    let stack_label = generator.new_label();
+    let exit_label = generator.new_label();
+    generator.emit(&[Word::Value(109), Word::Label(stack_label)]);
+    generator.emit(&[
+        Word::Value(21101),
+        Word::Label(exit_label),
+        Word::Value(0),
+        Word::Value(RETURN_ADDRESS_SLOT),
+    ]);
+    generator.emit(&[Word::Value(1105), Word::Value(1), Word::Label(main_label)]);
+    generator.place_label(exit_label);
+    generator.emit(&[Word::Value(99)]);
+    for function in &functions {
+        generator.function(function)?;
+    }
+    generator.place_label(stack_label);
+
+    let labels = &generator.labels;
+    Ok(generator
+        .code
+        .iter()
+        .map(|word| match word {
+            Word::Value(value) => *value,
+            Word::Label(label) => labels[*label].expect("Every label is placed") as i64,
+            Word::Frame(_, _) => panic!("Frame offsets are resolved per function"),
+        })
+        .collect())
+}
+
+pub fn compile_file(source_file: &str) -> io::Result<Vec<i64>> {
+    let source = fs::read_to_string(source_file)?;
+    compile(&source).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::IntcodeComputer;
+
+    fn run(source: &str, input: &[i64]) -> Vec<i64> {
+        let program = compile(source).unwrap();
+        let mut comp = IntcodeComputer::new(&program);
+        let mut input: Vec<i64> = input.iter().rev().copied().collect();
+        comp.run(&mut input);
+        comp.output().clone()
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let source = "
+            fn main() {
+                let a = 7;
+                let b = -3;
+                output(a + b * 2);
+                output(a - b - 1);
+                output(-(a * b));
+                output((a + 1) * (b - 1));
+            }";
+        assert_eq!(run(source, &[]), vec![1, 9, 21, -32]);
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let source = "
+            fn main() {
+                let x = input();
+                output(x == 5);
+                output(x != 5);
+                output(x < 5);
+                output(x > 5);
+                output(x <= 5);
+                output(x >= 5);
+                output(!x);
+            }";
+        assert_eq!(run(source, &[5]), vec![1, 0, 0, 0, 1, 1, 0]);
+        assert_eq!(run(source, &[2]), vec![0, 1, 1, 0, 1, 0, 0]);
+        assert_eq!(run(source, &[0]), vec![0, 1, 1, 0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn test_control_flow() {
+        let source = "
+            // sums the inputs until a zero is read
+            fn main() {
+                let total = 0;
+                let value = input();
+                while value != 0 {
+                    if value > 100 {
+                        output(value);
+                    } else if value < 0 {
+                        total = total - value;
+                    } else {
+                        total = total + value;
+                    }
+                    value = input();
+                }
+                output(total);
+            }";
+        assert_eq!(run(source, &[3, 500, -4, 10, 0]), vec![500, 17]);
+    }
+
+    #[test]
+    fn test_block_scope() {
+        let source = "
+            fn main() {
+                let i = 0;
+                while i < 3 {
+                    let square = i * i;
+                    output(square + 1);
+                    i = i + 1;
+                }
+                let after = 40 + i;
+                output(after);
+            }";
+        assert_eq!(run(source, &[]), vec![1, 2, 5, 43]);
+        assert_eq!(
+            compile("fn main() {\n if 1 { let a = 1; }\n output(a);\n}"),
+            Err(CompileError::new(3, "Variable 'a' is not declared"))
+        );
+    }
+
+    #[test]
+    fn test_recursive_functions() {
+        let source = "
+            fn factorial(n) {
+                if n < 2 {
+                    return 1;
+                }
+                return n * factorial(n - 1);
+            }
+
+            fn fibonacci(n) {
+                if n < 2 {
+                    return n;
+                }
+                return fibonacci(n - 1) + fibonacci(n - 2);
+            }
+
+            fn add3(a, b, c) {
+                return a + b + c;
+            }
+
+            fn main() {
+                output(factorial(input()));
+                output(fibonacci(15));
+                output(add3(factorial(3), fibonacci(7), 1000));
+            }";
+        assert_eq!(run(source, &[10]), vec![3_628_800, 610, 1019]);
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        assert_eq!(
+            compile("fn main() {\n output(x);\n}"),
+            Err(CompileError::new(2, "Variable 'x' is not declared"))
+        );
+        assert_eq!(
+            compile("fn f(a) { return a; }\nfn main() {\n f(1, 2);\n}"),
+            Err(CompileError::new(3, "Function 'f' takes 1 arguments"))
+        );
+        assert_eq!(
+            compile("fn start() {}"),
+            Err(CompileError::new(1, "Function 'main' is not defined"))
+        );
+        assert_eq!(
+            compile("fn main() {\n let a = 1\n}"),
+            Err(CompileError::new(3, "Expected ';'"))
+        );
+        assert_eq!(
+            compile("fn main() { output(1 % 2); }"),
+            Err(CompileError::new(1, "Unexpected character '%'"))
+        );
+    }
+}
@@ -0,0 +1,615 @@
+use crate::{
+    amplifier, diagnostic_program,
+    feedback_amplifier::Amplifier,
+    intcode::{ComputerState, IntcodeComputer},
+    intcode_optimizer::optimize,
+    program,
+};
+use std::convert::TryFrom;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Feature {
+    //opcodes 1, 2 and 99 in position mode
+    AddMultiply,
+    //opcodes 3 and 4
+    InputOutput,
+    ImmediateMode,
+    //opcodes 5 to 8
+    JumpsAndComparisons,
+    //more than one distinct input value
+    MultipleInputs,
+    //opcode 9 and parameter mode 2
+    RelativeMode,
+    //values that do not fit in an i32
+    LargeNumbers,
+    //addresses past the end of the program
+    ExtendedMemory,
+}
+
+#[derive(Debug)]
+pub struct Case {
+    pub name: &'static str,
+    pub program: &'static [i64],
+    //in the order the program consumes them
+    pub input: &'static [i64],
+    pub output: &'static [i64],
+    pub memory: &'static [(u64, i64)],
+    pub features: &'static [Feature],
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Outcome {
+    pub output: Vec<i64>,
+    //the cells that were asked for, if the engine exposes its memory
+    pub memory: Option<Vec<i64>>,
+    pub halted: bool,
+}
+
+pub trait Engine {
+    fn name(&self) -> &'static str;
+
+    fn features(&self) -> &'static [Feature];
+
+    fn run(&self, program: &[i64], input: &[i64], addresses: &[u64]) -> Outcome;
+
+    fn supports(&self, case: &Case) -> bool {
+        case.features.iter().all(|f| self.features().contains(f))
+    }
+}
+
+use Feature::*;
+
+const DAY_2: &[Feature] = &[AddMultiply];
+const DAY_5: &[Feature] = &[AddMultiply, InputOutput, ImmediateMode, JumpsAndComparisons];
+const DAY_7: &[Feature] = &[
+    AddMultiply,
+    InputOutput,
+    ImmediateMode,
+    JumpsAndComparisons,
+    MultipleInputs,
+];
+const DAY_9: &[Feature] = &[
+    AddMultiply,
+    InputOutput,
+    ImmediateMode,
+    JumpsAndComparisons,
+    MultipleInputs,
+    RelativeMode,
+    LargeNumbers,
+    ExtendedMemory,
+];
+
+const LARGE_EXAMPLE: &[i64] = &[
+    3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0, 0,
+    1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4, 20, 1105,
+    1, 46, 98, 99,
+];
+const QUINE: &[i64] = &[
+    109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+];
+
+pub const CASES: &[Case] = &[
+    Case {
+        name: "add",
+        program: &[1, 0, 0, 0, 99],
+        input: &[],
+        output: &[],
+        memory: &[(0, 2), (4, 99)],
+        features: &[AddMultiply],
+    },
+    Case {
+        name: "multiply",
+        program: &[2, 3, 0, 3, 99],
+        input: &[],
+        output: &[],
+        memory: &[(3, 6)],
+        features: &[AddMultiply],
+    },
+    Case {
+        name: "multiply past halt",
+        program: &[2, 4, 4, 5, 99, 0],
+        input: &[],
+        output: &[],
+        memory: &[(5, 9801)],
+        features: &[AddMultiply],
+    },
+    Case {
+        name: "overwrite halt",
+        program: &[1, 1, 1, 4, 99, 5, 6, 0, 99],
+        input: &[],
+        output: &[],
+        memory: &[(0, 30), (4, 2)],
+        features: &[AddMultiply],
+    },
+    Case {
+        name: "halt",
+        program: &[99, 1, 2, 3],
+        input: &[],
+        output: &[],
+        memory: &[(0, 99), (3, 3)],
+        features: &[AddMultiply],
+    },
+    Case {
+        name: "echo",
+        program: &[3, 0, 4, 0, 99],
+        input: &[10],
+        output: &[10],
+        memory: &[(0, 10)],
+        features: &[InputOutput],
+    },
+    Case {
+        name: "immediate multiply",
+        program: &[1002, 4, 3, 4, 33],
+        input: &[],
+        output: &[],
+        memory: &[(4, 99)],
+        features: &[AddMultiply, ImmediateMode],
+    },
+    Case {
+        name: "negative immediate",
+        program: &[1101, 100, -1, 4, 0],
+        input: &[],
+        output: &[],
+        memory: &[(4, 99)],
+        features: &[AddMultiply, ImmediateMode],
+    },
+    Case {
+        name: "self modifying input",
+        program: &[3, 3, 1101, 100, -1, 1, 4, 1, 99],
+        input: &[10],
+        output: &[9],
+        memory: &[(1, 9), (3, 10)],
+        features: &[AddMultiply, InputOutput, ImmediateMode],
+    },
+    Case {
+        name: "immediate output",
+        program: &[104, -7, 99],
+        input: &[],
+        output: &[-7],
+        memory: &[],
+        features: &[InputOutput, ImmediateMode],
+    },
+    Case {
+        name: "position equals, true",
+        program: &[3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8],
+        input: &[8],
+        output: &[1],
+        memory: &[(9, 1)],
+        features: &[InputOutput, JumpsAndComparisons],
+    },
+    Case {
+        name: "position equals, false",
+        program: &[3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8],
+        input: &[5],
+        output: &[0],
+        memory: &[(9, 0)],
+        features: &[InputOutput, JumpsAndComparisons],
+    },
+    Case {
+        name: "position less than, true",
+        program: &[3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8],
+        input: &[5],
+        output: &[1],
+        memory: &[],
+        features: &[InputOutput, JumpsAndComparisons],
+    },
+    Case {
+        name: "position less than, false",
+        program: &[3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8],
+        input: &[8],
+        output: &[0],
+        memory: &[],
+        features: &[InputOutput, JumpsAndComparisons],
+    },
+    Case {
+        name: "immediate equals",
+        program: &[3, 3, 1108, -1, 8, 3, 4, 3, 99],
+        input: &[8],
+        output: &[1],
+        memory: &[(3, 1)],
+        features: &[InputOutput, ImmediateMode, JumpsAndComparisons],
+    },
+    Case {
+        name: "immediate less than",
+        program: &[3, 3, 1107, -1, 8, 3, 4, 3, 99],
+        input: &[9],
+        output: &[0],
+        memory: &[(3, 0)],
+        features: &[InputOutput, ImmediateMode, JumpsAndComparisons],
+    },
+    Case {
+        name: "position jump, zero",
+        program: &[3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9],
+        input: &[0],
+        output: &[0],
+        memory: &[],
+        features: &[AddMultiply, InputOutput, JumpsAndComparisons],
+    },
+    Case {
+        name: "position jump, non zero",
+        program: &[3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9],
+        input: &[5],
+        output: &[1],
+        memory: &[(13, 1)],
+        features: &[AddMultiply, InputOutput, JumpsAndComparisons],
+    },
+    Case {
+        name: "immediate jump, zero",
+        program: &[3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1],
+        input: &[0],
+        output: &[0],
+        memory: &[],
+        features: &[AddMultiply, InputOutput, ImmediateMode, JumpsAndComparisons],
+    },
+    Case {
+        name: "immediate jump, non zero",
+        program: &[3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1],
+        input: &[3],
+        output: &[1],
+        memory: &[],
+        features: &[AddMultiply, InputOutput, ImmediateMode, JumpsAndComparisons],
+    },
+    Case {
+        name: "larger example, below 8",
+        program: LARGE_EXAMPLE,
+        input: &[7],
+        output: &[999],
+        memory: &[],
+        features: &[AddMultiply, InputOutput, ImmediateMode, JumpsAndComparisons],
+    },
+    Case {
+        name: "larger example, equal to 8",
+        program: LARGE_EXAMPLE,
+        input: &[8],
+        output: &[1000],
+        memory: &[(20, 1000)],
+        features: &[AddMultiply, InputOutput, ImmediateMode, JumpsAndComparisons],
+    },
+    Case {
+        name: "larger example, above 8",
+        program: LARGE_EXAMPLE,
+        input: &[9],
+        output: &[1001],
+        memory: &[(20, 1001)],
+        features: &[AddMultiply, InputOutput, ImmediateMode, JumpsAndComparisons],
+    },
+    Case {
+        name: "two inputs",
+        program: &[3, 0, 3, 1, 1, 0, 1, 2, 4, 2, 99],
+        input: &[3, 4],
+        output: &[7],
+        memory: &[(0, 3), (1, 4), (2, 7)],
+        features: &[AddMultiply, InputOutput, MultipleInputs],
+    },
+    Case {
+        name: "input order",
+        program: &[3, 0, 3, 1, 4, 0, 4, 1, 99],
+        input: &[5, 6],
+        output: &[5, 6],
+        memory: &[],
+        features: &[InputOutput, MultipleInputs],
+    },
+    Case {
+        name: "large multiply",
+        program: &[1102, 34_915_192, 34_915_192, 7, 4, 7, 99, 0],
+        input: &[],
+        output: &[1_219_070_632_396_864],
+        memory: &[(7, 1_219_070_632_396_864)],
+        features: &[AddMultiply, InputOutput, ImmediateMode, LargeNumbers],
+    },
+    Case {
+        name: "large immediate",
+        program: &[104, 1_125_899_906_842_624, 99],
+        input: &[],
+        output: &[1_125_899_906_842_624],
+        memory: &[],
+        features: &[InputOutput, ImmediateMode, LargeNumbers],
+    },
+    Case {
+        name: "quine",
+        program: QUINE,
+        input: &[],
+        output: QUINE,
+        memory: &[(100, 16), (101, 1)],
+        features: &[
+            AddMultiply,
+            InputOutput,
+            ImmediateMode,
+            JumpsAndComparisons,
+            RelativeMode,
+            ExtendedMemory,
+        ],
+    },
+    Case {
+        name: "relative input",
+        program: &[109, 10, 203, 5, 204, 5, 99],
+        input: &[42],
+        output: &[42],
+        memory: &[(15, 42)],
+        features: &[InputOutput, ImmediateMode, RelativeMode, ExtendedMemory],
+    },
+    Case {
+        name: "relative write",
+        program: &[109, 10, 21101, 2, 3, -10, 4, 0, 99],
+        input: &[],
+        output: &[5],
+        memory: &[(0, 5)],
+        features: &[AddMultiply, InputOutput, ImmediateMode, RelativeMode],
+    },
+    Case {
+        name: "relative read of a constant",
+        program: &[1101, 2, 3, 20, 204, 2, 99],
+        input: &[],
+        output: &[3],
+        memory: &[(2, 3), (20, 5)],
+        features: &[
+            AddMultiply,
+            InputOutput,
+            ImmediateMode,
+            RelativeMode,
+            ExtendedMemory,
+        ],
+    },
+    Case {
+        name: "write past program",
+        program: &[1101, 2, 3, 100, 4, 100, 99],
+        input: &[],
+        output: &[5],
+        memory: &[(100, 5), (1000, 0)],
+        features: &[AddMultiply, InputOutput, ImmediateMode, ExtendedMemory],
+    },
+];
+
+fn narrow(values: &[i64]) -> Vec<i32> {
+    values
+        .iter()
+        .map(|&v| i32::try_from(v).expect("Value does not fit the engine's word size"))
+        .collect()
+}
+
+fn widen(values: &[i32]) -> Vec<i64> {
+    values.iter().map(|&v| i64::from(v)).collect()
+}
+
+fn cells(memory: &[i32], addresses: &[u64]) -> Vec<i64> {
+    addresses
+        .iter()
+        .map(|&a| i64::from(memory[a as usize]))
+        .collect()
+}
+
+//program::process_instructions, day 2
+pub struct ProgramEngine;
+
+impl Engine for ProgramEngine {
+    fn name(&self) -> &'static str {
+        "program"
+    }
+
+    fn features(&self) -> &'static [Feature] {
+        DAY_2
+    }
+
+    fn run(&self, program: &[i64], _input: &[i64], addresses: &[u64]) -> Outcome {
+        let memory = program::process_instructions(None, None, &narrow(program));
+        Outcome {
+            output: Vec::new(),
+            memory: Some(cells(&memory, addresses)),
+            halted: true,
+        }
+    }
+}
+
+//diagnostic_program::process_instructions, day 5
+pub struct DiagnosticEngine;
+
+impl Engine for DiagnosticEngine {
+    fn name(&self) -> &'static str {
+        "diagnostic_program"
+    }
+
+    fn features(&self) -> &'static [Feature] {
+        DAY_5
+    }
+
+    fn run(&self, program: &[i64], input: &[i64], _addresses: &[u64]) -> Outcome {
+        //every input instruction reads the same value
+        let input = input.first().map(|&i| narrow(&[i])[0]);
+        let output = diagnostic_program::process_instructions(input, &narrow(program));
+        Outcome {
+            output: widen(&output),
+            memory: None,
+            halted: true,
+        }
+    }
+}
+
+//amplifier::process_instructions, day 7 part 1
+pub struct AmplifierEngine;
+
+impl Engine for AmplifierEngine {
+    fn name(&self) -> &'static str {
+        "amplifier"
+    }
+
+    fn features(&self) -> &'static [Feature] {
+        DAY_7
+    }
+
+    fn run(&self, program: &[i64], input: &[i64], _addresses: &[u64]) -> Outcome {
+        let mut input: Vec<i32> = narrow(input).into_iter().rev().collect();
+        let output = amplifier::process_instructions(&mut input, &narrow(program));
+        Outcome {
+            output: widen(&output),
+            memory: None,
+            halted: true,
+        }
+    }
+}
+
+//feedback_amplifier::Amplifier, day 7 part 2
+pub struct FeedbackAmplifierEngine;
+
+impl Engine for FeedbackAmplifierEngine {
+    fn name(&self) -> &'static str {
+        "feedback_amplifier"
+    }
+
+    fn features(&self) -> &'static [Feature] {
+        DAY_7
+    }
+
+    fn run(&self, program: &[i64], input: &[i64], addresses: &[u64]) -> Outcome {
+        let mut amplifier = Amplifier::new(&narrow(program));
+        let mut input: Vec<i32> = narrow(input).into_iter().rev().collect();
+        let output = amplifier.process_instructions(&mut input);
+        Outcome {
+            output: widen(&output),
+            memory: Some(cells(amplifier.memory(), addresses)),
+            halted: !amplifier.is_waiting(),
+        }
+    }
+}
+
+//intcode::{ComputerState, IntcodeComputer}, day 9
+pub struct IntcodeEngine;
+
+impl Engine for IntcodeEngine {
+    fn name(&self) -> &'static str {
+        "intcode"
+    }
+
+    fn features(&self) -> &'static [Feature] {
+        DAY_9
+    }
+
+    fn run(&self, program: &[i64], input: &[i64], addresses: &[u64]) -> Outcome {
+        let mut computer = IntcodeComputer::new(program);
+        let mut input: Vec<i64> = input.iter().rev().cloned().collect();
+        computer.run(&mut input);
+        Outcome {
+            output: computer.output().clone(),
+            memory: Some(addresses.iter().map(|&a| computer.peek(a)).collect()),
+            halted: computer.state() == &ComputerState::Halted,
+        }
+    }
+}
+
+//intcode_optimizer::optimize then intcode::IntcodeComputer, only io is kept the same
+pub struct OptimizedEngine;
+
+impl Engine for OptimizedEngine {
+    fn name(&self) -> &'static str {
+        "optimized"
+    }
+
+    fn features(&self) -> &'static [Feature] {
+        DAY_9
+    }
+
+    fn run(&self, program: &[i64], input: &[i64], addresses: &[u64]) -> Outcome {
+        let (image, _) = optimize(program);
+        let outcome = IntcodeEngine.run(&image, input, addresses);
+        Outcome {
+            memory: None,
+            ..outcome
+        }
+    }
+}
+
+pub fn engines() -> Vec<Box<dyn Engine>> {
+    vec![
+        Box::new(ProgramEngine),
+        Box::new(DiagnosticEngine),
+        Box::new(AmplifierEngine),
+        Box::new(FeedbackAmplifierEngine),
+        Box::new(IntcodeEngine),
+        Box::new(OptimizedEngine),
+    ]
+}
+
+pub fn check_case(engine: &dyn Engine, case: &Case) -> Result<(), String> {
+    let addresses: Vec<u64> = case.memory.iter().map(|&(a, _)| a).collect();
+    let outcome = engine.run(case.program, case.input, &addresses);
+    if !outcome.halted {
+        return Err(format!("{}: {}: did not halt", engine.name(), case.name));
+    }
+    if outcome.output != case.output {
+        return Err(format!(
+            "{}: {}: expected output {:?}, got {:?}",
+            engine.name(),
+            case.name,
+            case.output,
+            outcome.output
+        ));
+    }
+    if let Some(memory) = outcome.memory {
+        for (&(address, expected), actual) in case.memory.iter().zip(memory) {
+            if actual != expected {
+                return Err(format!(
+                    "{}: {}: expected {} at address {}, got {}",
+                    engine.name(),
+                    case.name,
+                    expected,
+                    address,
+                    actual
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+//runs every supported case, returns the number of cases run and the failures
+pub fn check(engine: &dyn Engine) -> (usize, Vec<String>) {
+    let mut run = 0;
+    let mut failures = Vec::new();
+    for case in CASES.iter().filter(|c| engine.supports(c)) {
+        run += 1;
+        if let Err(failure) = check_case(engine, case) {
+            failures.push(failure);
+        }
+    }
+    (run, failures)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_conforms(engine: &dyn Engine, expected_cases: usize) {
+        let (run, failures) = check(engine);
+        assert!(failures.is_empty(), "{:#?}", failures);
+        assert_eq!(run, expected_cases);
+    }
+
+    #[test]
+    fn test_program_engine() {
+        assert_conforms(&ProgramEngine, 5);
+    }
+
+    #[test]
+    fn test_diagnostic_engine() {
+        assert_conforms(&DiagnosticEngine, 23);
+    }
+
+    #[test]
+    fn test_amplifier_engine() {
+        assert_conforms(&AmplifierEngine, 25);
+    }
+
+    #[test]
+    fn test_feedback_amplifier_engine() {
+        assert_conforms(&FeedbackAmplifierEngine, 25);
+    }
+
+    #[test]
+    fn test_intcode_engine() {
+        assert_conforms(&IntcodeEngine, CASES.len());
+    }
+
+    #[test]
+    fn test_optimized_engine() {
+        assert_conforms(&OptimizedEngine, CASES.len());
+    }
+}
@@ -0,0 +1,127 @@
+use crate::intcode::{ComputerState, IntcodeComputer};
+use std::{
+    io::{self, BufRead, Write},
+    iter,
+};
+
+//plays an ascii program on a terminal: every line read becomes input, ending in a newline,
+//and output is printed as text. values outside ascii get a line of their own
+pub struct Console<'a> {
+    computer: &'a mut IntcodeComputer,
+    input: &'a mut Vec<i64>,
+    printed: usize,
+    at_line_start: bool,
+}
+
+impl<'a> Console<'a> {
+    //`input` is used up before anything is read
+    pub fn new(computer: &'a mut IntcodeComputer, input: &'a mut Vec<i64>) -> Console<'a> {
+        let printed = computer.output().len();
+        Console {
+            computer,
+            input,
+            printed,
+            at_line_start: true,
+        }
+    }
+
+    //returns once the program stops for anything other than input,
+    //or the reader runs out while the program waits for more
+    pub fn session<R: BufRead, W: Write>(
+        &mut self,
+        mut reader: R,
+        mut writer: W,
+    ) -> io::Result<()> {
+        loop {
+            self.computer.run(self.input);
+            self.print(&mut writer)?;
+            if self.computer.state() != &ComputerState::Waiting {
+                break;
+            }
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 {
+                break;
+            }
+            let line = line.trim_end_matches(&['\r', '\n'][..]);
+            let values: Vec<i64> = line
+                .chars()
+                .chain(iter::once('\n'))
+                .map(|c| c as i64)
+                .collect();
+            self.input.splice(0..0, values.into_iter().rev());
+        }
+        if !self.at_line_start {
+            writeln!(writer)?;
+        }
+        writer.flush()
+    }
+
+    fn print<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        for &value in &self.computer.output()[self.printed..] {
+            match value {
+                0..=127 => {
+                    write!(writer, "{}", value as u8 as char)?;
+                    self.at_line_start = value == 10;
+                }
+                _ => {
+                    if !self.at_line_start {
+                        writeln!(writer)?;
+                    }
+                    writeln!(writer, "{}", value)?;
+                    self.at_line_start = true;
+                }
+            }
+        }
+        self.printed = self.computer.output().len();
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(program: &[i64], typed: &str) -> (String, ComputerState) {
+        let mut computer = IntcodeComputer::new(program);
+        let mut input = Vec::new();
+        let mut written = Vec::new();
+        Console::new(&mut computer, &mut input)
+            .session(typed.as_bytes(), &mut written)
+            .unwrap();
+        (
+            String::from_utf8(written).unwrap(),
+            computer.state().clone(),
+        )
+    }
+
+    #[test]
+    fn test_session() {
+        //echoes every character it reads
+        let echo = [3, 100, 4, 100, 1105, 1, 0];
+        assert_eq!(
+            session(&echo, "look\r\nnorth"),
+            ("look\nnorth\n".to_string(), ComputerState::Waiting)
+        );
+        assert_eq!(session(&echo, ""), (String::new(), ComputerState::Waiting));
+
+        //prints a greeting and a number, then echoes one character and halts
+        let greet = [104, 104, 104, 105, 104, 1000, 3, 20, 4, 20, 99];
+        assert_eq!(
+            session(&greet, "xy\nz\n"),
+            ("hi\n1000\nx\n".to_string(), ComputerState::Halted)
+        );
+    }
+
+    #[test]
+    fn test_queued_input() {
+        let program = [3, 20, 3, 21, 4, 20, 4, 21, 99];
+        let mut computer = IntcodeComputer::new(&program);
+        let mut input = vec![65];
+        let mut written = Vec::new();
+        Console::new(&mut computer, &mut input)
+            .session("B".as_bytes(), &mut written)
+            .unwrap();
+        assert_eq!(String::from_utf8(written).unwrap(), "AB\n");
+        assert_eq!(input, vec![10]);
+    }
+}
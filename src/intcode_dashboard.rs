@@ -0,0 +1,429 @@
+use crate::intcode::{ComputerState, IntcodeComputer};
+use std::{
+    collections::VecDeque,
+    fmt::{self, Write as _},
+    io::{self, Write},
+    sync::mpsc::{Receiver, TryRecvError},
+    thread,
+    time::{Duration, Instant},
+};
+
+const CLEAR: &str = "\x1b[H\x1b[2J";
+const REVERSE: &str = "\x1b[7m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+//how often the screen is redrawn
+const FRAME: Duration = Duration::from_millis(50);
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+//instructions per second, None runs flat out
+const SPEEDS: [Option<u64>; 7] = [
+    Some(1),
+    Some(10),
+    Some(100),
+    Some(1_000),
+    Some(10_000),
+    Some(100_000),
+    None,
+];
+//how many of each are kept for display
+const HISTORY: usize = 8;
+//instructions already run, then from ip onwards
+const RECENT: usize = 3;
+const LISTING: usize = 7;
+const MEMORY_ROWS: u64 = 8;
+const MEMORY_COLUMNS: u64 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Control {
+    Faster,
+    Slower,
+    //toggles between paused and running
+    Pause,
+    //one instruction while paused
+    Step,
+    Quit,
+}
+
+impl Control {
+    pub fn parse(key: &str) -> Option<Control> {
+        match key.trim() {
+            "+" | "f" => Some(Control::Faster),
+            "-" | "s" => Some(Control::Slower),
+            "p" | "" => Some(Control::Pause),
+            "n" => Some(Control::Step),
+            "q" => Some(Control::Quit),
+            _ => None,
+        }
+    }
+}
+
+//a full screen view of a computer that is stepped at a chosen speed
+pub struct Dashboard {
+    computer: IntcodeComputer,
+    input: Vec<i64>,
+    speed: usize,
+    paused: bool,
+    started: bool,
+    steps: u64,
+    //step counts over the last RATE_WINDOW, to work out the rate from
+    counts: VecDeque<(Instant, u64)>,
+    //slow speeds run less than one instruction a frame
+    credit: f64,
+    previous: VecDeque<u64>,
+    inputs: VecDeque<i64>,
+    //the memory window follows the instruction pointer unless it is set
+    memory_start: Option<u64>,
+}
+
+impl Dashboard {
+    //`input` is in the order the program reads it
+    pub fn new(computer: IntcodeComputer, input: &[i64]) -> Dashboard {
+        Dashboard {
+            computer,
+            input: input.iter().rev().copied().collect(),
+            speed: SPEEDS.len() - 1,
+            paused: false,
+            started: false,
+            steps: 0,
+            counts: VecDeque::new(),
+            credit: 0.0,
+            previous: VecDeque::new(),
+            inputs: VecDeque::new(),
+            memory_start: None,
+        }
+    }
+
+    pub fn computer(&self) -> &IntcodeComputer {
+        &self.computer
+    }
+
+    //picks the nearest speed that is at most `per_second`
+    pub fn set_speed(&mut self, per_second: Option<u64>) {
+        self.speed = match per_second {
+            None => SPEEDS.len() - 1,
+            Some(per_second) => SPEEDS
+                .iter()
+                .rposition(|speed| speed.is_some_and(|speed| speed <= per_second))
+                .unwrap_or(0),
+        }
+    }
+
+    pub fn speed(&self) -> Option<u64> {
+        SPEEDS[self.speed]
+    }
+
+    pub fn show_memory(&mut self, start: u64) {
+        self.memory_start = Some(start);
+    }
+
+    pub fn control(&mut self, control: Control) {
+        match control {
+            Control::Faster => self.speed = (self.speed + 1).min(SPEEDS.len() - 1),
+            Control::Slower => self.speed = self.speed.saturating_sub(1),
+            Control::Pause => self.paused = !self.paused,
+            Control::Step if self.paused => self.step(),
+            Control::Step | Control::Quit => {}
+        }
+    }
+
+    //halted, faulted or out of input, nothing more will happen
+    pub fn is_finished(&self) -> bool {
+        self.started && self.computer.state() != &ComputerState::Running
+    }
+
+    pub fn step(&mut self) {
+        if self.is_finished() {
+            return;
+        }
+        self.started = true;
+        let address = self.computer.instruction_pointer();
+        let queued = self.input.len();
+        let next_input = self.input.last().copied();
+        self.computer.step(&mut self.input);
+        if self.computer.state() == &ComputerState::Waiting {
+            return;
+        }
+        self.steps += 1;
+        if let Some(value) = next_input.filter(|_| self.input.len() < queued) {
+            push(&mut self.inputs, value);
+        }
+        push(&mut self.previous, address);
+    }
+
+    //steps for one frame's worth of time at the current speed
+    pub fn advance(&mut self, frame: Duration) {
+        let start = Instant::now();
+        self.counts.push_back((start, self.steps));
+        while self
+            .counts
+            .front()
+            .is_some_and(|&(time, _)| start - time > RATE_WINDOW)
+        {
+            self.counts.pop_front();
+        }
+        if self.paused || self.is_finished() {
+            return;
+        }
+        match self.speed() {
+            Some(per_second) => {
+                self.credit += per_second as f64 * frame.as_secs_f64();
+                let budget = self.credit.floor();
+                self.credit -= budget;
+                for _ in 0..budget as u64 {
+                    self.step();
+                }
+                if let Some(rest) = frame.checked_sub(start.elapsed()) {
+                    thread::sleep(rest);
+                }
+            }
+            None => {
+                while start.elapsed() < frame && !self.is_finished() {
+                    for _ in 0..1_000 {
+                        self.step();
+                    }
+                }
+            }
+        }
+    }
+
+    //instructions per second over the last RATE_WINDOW
+    pub fn rate(&self) -> f64 {
+        match self.counts.front() {
+            Some(&(time, steps)) if self.steps > steps => {
+                (self.steps - steps) as f64 / time.elapsed().as_secs_f64()
+            }
+            _ => 0.0,
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut screen = String::from(CLEAR);
+        self.render_status(&mut screen)
+            .and_then(|_| self.render_listing(&mut screen))
+            .and_then(|_| self.render_memory(&mut screen))
+            .and_then(|_| self.render_history(&mut screen))
+            .expect("Writing to a string cannot fail");
+        screen
+    }
+
+    fn render_status(&self, screen: &mut String) -> fmt::Result {
+        let state = match self.computer.state() {
+            _ if self.paused => "paused".to_string(),
+            ComputerState::Running => "running".to_string(),
+            ComputerState::Waiting => "waiting for input".to_string(),
+            ComputerState::Halted if !self.started => "ready".to_string(),
+            ComputerState::Halted => "halted".to_string(),
+            ComputerState::Paused => "interrupted".to_string(),
+            ComputerState::Faulted(fault) => format!(
+                "fault at {}: {:?}",
+                fault.instruction_pointer(),
+                fault.kind()
+            ),
+        };
+        let speed = self
+            .speed()
+            .map_or("max".to_string(), |speed| format!("{}/s", speed));
+        let fields = [
+            ("ip", self.computer.instruction_pointer().to_string()),
+            ("rb", self.computer.relative_base_offset().to_string()),
+            ("steps", self.steps.to_string()),
+            ("rate", format_rate(self.rate())),
+            ("speed", speed),
+        ];
+        for (name, value) in &fields {
+            write!(screen, "{}{}{} {}  ", BOLD, name, RESET, value)?;
+        }
+        writeln!(screen, "{}\n", state)
+    }
+
+    //the instructions that led here, then what a linear sweep from ip decodes
+    fn render_listing(&self, screen: &mut String) -> fmt::Result {
+        let recent = self.previous.len().saturating_sub(RECENT);
+        for &address in self.previous.iter().skip(recent) {
+            writeln!(screen, "   {:>6}  {}", address, self.decode(address).0)?;
+        }
+        let mut address = self.computer.instruction_pointer();
+        for idx in 0..LISTING {
+            let (text, length) = self.decode(address);
+            if idx == 0 {
+                writeln!(
+                    screen,
+                    "{}>  {:>6}  {:<32}{}",
+                    REVERSE, address, text, RESET
+                )?;
+            } else {
+                writeln!(screen, "   {:>6}  {}", address, text)?;
+            }
+            address += length;
+        }
+        writeln!(screen)
+    }
+
+    fn decode(&self, address: u64) -> (String, u64) {
+        match self.computer.instruction(address) {
+            Some(instruction) => (instruction.to_string(), instruction.length()),
+            None => (format!("?? {}", self.computer.peek(address)), 1),
+        }
+    }
+
+    fn render_memory(&self, screen: &mut String) -> fmt::Result {
+        let ip = self.computer.instruction_pointer();
+        let start = self.memory_start.unwrap_or_else(|| {
+            (ip / MEMORY_COLUMNS).saturating_sub(MEMORY_ROWS / 2) * MEMORY_COLUMNS
+        });
+        for row in 0..MEMORY_ROWS {
+            let row_start = start + row * MEMORY_COLUMNS;
+            write!(screen, "{:>6} ", row_start)?;
+            for address in row_start..row_start + MEMORY_COLUMNS {
+                let value = self.computer.peek(address);
+                if address == ip {
+                    write!(screen, " {}{:>8}{}", REVERSE, value, RESET)?;
+                } else {
+                    write!(screen, " {:>8}", value)?;
+                }
+            }
+            writeln!(screen)?;
+        }
+        writeln!(screen)
+    }
+
+    fn render_history(&self, screen: &mut String) -> fmt::Result {
+        let output = self.computer.output();
+        let outputs = &output[output.len().saturating_sub(HISTORY)..];
+        writeln!(
+            screen,
+            "{}inputs{}   {}",
+            BOLD,
+            RESET,
+            join(self.inputs.iter())
+        )?;
+        writeln!(
+            screen,
+            "{}outputs{}  {}\n",
+            BOLD,
+            RESET,
+            join(outputs.iter())
+        )?;
+        writeln!(
+            screen,
+            "+ faster  - slower  p pause  n step  q quit, then enter"
+        )
+    }
+
+    //redraws every frame until the program finishes or Quit arrives
+    pub fn run<W: Write>(&mut self, mut writer: W, controls: &Receiver<Control>) -> io::Result<()> {
+        let mut disconnected = false;
+        loop {
+            loop {
+                match controls.try_recv() {
+                    Ok(Control::Quit) => return Ok(()),
+                    Ok(control) => self.control(control),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        disconnected = true;
+                        break;
+                    }
+                }
+            }
+            self.advance(FRAME);
+            write!(writer, "{}", self.render())?;
+            writer.flush()?;
+            //once paused with nobody left to unpause, the last frame stays up
+            if self.is_finished() || (self.paused && disconnected) {
+                return Ok(());
+            }
+            if self.paused {
+                thread::sleep(FRAME);
+            }
+        }
+    }
+}
+
+fn push<T>(history: &mut VecDeque<T>, value: T) {
+    if history.len() == HISTORY {
+        history.pop_front();
+    }
+    history.push_back(value);
+}
+
+fn join<'a>(values: impl Iterator<Item = &'a i64>) -> String {
+    let values: Vec<String> = values.map(|v| v.to_string()).collect();
+    values.join(", ")
+}
+
+fn format_rate(rate: f64) -> String {
+    match rate {
+        r if r >= 1_000_000.0 => format!("{:.1}M/s", r / 1_000_000.0),
+        r if r >= 1_000.0 => format!("{:.1}k/s", r / 1_000.0),
+        r => format!("{:.0}/s", r),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::load_program_input;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn test_controls() {
+        assert_eq!(Control::parse(" + "), Some(Control::Faster));
+        assert_eq!(Control::parse(""), Some(Control::Pause));
+        assert_eq!(Control::parse("x"), None);
+
+        let mut dashboard = Dashboard::new(IntcodeComputer::new(&[99]), &[]);
+        assert_eq!(dashboard.speed(), None);
+        dashboard.set_speed(Some(500));
+        assert_eq!(dashboard.speed(), Some(100));
+        dashboard.control(Control::Faster);
+        assert_eq!(dashboard.speed(), Some(1_000));
+        for _ in 0..10 {
+            dashboard.control(Control::Slower);
+        }
+        assert_eq!(dashboard.speed(), Some(1));
+    }
+
+    #[test]
+    fn test_render() {
+        //echoes two inputs
+        let program = [3, 20, 4, 20, 3, 20, 4, 20, 99];
+        let mut dashboard = Dashboard::new(IntcodeComputer::new(&program), &[7, 8]);
+        assert!(dashboard.render().contains("ready"));
+        for _ in 0..3 {
+            dashboard.step();
+        }
+        let screen = dashboard.render();
+        assert!(screen.starts_with(CLEAR));
+        assert!(screen.contains(&format!("{}ip{} 6  ", BOLD, RESET)));
+        assert!(screen.contains("running"));
+        assert!(screen.contains("       2  out [20]\n"));
+        assert!(screen.contains(&format!("{}>       6  out [20]", REVERSE)));
+        assert!(screen.contains("       8  hlt\n"));
+        assert!(screen.contains(&format!("{}inputs{}   7, 8\n", BOLD, RESET)));
+        assert!(screen.contains(&format!("{}outputs{}  7\n", BOLD, RESET)));
+        //the cell at ip is highlighted in the memory window
+        assert!(screen.contains(&format!("      20 {}       4{}", REVERSE, RESET)));
+    }
+
+    #[test]
+    fn test_run() {
+        let program = load_program_input("diagnostic_program.txt").unwrap();
+        let mut dashboard = Dashboard::new(IntcodeComputer::new(&program), &[5]);
+        let (_sender, receiver) = channel();
+        let mut screen = Vec::new();
+        dashboard.run(&mut screen, &receiver).unwrap();
+        assert!(dashboard.is_finished());
+        assert_eq!(dashboard.computer().output(), &vec![8_346_937]);
+        let screen = String::from_utf8(screen).unwrap();
+        let last_frame = screen.rsplit(CLEAR).next().unwrap();
+        assert!(last_frame.contains("halted"));
+
+        //an endless loop only stops when asked to
+        let mut dashboard = Dashboard::new(IntcodeComputer::new(&[1105, 1, 0]), &[]);
+        let (sender, receiver) = channel();
+        sender.send(Control::Quit).unwrap();
+        dashboard.run(io::sink(), &receiver).unwrap();
+        assert!(!dashboard.is_finished());
+    }
+}
@@ -0,0 +1,306 @@
+use crate::intcode::{decode_instruction, Instruction, IntcodeComputer};
+use std::{collections::BTreeMap, fmt};
+
+const CELLS_PER_ROW: u64 = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryDump {
+    cells: BTreeMap<u64, i64>,
+    //linear sweep over the image at address 0
+    instructions: BTreeMap<u64, Instruction>,
+}
+
+impl MemoryDump {
+    pub fn new(computer: &IntcodeComputer) -> MemoryDump {
+        MemoryDump::from_cells(computer.memory())
+    }
+
+    pub fn from_program(program: &[i64]) -> MemoryDump {
+        MemoryDump::from_cells(
+            program
+                .iter()
+                .enumerate()
+                .map(|(a, &v)| (a as u64, v))
+                .collect(),
+        )
+    }
+
+    fn from_cells(cells: BTreeMap<u64, i64>) -> MemoryDump {
+        //the image is the run of cells starting at 0, anything past it is data
+        let image: Vec<i64> = cells
+            .iter()
+            .enumerate()
+            .take_while(|(idx, (&a, _))| *idx as u64 == a)
+            .map(|(_, (_, &v))| v)
+            .collect();
+        let mut instructions = BTreeMap::new();
+        let mut address = 0;
+        while address < image.len() as u64 {
+            match decode_instruction(&image, address) {
+                Some(instruction) => {
+                    let length = instruction.length();
+                    instructions.insert(address, instruction);
+                    address += length;
+                }
+                None => address += 1,
+            }
+        }
+        MemoryDump {
+            cells,
+            instructions,
+        }
+    }
+
+    pub fn value(&self, address: u64) -> i64 {
+        self.cells.get(&address).copied().unwrap_or(0)
+    }
+
+    //the start address and decoded form of the instruction covering the address
+    pub fn instruction_at(&self, address: u64) -> Option<(u64, &Instruction)> {
+        self.instructions
+            .range(..=address)
+            .next_back()
+            .filter(|(&start, i)| address < start + i.length())
+            .map(|(&start, i)| (start, i))
+    }
+
+    pub fn diff(&self, after: &MemoryDump) -> MemoryDiff {
+        let mut addresses: Vec<u64> = self
+            .cells
+            .keys()
+            .chain(after.cells.keys())
+            .copied()
+            .collect();
+        addresses.sort();
+        addresses.dedup();
+        let changes = addresses
+            .into_iter()
+            .filter(|&a| self.value(a) != after.value(a))
+            .map(|address| CellChange {
+                address,
+                old: self.value(address),
+                new: after.value(address),
+                instruction: self
+                    .instruction_at(address)
+                    .map(|(start, i)| (start, i.to_string())),
+            })
+            .collect();
+        MemoryDiff { changes }
+    }
+
+    fn end(&self) -> u64 {
+        self.cells.keys().next_back().map_or(0, |a| a + 1)
+    }
+
+    fn next_instruction(&self, address: u64) -> Option<u64> {
+        self.instructions.range(address..).next().map(|(&a, _)| a)
+    }
+
+    fn next_non_zero(&self, address: u64) -> Option<u64> {
+        self.cells
+            .range(address..)
+            .find(|(_, &v)| v != 0)
+            .map(|(&a, _)| a)
+    }
+}
+
+fn printable(values: &[i64]) -> String {
+    values
+        .iter()
+        .map(|&v| match v {
+            32..=126 => v as u8 as char,
+            _ => '.',
+        })
+        .collect()
+}
+
+//like hexdump, but in decimal since that is how intcode addresses itself,
+//runs of zero rows are folded into a single *
+impl fmt::Display for MemoryDump {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let end = self.end();
+        let address_width = end.to_string().len().max(4);
+        let cell_width = self
+            .cells
+            .values()
+            .map(|v| v.to_string().len())
+            .max()
+            .unwrap_or(1);
+        let row_width = (cell_width + 1) * CELLS_PER_ROW as usize;
+
+        let mut address = 0;
+        let mut previous_was_zero = false;
+        let mut folded = false;
+        while address < end {
+            if let Some(instruction) = self.instructions.get(&address) {
+                let values: Vec<String> = (address..address + instruction.length())
+                    .map(|a| format!("{:>w$}", self.value(a), w = cell_width))
+                    .collect();
+                writeln!(
+                    f,
+                    "{:0>aw$}  {:<rw$} {}",
+                    address,
+                    values.join(" "),
+                    instruction,
+                    aw = address_width,
+                    rw = row_width
+                )?;
+                address += instruction.length();
+                previous_was_zero = false;
+                folded = false;
+                continue;
+            }
+
+            let row_end = (address + CELLS_PER_ROW)
+                .min(self.next_instruction(address).unwrap_or(end))
+                .min(end);
+            let values: Vec<i64> = (address..row_end).map(|a| self.value(a)).collect();
+            if values.iter().all(|&v| v == 0) {
+                if previous_was_zero {
+                    if !folded {
+                        writeln!(f, "*")?;
+                        folded = true;
+                    }
+                    //skip straight to whatever comes next
+                    let next = [self.next_instruction(address), self.next_non_zero(address)]
+                        .iter()
+                        .flatten()
+                        .min()
+                        .copied()
+                        .unwrap_or(end);
+                    address = row_end.max(next);
+                    continue;
+                }
+                previous_was_zero = true;
+            } else {
+                previous_was_zero = false;
+                folded = false;
+            }
+            let cells: Vec<String> = values
+                .iter()
+                .map(|v| format!("{:>w$}", v, w = cell_width))
+                .collect();
+            writeln!(
+                f,
+                "{:0>aw$}  {:<rw$} |{}|",
+                address,
+                cells.join(" "),
+                printable(&values),
+                aw = address_width,
+                rw = row_width
+            )?;
+            address = row_end;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CellChange {
+    pub address: u64,
+    pub old: i64,
+    pub new: i64,
+    //the start address and text of the instruction the cell was part of
+    pub instruction: Option<(u64, String)>,
+}
+
+impl fmt::Display for CellChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} -> {}", self.address, self.old, self.new)?;
+        if let Some((start, text)) = &self.instruction {
+            write!(f, "  in {}: {}", start, text)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryDiff {
+    changes: Vec<CellChange>,
+}
+
+impl MemoryDiff {
+    pub fn changes(&self) -> &[CellChange] {
+        &self.changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for MemoryDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dump() {
+        let program = vec![1101, 2, 3, 12, 4, 12, 99, 72, 33, 0, 0, 0, 0];
+        let mut comp = IntcodeComputer::new(&program);
+        comp.run(&mut vec![]);
+        comp.patch(40, 7);
+        let dump = MemoryDump::new(&comp).to_string();
+        let lines: Vec<&str> = dump.lines().map(|l| l.trim_end()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "0000  1101    2    3   12                      add 2, 3, [12]",
+                "0004     4   12                                out [12]",
+                "0006    99                                     hlt",
+                "0007    72   33    0    0    0    5    0    0  |H!......|",
+                "0015     0    0    0    0    0    0    0    0  |........|",
+                "*",
+                "0040     7                                     |.|",
+            ]
+        );
+        assert_eq!(
+            MemoryDump::from_program(&program).instruction_at(5),
+            Some((4, &decode_instruction(&program, 4).unwrap()))
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let program = vec![3, 9, 1001, 9, 5, 10, 4, 10, 99, 0, 0];
+        let mut comp = IntcodeComputer::new(&program);
+        let before = MemoryDump::new(&comp);
+        comp.run(&mut vec![4]);
+        let after = MemoryDump::new(&comp);
+
+        let diff = before.diff(&after);
+        assert_eq!(
+            diff.changes(),
+            &[
+                CellChange {
+                    address: 9,
+                    old: 0,
+                    new: 4,
+                    instruction: None,
+                },
+                CellChange {
+                    address: 10,
+                    old: 0,
+                    new: 9,
+                    instruction: None,
+                },
+            ]
+        );
+        assert_eq!(after.diff(&after), MemoryDiff { changes: vec![] });
+
+        //self modifying code reports the instruction it overwrote
+        let mut comp = IntcodeComputer::new(&[1101, 1, 1, 5, 4, 99]);
+        let before = MemoryDump::new(&comp);
+        comp.step(&mut vec![]);
+        let diff = before.diff(&MemoryDump::new(&comp)).to_string();
+        assert_eq!(diff, "5: 99 -> 2  in 4: out [99]\n");
+    }
+}
@@ -0,0 +1,493 @@
+use crate::intcode_conformance::{engines, Engine, Feature, Outcome};
+use std::{
+    any::Any,
+    fmt,
+    panic::{catch_unwind, AssertUnwindSafe},
+};
+
+//xorshift64*, good enough to pick opcodes
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        //the state must never be zero
+        Rng {
+            state: seed ^ 0x9E37_79B9_7F4A_7C15,
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    pub fn range(&mut self, low: i64, high: i64) -> i64 {
+        low + (self.next_u64() % (high - low + 1) as u64) as i64
+    }
+}
+
+//the opcode subset every compared engine has to support
+const FEATURES: &[Feature] = &[
+    Feature::AddMultiply,
+    Feature::InputOutput,
+    Feature::ImmediateMode,
+    Feature::JumpsAndComparisons,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operand {
+    Immediate(i64),
+    //never written, so safe to feed into arithmetic
+    Constant(usize),
+    Scratch(usize),
+    //any cell of the code, which is never written either
+    Code(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Target {
+    Immediate(usize),
+    //through a pointer stored after the constants
+    Indirect(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Add(Operand, Operand, usize),
+    Multiply(Operand, Operand, usize),
+    LessThan(Operand, Operand, usize),
+    Equals(Operand, Operand, usize),
+    Input(usize),
+    Output(Operand),
+    JumpIfTrue(Operand, Target),
+    JumpIfFalse(Operand, Target),
+    Halt,
+}
+
+impl Op {
+    fn length(self) -> usize {
+        match self {
+            Op::Add(..) | Op::Multiply(..) | Op::LessThan(..) | Op::Equals(..) => 4,
+            Op::Input(_) | Op::Output(_) => 2,
+            Op::JumpIfTrue(..) | Op::JumpIfFalse(..) => 3,
+            Op::Halt => 1,
+        }
+    }
+
+    fn target(self) -> Option<Target> {
+        match self {
+            Op::JumpIfTrue(_, t) | Op::JumpIfFalse(_, t) => Some(t),
+            _ => None,
+        }
+    }
+
+    fn map_target(self, f: impl Fn(usize) -> usize) -> Op {
+        let map = |t| match t {
+            Target::Immediate(i) => Target::Immediate(f(i)),
+            Target::Indirect(i) => Target::Indirect(f(i)),
+        };
+        match self {
+            Op::JumpIfTrue(c, t) => Op::JumpIfTrue(c, map(t)),
+            Op::JumpIfFalse(c, t) => Op::JumpIfFalse(c, map(t)),
+            op => op,
+        }
+    }
+}
+
+//jumps only go forward and writes only hit the scratch cells,
+//so every program halts after at most one pass over its code
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzProgram {
+    ops: Vec<Op>,
+    constants: Vec<i64>,
+    scratch: Vec<i64>,
+    input: i64,
+}
+
+impl FuzzProgram {
+    pub fn generate(rng: &mut Rng, size: usize) -> FuzzProgram {
+        let constants = (0..rng.below(6) + 1)
+            .map(|_| rng.range(-20, 20))
+            .collect::<Vec<_>>();
+        let scratch = (0..rng.below(6) + 1)
+            .map(|_| rng.range(-20, 20))
+            .collect::<Vec<_>>();
+        let op_count = rng.below(size) + 1;
+        let mut ops = Vec::with_capacity(op_count);
+        for index in 0..op_count {
+            let write = rng.below(scratch.len());
+            let op = match rng.below(20) {
+                0..=3 => Op::Add(
+                    stable_operand(rng, constants.len()),
+                    stable_operand(rng, constants.len()),
+                    write,
+                ),
+                4..=6 => Op::Multiply(
+                    stable_operand(rng, constants.len()),
+                    stable_operand(rng, constants.len()),
+                    write,
+                ),
+                7..=8 => Op::LessThan(
+                    any_operand(rng, constants.len(), scratch.len()),
+                    any_operand(rng, constants.len(), scratch.len()),
+                    write,
+                ),
+                9..=10 => Op::Equals(
+                    any_operand(rng, constants.len(), scratch.len()),
+                    any_operand(rng, constants.len(), scratch.len()),
+                    write,
+                ),
+                11..=12 => Op::Input(write),
+                13..=15 => Op::Output(any_operand(rng, constants.len(), scratch.len())),
+                16..=17 => Op::JumpIfTrue(
+                    any_operand(rng, constants.len(), scratch.len()),
+                    forward_target(rng, index, op_count),
+                ),
+                18 => Op::JumpIfFalse(
+                    any_operand(rng, constants.len(), scratch.len()),
+                    forward_target(rng, index, op_count),
+                ),
+                _ => Op::Halt,
+            };
+            ops.push(op);
+        }
+        FuzzProgram {
+            ops,
+            constants,
+            scratch,
+            input: rng.range(-20, 20),
+        }
+    }
+
+    fn addresses(&self) -> Vec<usize> {
+        let mut addresses = Vec::with_capacity(self.ops.len() + 1);
+        let mut address = 0;
+        for op in &self.ops {
+            addresses.push(address);
+            address += op.length();
+        }
+        //the final halt
+        addresses.push(address);
+        addresses
+    }
+
+    pub fn assemble(&self) -> Vec<i64> {
+        let addresses = self.addresses();
+        let code_length = addresses[self.ops.len()] + 1;
+        let pointers: Vec<usize> = self
+            .ops
+            .iter()
+            .filter_map(|op| match op.target() {
+                Some(Target::Indirect(t)) => Some(t),
+                _ => None,
+            })
+            .collect();
+        let constant_base = code_length;
+        let pointer_base = constant_base + self.constants.len();
+        let scratch_base = pointer_base + pointers.len();
+
+        let operand = |o: Operand| match o {
+            Operand::Immediate(v) => (1, v),
+            Operand::Constant(i) => (0, (constant_base + i) as i64),
+            Operand::Scratch(i) => (0, (scratch_base + i) as i64),
+            Operand::Code(a) => (0, (a % code_length) as i64),
+        };
+        let write = |w: usize| (0, (scratch_base + w) as i64);
+        let mut next_pointer = 0;
+        let mut target = |t: Target| match t {
+            Target::Immediate(i) => (1, addresses[i] as i64),
+            Target::Indirect(_) => {
+                next_pointer += 1;
+                (0, (pointer_base + next_pointer - 1) as i64)
+            }
+        };
+
+        let mut program = Vec::new();
+        for &op in &self.ops {
+            let (opcode, parameters) = match op {
+                Op::Add(a, b, w) => (1, vec![operand(a), operand(b), write(w)]),
+                Op::Multiply(a, b, w) => (2, vec![operand(a), operand(b), write(w)]),
+                Op::LessThan(a, b, w) => (7, vec![operand(a), operand(b), write(w)]),
+                Op::Equals(a, b, w) => (8, vec![operand(a), operand(b), write(w)]),
+                Op::Input(w) => (3, vec![write(w)]),
+                Op::Output(a) => (4, vec![operand(a)]),
+                Op::JumpIfTrue(c, t) => (5, vec![operand(c), target(t)]),
+                Op::JumpIfFalse(c, t) => (6, vec![operand(c), target(t)]),
+                Op::Halt => (99, vec![]),
+            };
+            let modes = parameters
+                .iter()
+                .enumerate()
+                .map(|(i, (mode, _))| mode * 10_i64.pow(i as u32 + 2))
+                .sum::<i64>();
+            program.push(opcode + modes);
+            program.extend(parameters.iter().map(|(_, value)| value));
+        }
+        program.push(99);
+        program.extend(&self.constants);
+        program.extend(pointers.iter().map(|&t| addresses[t] as i64));
+        program.extend(&self.scratch);
+        program
+    }
+
+    //one value for every input instruction, the legacy engine cannot take more
+    pub fn inputs(&self) -> Vec<i64> {
+        let count = self
+            .ops
+            .iter()
+            .filter(|op| matches!(op, Op::Input(_)))
+            .count();
+        vec![self.input; count]
+    }
+
+    fn without_op(&self, index: usize) -> FuzzProgram {
+        let mut ops = self.ops.clone();
+        ops.remove(index);
+        //jumps to the removed op land on the one that followed it
+        let ops = ops
+            .into_iter()
+            .map(|op| op.map_target(|t| if t > index { t - 1 } else { t }))
+            .collect();
+        FuzzProgram {
+            ops,
+            ..self.clone()
+        }
+    }
+}
+
+fn stable_operand(rng: &mut Rng, constants: usize) -> Operand {
+    match rng.below(3) {
+        0 => Operand::Immediate(rng.range(-50, 50)),
+        1 => Operand::Constant(rng.below(constants)),
+        _ => Operand::Code(rng.below(64)),
+    }
+}
+
+fn any_operand(rng: &mut Rng, constants: usize, scratch: usize) -> Operand {
+    match rng.below(4) {
+        0 => Operand::Scratch(rng.below(scratch)),
+        _ => stable_operand(rng, constants),
+    }
+}
+
+fn forward_target(rng: &mut Rng, index: usize, op_count: usize) -> Target {
+    //op_count is the final halt
+    let t = index + 1 + rng.below(op_count - index);
+    match rng.below(2) {
+        0 => Target::Immediate(t),
+        _ => Target::Indirect(t),
+    }
+}
+
+#[derive(Debug)]
+pub struct Divergence {
+    pub program: FuzzProgram,
+    pub reference: (&'static str, Result<Outcome, String>),
+    pub other: (&'static str, Result<Outcome, String>),
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let program = self.program.assemble();
+        let program: Vec<String> = program.iter().map(|v| v.to_string()).collect();
+        writeln!(f, "program: {}", program.join(","))?;
+        writeln!(f, "input: {:?}", self.program.inputs())?;
+        writeln!(f, "{}: {:?}", self.reference.0, self.reference.1)?;
+        write!(f, "{}: {:?}", self.other.0, self.other.1)
+    }
+}
+
+pub(crate) fn run(engine: &dyn Engine, program: &[i64], input: &[i64]) -> Result<Outcome, String> {
+    let addresses: Vec<u64> = (0..program.len() as u64).collect();
+    catch_unwind(AssertUnwindSafe(|| engine.run(program, input, &addresses)))
+        .map_err(|e| panic_message(&*e))
+}
+
+//what a caught panic was raised with
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<String>()
+        .cloned()
+        .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+        .unwrap_or_else(|| "panicked".to_string())
+}
+
+pub(crate) fn agree(reference: &Result<Outcome, String>, other: &Result<Outcome, String>) -> bool {
+    match (reference, other) {
+        (Ok(r), Ok(o)) => {
+            r.output == o.output
+                && r.halted == o.halted
+                && match (&r.memory, &o.memory) {
+                    (Some(rm), Some(om)) => rm == om,
+                    _ => true,
+                }
+        }
+        (Err(_), Err(_)) => true,
+        _ => false,
+    }
+}
+
+//the first engine is the reference the others are held to
+pub fn compare(engines: &[Box<dyn Engine>], program: &FuzzProgram) -> Option<Divergence> {
+    let image = program.assemble();
+    let input = program.inputs();
+    let mut engines = engines
+        .iter()
+        .filter(|e| FEATURES.iter().all(|f| e.features().contains(f)));
+    let reference = engines.next()?;
+    let reference_outcome = run(reference.as_ref(), &image, &input);
+    for engine in engines {
+        let outcome = run(engine.as_ref(), &image, &input);
+        if !agree(&reference_outcome, &outcome) {
+            return Some(Divergence {
+                program: program.clone(),
+                reference: (reference.name(), reference_outcome),
+                other: (engine.name(), outcome),
+            });
+        }
+    }
+    None
+}
+
+//drops ops and shrinks values for as long as the engines still disagree
+pub fn minimize(engines: &[Box<dyn Engine>], divergence: Divergence) -> Divergence {
+    let mut best = divergence;
+    loop {
+        let program = &best.program;
+        let mut candidates: Vec<FuzzProgram> = (0..program.ops.len())
+            .map(|i| program.without_op(i))
+            .collect();
+        if program.input != 0 {
+            candidates.push(FuzzProgram {
+                input: 0,
+                ..program.clone()
+            });
+        }
+        for (i, &value) in program.constants.iter().chain(&program.scratch).enumerate() {
+            if value != 0 {
+                let mut candidate = program.clone();
+                if i < candidate.constants.len() {
+                    candidate.constants[i] = 0;
+                } else {
+                    candidate.scratch[i - program.constants.len()] = 0;
+                }
+                candidates.push(candidate);
+            }
+        }
+        match candidates.iter().find_map(|c| compare(engines, c)) {
+            Some(smaller) => best = smaller,
+            None => return best,
+        }
+    }
+}
+
+pub fn fuzz_with(
+    engines: &[Box<dyn Engine>],
+    rng: &mut Rng,
+    iterations: usize,
+    size: usize,
+) -> Option<Divergence> {
+    for _ in 0..iterations {
+        let program = FuzzProgram::generate(rng, size);
+        if let Some(divergence) = compare(engines, &program) {
+            return Some(minimize(engines, divergence));
+        }
+    }
+    None
+}
+
+pub fn fuzz(seed: u64, iterations: usize) -> Option<Divergence> {
+    fuzz_with(&engines(), &mut Rng::new(seed), iterations, 30)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode_conformance::IntcodeEngine;
+
+    //drops the last output, standing in for a broken engine
+    struct LossyEngine;
+
+    impl Engine for LossyEngine {
+        fn name(&self) -> &'static str {
+            "lossy"
+        }
+
+        fn features(&self) -> &'static [Feature] {
+            IntcodeEngine.features()
+        }
+
+        fn run(&self, program: &[i64], input: &[i64], addresses: &[u64]) -> Outcome {
+            let mut outcome = IntcodeEngine.run(program, input, addresses);
+            outcome.output.pop();
+            outcome
+        }
+    }
+
+    #[test]
+    fn test_rng_is_deterministic() {
+        let mut a = Rng::new(7);
+        let mut b = Rng::new(7);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+        let mut rng = Rng::new(0);
+        for _ in 0..1000 {
+            let v = rng.range(-3, 3);
+            assert!((-3..=3).contains(&v));
+        }
+    }
+
+    #[test]
+    fn test_generated_programs_halt() {
+        let mut rng = Rng::new(1);
+        for _ in 0..200 {
+            let program = FuzzProgram::generate(&mut rng, 30);
+            let outcome = IntcodeEngine.run(&program.assemble(), &program.inputs(), &[]);
+            assert!(outcome.halted, "{:?}", program);
+        }
+    }
+
+    #[test]
+    fn test_relative_read_agrees() {
+        //the optimizer used to fold away the constant the output reads
+        let program = [1101, 2, 3, 20, 204, 2, 99];
+        let engines: Vec<_> = engines()
+            .into_iter()
+            .filter(|e| e.features().contains(&Feature::RelativeMode))
+            .collect();
+        assert!(engines.len() > 1);
+        let reference = run(engines[0].as_ref(), &program, &[]);
+        for engine in &engines[1..] {
+            let outcome = run(engine.as_ref(), &program, &[]);
+            assert!(agree(&reference, &outcome), "{}", engine.name());
+        }
+    }
+
+    #[test]
+    fn test_engines_agree() {
+        if let Some(divergence) = fuzz(2019, 500) {
+            panic!("{}", divergence);
+        }
+    }
+
+    #[test]
+    fn test_minimize_divergence() {
+        let engines: Vec<Box<dyn Engine>> = vec![Box::new(IntcodeEngine), Box::new(LossyEngine)];
+        let divergence = fuzz_with(&engines, &mut Rng::new(3), 100, 30)
+            .expect("The lossy engine should diverge");
+        //a single output is all it takes
+        assert_eq!(divergence.program.ops.len(), 1);
+        assert!(matches!(divergence.program.ops[0], Op::Output(_)));
+        assert!(divergence.to_string().starts_with("program: "));
+    }
+}
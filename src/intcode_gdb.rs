@@ -0,0 +1,596 @@
+use crate::intcode::{ComputerState, InstructionSet, IntcodeComputer};
+use std::{
+    collections::HashSet,
+    convert::TryInto,
+    io,
+    io::{BufReader, Read, Write},
+    net::{Shutdown, TcpListener},
+    sync::mpsc::{channel, Receiver},
+    thread,
+};
+
+const CELL_SIZE: u64 = 8;
+//the most bytes one m packet may ask for
+const MAX_READ_LENGTH: u64 = 0x2000;
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.intcode.core">
+    <reg name="ip" bitsize="64" type="code_ptr" regnum="0"/>
+    <reg name="rb" bitsize="64" type="data_ptr" regnum="1"/>
+  </feature>
+</target>
+"#;
+
+enum Packet {
+    Command(String),
+    Interrupt,
+    Corrupt,
+}
+
+pub struct GdbStub<'a> {
+    computer: &'a mut IntcodeComputer,
+    input: &'a mut Vec<i64>,
+    breakpoints: HashSet<u64>,
+    stop_reason: String,
+    //a 0x03 that already stopped a continue, so it needs no reply of its own
+    interrupt_handled: bool,
+}
+
+impl<'a> GdbStub<'a> {
+    //a bad instruction stops the program with a fault the debugger is told about
+    pub fn new(computer: &'a mut IntcodeComputer, input: &'a mut Vec<i64>) -> GdbStub<'a> {
+        if computer.instruction_set().is_none() {
+            computer.restrict(InstructionSet::Day9);
+        }
+        GdbStub {
+            computer,
+            input,
+            breakpoints: HashSet::new(),
+            stop_reason: "S05".to_string(),
+            interrupt_handled: false,
+        }
+    }
+
+    pub fn serve(&mut self, port: u16) -> io::Result<()> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        self.serve_on(&listener)
+    }
+
+    pub fn serve_on(&mut self, listener: &TcpListener) -> io::Result<()> {
+        let (stream, _) = listener.accept()?;
+        let reader = BufReader::new(stream.try_clone()?);
+        //lets the packet reader see the end of the stream
+        self.session_closing(reader, &stream, || stream.shutdown(Shutdown::Both).is_ok())
+    }
+
+    //packets are read on their own thread, so a 0x03 can stop a continue that never ends
+    pub fn session<R, W>(&mut self, reader: R, writer: W) -> io::Result<()>
+    where
+        R: Read + Send + 'static,
+        W: Write,
+    {
+        self.session_closing(reader, writer, || false)
+    }
+
+    //`close` is called once the session is over and says whether the reader now sees the end
+    //of its stream. a reader that does not, like stdin, is left to stop at its next packet
+    fn session_closing<R, W, C>(&mut self, mut reader: R, writer: W, close: C) -> io::Result<()>
+    where
+        R: Read + Send + 'static,
+        W: Write,
+        C: FnOnce() -> bool,
+    {
+        let interrupt = self.computer.interrupt_handle();
+        let (sender, receiver) = channel();
+        let packet_reader = thread::spawn(move || loop {
+            let packet = read_packet(&mut reader);
+            if let Ok(Some(Packet::Interrupt)) = packet {
+                interrupt.request();
+            }
+            let done = !matches!(packet, Ok(Some(_)));
+            if sender.send(packet).is_err() || done {
+                break;
+            }
+        });
+        let result = self.serve_packets(&receiver, writer);
+        drop(receiver);
+        if close() || packet_reader.is_finished() {
+            let _ = packet_reader.join();
+        }
+        result
+    }
+
+    fn serve_packets<W: Write>(
+        &mut self,
+        receiver: &Receiver<io::Result<Option<Packet>>>,
+        mut writer: W,
+    ) -> io::Result<()> {
+        let interrupt = self.computer.interrupt_handle();
+        loop {
+            let packet = match receiver.recv() {
+                Ok(packet) => packet?,
+                Err(_) => None,
+            };
+            let packet = match packet {
+                Some(packet) => packet,
+                None => return Ok(()),
+            };
+            let command = match packet {
+                Packet::Command(command) => command,
+                Packet::Interrupt if self.interrupt_handled => {
+                    self.interrupt_handled = false;
+                    continue;
+                }
+                Packet::Interrupt => {
+                    interrupt.cancel();
+                    write_packet(&mut writer, "S02")?;
+                    writer.flush()?;
+                    continue;
+                }
+                Packet::Corrupt => {
+                    writer.write_all(b"-")?;
+                    continue;
+                }
+            };
+            writer.write_all(b"+")?;
+            let (replies, close) = self.handle_command(&command);
+            for reply in replies {
+                write_packet(&mut writer, &reply)?;
+            }
+            writer.flush()?;
+            if close {
+                return Ok(());
+            }
+        }
+    }
+
+    fn handle_command(&mut self, command: &str) -> (Vec<String>, bool) {
+        let reply = match command.chars().next() {
+            Some('?') => self.stop_reason.clone(),
+            Some('g') => format!("{}{}", self.register(0), self.register(1)),
+            Some('G') => match (
+                command.get(1..).and_then(decode_word),
+                command.get(17..).and_then(decode_word),
+            ) {
+                (Some(instruction_pointer), Some(relative_base_offset)) => {
+                    self.set_register(0, instruction_pointer);
+                    self.set_register(1, relative_base_offset);
+                    "OK".to_string()
+                }
+                _ => "E01".to_string(),
+            },
+            Some('p') => match u64::from_str_radix(&command[1..], 16) {
+                Ok(register) if register < 2 => self.register(register),
+                _ => "E01".to_string(),
+            },
+            Some('P') => self.write_register(&command[1..]),
+            Some('m') => self.read_memory(&command[1..]),
+            Some('M') => self.write_memory(&command[1..]),
+            Some('s') => self.resume(true),
+            Some('c') => self.resume(false),
+            Some('Z') => self.breakpoint(&command[1..], true),
+            Some('z') => self.breakpoint(&command[1..], false),
+            Some('H') => "OK".to_string(),
+            Some('k') => return (Vec::new(), true),
+            Some('D') => return (vec!["OK".to_string()], true),
+            Some('q') => return (self.query(&command[1..]), false),
+            _ => String::new(),
+        };
+        (vec![reply], false)
+    }
+
+    fn register(&self, register: u64) -> String {
+        let cell_address = match register {
+            0 => self.computer.instruction_pointer(),
+            _ => self.computer.relative_base_offset(),
+        };
+        encode_word((cell_address * CELL_SIZE) as i64)
+    }
+
+    fn set_register(&mut self, register: u64, byte_address: i64) {
+        let cell_address = byte_address as u64 / CELL_SIZE;
+        match register {
+            0 => self.computer.set_instruction_pointer(cell_address),
+            _ => self.computer.set_relative_base_offset(cell_address),
+        }
+    }
+
+    fn resume(&mut self, single_step: bool) -> String {
+        self.stop_reason = loop {
+            self.computer.step(self.input);
+            match self.computer.state() {
+                ComputerState::Running => {
+                    if single_step
+                        || self
+                            .breakpoints
+                            .contains(&self.computer.instruction_pointer())
+                    {
+                        break "S05";
+                    }
+                }
+                ComputerState::Halted => break "W00",
+                ComputerState::Faulted(_) => break "S0b",
+                ComputerState::Waiting => break "S05",
+                ComputerState::Paused => {
+                    self.interrupt_handled = true;
+                    break "S02";
+                }
+            }
+        }
+        .to_string();
+        self.stop_reason.clone()
+    }
+
+    fn write_register(&mut self, arguments: &str) -> String {
+        let mut parts = arguments.splitn(2, '=');
+        let register = parts.next().and_then(|r| u64::from_str_radix(r, 16).ok());
+        let value = parts.next().and_then(decode_word);
+        match (register, value) {
+            (Some(register), Some(value)) if register < 2 => self.set_register(register, value),
+            _ => return "E01".to_string(),
+        }
+        "OK".to_string()
+    }
+
+    fn read_memory(&mut self, arguments: &str) -> String {
+        let (address, end) = match parse_address_length(arguments) {
+            Some((address, length)) if length <= MAX_READ_LENGTH => {
+                match address.checked_add(length) {
+                    Some(end) => (address, end),
+                    None => return "E01".to_string(),
+                }
+            }
+            _ => return "E01".to_string(),
+        };
+        let mut reply = String::new();
+        for byte_address in address..end {
+            let cell = self.computer.peek(byte_address / CELL_SIZE).to_le_bytes();
+            reply.push_str(&format!(
+                "{:02x}",
+                cell[(byte_address % CELL_SIZE) as usize]
+            ));
+        }
+        reply
+    }
+
+    fn write_memory(&mut self, arguments: &str) -> String {
+        let mut parts = arguments.splitn(2, ':');
+        let range = parts.next().and_then(parse_address_length);
+        let data = parts.next().and_then(decode_hex);
+        let (address, data) = match (range, data) {
+            (Some((address, length)), Some(data))
+                if data.len() as u64 == length && address.checked_add(length).is_some() =>
+            {
+                (address, data)
+            }
+            _ => return "E01".to_string(),
+        };
+        for (offset, byte) in data.iter().enumerate() {
+            let byte_address = address + offset as u64;
+            let cell_address = byte_address / CELL_SIZE;
+            let mut cell = self.computer.peek(cell_address).to_le_bytes();
+            cell[(byte_address % CELL_SIZE) as usize] = *byte;
+            self.computer.patch(cell_address, i64::from_le_bytes(cell));
+        }
+        "OK".to_string()
+    }
+
+    fn breakpoint(&mut self, arguments: &str, insert: bool) -> String {
+        let parts: Vec<&str> = arguments.split(',').collect();
+        if parts.len() < 2 || parts[0] != "0" {
+            return String::new();
+        }
+        let address = match u64::from_str_radix(parts[1], 16) {
+            Ok(address) => address / CELL_SIZE,
+            Err(_) => return "E01".to_string(),
+        };
+        if insert {
+            self.breakpoints.insert(address);
+        } else {
+            self.breakpoints.remove(&address);
+        }
+        "OK".to_string()
+    }
+
+    fn query(&mut self, query: &str) -> Vec<String> {
+        let reply = if query.starts_with("Supported") {
+            "PacketSize=4000;qXfer:features:read+".to_string()
+        } else if query.starts_with("Attached") {
+            "1".to_string()
+        } else if query == "C" {
+            "QC1".to_string()
+        } else if query == "fThreadInfo" {
+            "m1".to_string()
+        } else if query == "sThreadInfo" {
+            "l".to_string()
+        } else if let Some(arguments) = query.strip_prefix("Xfer:features:read:target.xml:") {
+            read_target_xml(arguments)
+        } else if let Some(hex_command) = query.strip_prefix("Rcmd,") {
+            return self.monitor_command(hex_command);
+        } else {
+            String::new()
+        };
+        vec![reply]
+    }
+
+    fn monitor_command(&mut self, hex_command: &str) -> Vec<String> {
+        let command = match decode_hex(hex_command).and_then(|c| String::from_utf8(c).ok()) {
+            Some(command) => command,
+            None => return vec!["E01".to_string()],
+        };
+        let mut words = command.split_whitespace();
+        let text = match words.next() {
+            Some("input") => {
+                let mut values = Vec::new();
+                for word in words {
+                    match word.parse::<i64>() {
+                        Ok(value) => values.push(value),
+                        Err(_) => return vec!["E01".to_string()],
+                    }
+                }
+                for value in values {
+                    self.input.insert(0, value);
+                }
+                format!("{} input values queued\n", self.input.len())
+            }
+            Some("output") => format!("{:?}\n", self.computer.output()),
+            _ => "Monitor commands are: input <values...>, output\n".to_string(),
+        };
+        vec![
+            format!("O{}", encode_hex(text.as_bytes())),
+            "OK".to_string(),
+        ]
+    }
+}
+
+fn read_target_xml(arguments: &str) -> String {
+    let (offset, length) = match parse_address_length(arguments) {
+        Some(range) => range,
+        None => return "E01".to_string(),
+    };
+    let start = (offset as usize).min(TARGET_XML.len());
+    let end = start.saturating_add(length as usize).min(TARGET_XML.len());
+    let prefix = if end == TARGET_XML.len() { "l" } else { "m" };
+    format!("{}{}", prefix, &TARGET_XML[start..end])
+}
+
+fn read_byte<R: Read>(reader: &mut R) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    match reader.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+fn read_packet<R: Read>(reader: &mut R) -> io::Result<Option<Packet>> {
+    loop {
+        match read_byte(reader)? {
+            Some(b'$') => break,
+            Some(0x03) => return Ok(Some(Packet::Interrupt)),
+            Some(_) => continue,
+            None => return Ok(None),
+        }
+    }
+    let mut data = Vec::new();
+    loop {
+        match read_byte(reader)? {
+            Some(b'#') => break,
+            Some(byte) => data.push(byte),
+            None => return Ok(None),
+        }
+    }
+    let mut checksum = [0; 2];
+    reader.read_exact(&mut checksum)?;
+    let expected = std::str::from_utf8(&checksum)
+        .ok()
+        .and_then(|c| u8::from_str_radix(c, 16).ok());
+    match (expected, String::from_utf8(data)) {
+        (Some(expected), Ok(command)) if expected == calculate_checksum(&command) => {
+            Ok(Some(Packet::Command(command)))
+        }
+        _ => Ok(Some(Packet::Corrupt)),
+    }
+}
+
+fn write_packet<W: Write>(writer: &mut W, data: &str) -> io::Result<()> {
+    write!(writer, "${}#{:02x}", data, calculate_checksum(data))
+}
+
+fn calculate_checksum(data: &str) -> u8 {
+    data.bytes().fold(0, |sum, byte| sum.wrapping_add(byte))
+}
+
+fn parse_address_length(arguments: &str) -> Option<(u64, u64)> {
+    let mut parts = arguments.splitn(2, ',');
+    let address = u64::from_str_radix(parts.next()?, 16).ok()?;
+    let length = u64::from_str_radix(parts.next()?, 16).ok()?;
+    Some((address, length))
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 == 1 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(hex.get(idx..idx + 2)?, 16).ok())
+        .collect()
+}
+
+fn encode_word(value: i64) -> String {
+    encode_hex(&value.to_le_bytes())
+}
+
+fn decode_word(hex: &str) -> Option<i64> {
+    let bytes = decode_hex(hex.get(..16)?)?;
+    Some(i64::from_le_bytes(bytes.as_slice().try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::Cursor, net::TcpStream};
+
+    fn packet(data: &str) -> String {
+        format!("${}#{:02x}", data, calculate_checksum(data))
+    }
+
+    fn replies(written: &[u8]) -> Vec<String> {
+        let written = String::from_utf8(written.to_vec()).unwrap();
+        written
+            .split('$')
+            .skip(1)
+            .map(|reply| reply.split('#').next().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(packet("OK"), "$OK#9a");
+        assert_eq!(packet("qSupported"), "$qSupported#37");
+    }
+
+    #[test]
+    fn test_session() {
+        let program = vec![3, 9, 1001, 9, 5, 9, 4, 9, 99, 0];
+        let mut computer = IntcodeComputer::new(&program);
+        let mut input = Vec::new();
+        let commands = [
+            "?",
+            "qRcmd,696e707574203337",
+            "g",
+            "s",
+            "p0",
+            "m48,8",
+            "Z0,30,1",
+            "c",
+            "m48,8",
+            "M48,8:0100000000000000",
+            "P0=3000000000000000",
+            "z0,30,1",
+            "c",
+            "qRcmd,6f7574707574",
+            "k",
+        ];
+        let script: String = commands.iter().map(|c| packet(c)).collect();
+        let mut written = Vec::new();
+        GdbStub::new(&mut computer, &mut input)
+            .session(Cursor::new(script), &mut written)
+            .unwrap();
+
+        assert_eq!(
+            replies(&written),
+            vec![
+                "S05".to_string(),
+                format!("O{}", encode_hex(b"1 input values queued\n")),
+                "OK".to_string(),
+                "00000000000000000000000000000000".to_string(),
+                "S05".to_string(),
+                "1000000000000000".to_string(),
+                "2500000000000000".to_string(),
+                "OK".to_string(),
+                "S05".to_string(),
+                "2a00000000000000".to_string(),
+                "OK".to_string(),
+                "OK".to_string(),
+                "OK".to_string(),
+                "W00".to_string(),
+                format!("O{}", encode_hex(b"[1]\n")),
+                "OK".to_string(),
+            ]
+        );
+        assert_eq!(computer.output(), &vec![1]);
+    }
+
+    #[test]
+    fn test_interrupt_continue() {
+        //loops forever, only the 0x03 after the c can stop it
+        let mut computer = IntcodeComputer::new(&[1105, 1, 0]);
+        let mut input = Vec::new();
+        let script = format!("{}\x03{}{}", packet("c"), packet("?"), packet("k"));
+        let mut written = Vec::new();
+        GdbStub::new(&mut computer, &mut input)
+            .session(Cursor::new(script), &mut written)
+            .unwrap();
+        assert_eq!(replies(&written), vec!["S02", "S02"]);
+
+        //while stopped the 0x03 is answered straight away and does not stick around
+        let mut computer = IntcodeComputer::new(&[1101, 1, 1, 5, 99, 0]);
+        let mut input = Vec::new();
+        let script = format!("\x03{}{}", packet("c"), packet("k"));
+        let mut written = Vec::new();
+        GdbStub::new(&mut computer, &mut input)
+            .session(Cursor::new(script), &mut written)
+            .unwrap();
+        assert_eq!(replies(&written), vec!["S02", "W00"]);
+    }
+
+    #[test]
+    fn test_fault_stops() {
+        //an unknown opcode, then a jump to a negative address
+        for program in &[vec![42], vec![1105, 1, -1]] {
+            let mut computer = IntcodeComputer::new(program);
+            let mut input = Vec::new();
+            let script = format!("{}{}{}", packet("c"), packet("?"), packet("k"));
+            let mut written = Vec::new();
+            GdbStub::new(&mut computer, &mut input)
+                .session(Cursor::new(script), &mut written)
+                .unwrap();
+            assert_eq!(replies(&written), vec!["S0b", "S0b"]);
+            assert!(computer.fault().is_some());
+        }
+
+        //a narrower set is kept
+        let mut computer = IntcodeComputer::new(&[109, 1, 99]);
+        computer.restrict(InstructionSet::Day5);
+        let mut input = Vec::new();
+        GdbStub::new(&mut computer, &mut input);
+        assert_eq!(computer.instruction_set(), Some(InstructionSet::Day5));
+    }
+
+    #[test]
+    fn test_bad_memory_ranges() {
+        let mut computer = IntcodeComputer::new(&[99]);
+        let mut input = Vec::new();
+        let mut stub = GdbStub::new(&mut computer, &mut input);
+        assert_eq!(stub.read_memory("ffffffffffffffff,10"), "E01");
+        assert_eq!(stub.read_memory("0,ffffffffffff"), "E01");
+        assert_eq!(stub.read_memory("0,2"), "6300");
+        assert_eq!(stub.write_memory("ffffffffffffffff,2:0102"), "E01");
+        assert_eq!(
+            read_target_xml("10,ffffffffffffffff").len(),
+            TARGET_XML.len() - 15
+        );
+    }
+
+    #[test]
+    fn test_serve_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let client = std::thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            let mut received = Vec::new();
+            stream
+                .write_all(format!("{}{}", packet("g"), packet("D")).as_bytes())
+                .unwrap();
+            stream.read_to_end(&mut received).unwrap();
+            received
+        });
+
+        let mut computer = IntcodeComputer::new(&[99]);
+        let mut input = Vec::new();
+        GdbStub::new(&mut computer, &mut input)
+            .serve_on(&listener)
+            .unwrap();
+        let received = client.join().unwrap();
+        assert_eq!(
+            replies(&received),
+            vec!["00000000000000000000000000000000", "OK"]
+        );
+    }
+}
@@ -0,0 +1,323 @@
+use crate::{
+    intcode::{
+        decode_instruction, ComputerState, Instruction, IntcodeComputer, Limits, OpCode,
+        ParameterMode,
+    },
+    intcode_conformance::Engine,
+    intcode_fuzz::{agree, run},
+};
+
+//a candidate that runs this long is taken to be stuck in a loop
+const MAX_STEPS: u64 = 100_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Behaviour {
+    //whatever was printed before the computer stopped
+    pub output: Vec<i64>,
+    //running means the step limit was hit
+    pub outcome: ComputerState,
+}
+
+pub fn behaviour(program: &[i64], input: &[i64], limits: Limits) -> Behaviour {
+    let mut computer = IntcodeComputer::with_limits(program, limits);
+    let mut input: Vec<i64> = input.iter().rev().copied().collect();
+    computer.run_for(&mut input, MAX_STEPS);
+    Behaviour {
+        output: computer.output().clone(),
+        outcome: computer.state().clone(),
+    }
+}
+
+pub fn faults(limits: Limits) -> impl Fn(&[i64], &[i64]) -> bool {
+    move |program, input| {
+        matches!(
+            behaviour(program, input, limits).outcome,
+            ComputerState::Faulted(_)
+        )
+    }
+}
+
+//any fault without limits when `kind` is empty, otherwise one whose kind starts with it,
+//like `NegativeAddress` or `UndefinedInstruction(0)`
+pub fn faults_with(kind: &str) -> impl Fn(&[i64], &[i64]) -> bool {
+    let kind = kind.to_string();
+    move |program, input| match behaviour(program, input, Limits::default()).outcome {
+        ComputerState::Faulted(fault) => format!("{:?}", fault.kind()).starts_with(&kind),
+        _ => false,
+    }
+}
+
+pub fn outputs(value: i64) -> impl Fn(&[i64], &[i64]) -> bool {
+    move |program, input| {
+        behaviour(program, input, Limits::default())
+            .output
+            .contains(&value)
+    }
+}
+
+//only candidates that stop on IntcodeComputer are handed to the engines,
+//most of them have no step limit of their own
+pub fn diverges<'a>(
+    reference: &'a dyn Engine,
+    other: &'a dyn Engine,
+) -> impl Fn(&[i64], &[i64]) -> bool + 'a {
+    move |program, input| {
+        if behaviour(program, input, Limits::default()).outcome == ComputerState::Running {
+            return false;
+        }
+        !agree(&run(reference, program, input), &run(other, program, input))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reproducer {
+    pub program: Vec<i64>,
+    pub input: Vec<i64>,
+    //how many times the predicate was evaluated
+    pub tests: usize,
+}
+
+//shrinks the program and its input for as long as `predicate` keeps holding,
+//None when it does not hold to begin with
+pub fn minimize<P>(program: &[i64], input: &[i64], predicate: P) -> Option<Reproducer>
+where
+    P: Fn(&[i64], &[i64]) -> bool,
+{
+    let mut tests = 0;
+    let mut test = |program: &[i64], input: &[i64]| {
+        tests += 1;
+        predicate(program, input)
+    };
+    if !test(program, input) {
+        return None;
+    }
+    let mut program = program.to_vec();
+    let mut input = input.to_vec();
+    loop {
+        let before = (program.clone(), input.clone());
+        program = remove_instructions(program, true, |p| test(p, &input));
+        program = remove_instructions(program, false, |p| test(p, &input));
+        program = remove_chunks(program, |p| test(p, &input));
+        input = remove_chunks(input, |i| test(&program, i));
+        program = simplify_values(program, |p| test(p, &input));
+        input = simplify_values(input, |i| test(&program, i));
+        if (&program, &input) == (&before.0, &before.1) {
+            break;
+        }
+    }
+    Some(Reproducer {
+        program,
+        input,
+        tests,
+    })
+}
+
+//takes out ever smaller chunks, keeping every removal the test still passes without
+fn remove_chunks<T, F>(mut items: Vec<T>, mut test: F) -> Vec<T>
+where
+    T: Clone,
+    F: FnMut(&[T]) -> bool,
+{
+    let mut chunk = (items.len() / 2).max(1);
+    loop {
+        let mut start = 0;
+        while start < items.len() {
+            let end = (start + chunk).min(items.len());
+            let candidate: Vec<T> = items[..start]
+                .iter()
+                .chain(&items[end..])
+                .cloned()
+                .collect();
+            if test(&candidate) {
+                items = candidate;
+            } else {
+                start += chunk;
+            }
+        }
+        if chunk == 1 {
+            return items;
+        }
+        chunk /= 2;
+    }
+}
+
+//a linear sweep like the disassembler's, anything that does not decode is one data cell
+struct Unit {
+    address: i64,
+    length: i64,
+    instruction: Option<Instruction>,
+}
+
+fn units(program: &[i64]) -> Vec<Unit> {
+    let mut units = Vec::new();
+    let mut address = 0;
+    while address < program.len() {
+        let instruction = decode_instruction(program, address as u64).filter(|i| {
+            address + i.length() as usize <= program.len()
+                && program[address..address + i.length() as usize] == i.encode()[..]
+        });
+        let length = instruction.as_ref().map_or(1, |i| i.length() as usize);
+        units.push(Unit {
+            address: address as i64,
+            length: length as i64,
+            instruction,
+        });
+        address += length;
+    }
+    units
+}
+
+//when `relocate` is set, position parameters and immediate jump targets
+//are moved down by the number of cells removed before what they point at
+fn rebuild(program: &[i64], units: &[Unit], kept: &[usize], relocate: bool) -> Vec<i64> {
+    let removed: Vec<&Unit> = units
+        .iter()
+        .enumerate()
+        .filter(|(idx, _)| !kept.contains(idx))
+        .map(|(_, unit)| unit)
+        .collect();
+    let moved = |address: i64| {
+        address
+            - removed
+                .iter()
+                .filter(|unit| unit.address < address)
+                .map(|unit| unit.length.min(address - unit.address))
+                .sum::<i64>()
+    };
+    let mut image = Vec::new();
+    for unit in kept.iter().map(|&idx| &units[idx]) {
+        let start = unit.address as usize;
+        match &unit.instruction {
+            Some(instruction) if relocate => {
+                let jumps = matches!(
+                    instruction.opcode(),
+                    OpCode::JumpIfTrue | OpCode::JumpIfFalse
+                );
+                let parameters = instruction
+                    .parameters()
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, &(mode, value))| match mode {
+                        ParameterMode::Position if value >= 0 => (mode, moved(value)),
+                        ParameterMode::Immediate if jumps && idx == 1 && value >= 0 => {
+                            (mode, moved(value))
+                        }
+                        _ => (mode, value),
+                    })
+                    .collect();
+                image.extend(Instruction::new(instruction.opcode(), parameters).encode());
+            }
+            _ => image.extend(&program[start..start + unit.length as usize]),
+        }
+    }
+    image
+}
+
+fn remove_instructions<F>(program: Vec<i64>, relocate: bool, mut test: F) -> Vec<i64>
+where
+    F: FnMut(&[i64]) -> bool,
+{
+    let units = units(&program);
+    let indices: Vec<usize> = (0..units.len()).collect();
+    let kept = remove_chunks(indices, |kept| {
+        test(&rebuild(&program, &units, kept, relocate))
+    });
+    rebuild(&program, &units, &kept, relocate)
+}
+
+//zero first, then halving towards it, with positives preferred over negatives
+fn simplify_values<F>(mut values: Vec<i64>, mut test: F) -> Vec<i64>
+where
+    F: FnMut(&[i64]) -> bool,
+{
+    let simpler = |a: i64, b: i64| (a.unsigned_abs(), a < 0) < (b.unsigned_abs(), b < 0);
+    for idx in 0..values.len() {
+        loop {
+            let value = values[idx];
+            let candidates = [0, value / 2, value.checked_neg().unwrap_or(value)];
+            let accepted = candidates.iter().find(|&&candidate| {
+                if !simpler(candidate, value) {
+                    return false;
+                }
+                let mut candidate_values = values.clone();
+                candidate_values[idx] = candidate;
+                test(&candidate_values)
+            });
+            match accepted {
+                Some(&candidate) => values[idx] = candidate,
+                None => break,
+            }
+        }
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode_conformance::{Feature, IntcodeEngine, Outcome};
+
+    #[test]
+    fn test_minimize_fault_kind() {
+        //the input becomes the address the last add reads
+        let program = vec![104, 1, 3, 11, 1101, 2, 3, 20, 4, 20, 1, 0, 0, 20, 99];
+        assert_eq!(
+            minimize(&program, &[7], faults_with("NegativeAddress")),
+            None
+        );
+        let reproducer = minimize(&program, &[-7], faults_with("NegativeAddress")).unwrap();
+        assert_eq!(reproducer.program, vec![3, 3, 1, 0, 0, 0]);
+        assert_eq!(reproducer.input, vec![-1]);
+        assert_eq!(minimize(&[99], &[], faults_with("")), None);
+    }
+
+    #[test]
+    fn test_minimize_output() {
+        let program = vec![104, 7, 3, 30, 1001, 30, 2, 31, 4, 31, 104, 9, 99];
+        let reproducer = minimize(&program, &[40, 5], outputs(42)).unwrap();
+        assert_eq!(reproducer.input, vec![40]);
+        assert!(reproducer.program.len() < program.len());
+        assert!(outputs(42)(&reproducer.program, &reproducer.input));
+    }
+
+    #[test]
+    fn test_minimize_fault() {
+        let limits = Limits {
+            max_output: Some(2),
+            ..Limits::default()
+        };
+        let program = vec![3, 20, 104, 1, 104, 2, 1001, 20, -1, 20, 1005, 20, 2, 99];
+        let reproducer = minimize(&program, &[3], faults(limits)).unwrap();
+        assert!(reproducer.program.len() < program.len());
+        assert!(faults(limits)(&reproducer.program, &reproducer.input));
+    }
+
+    //adds one to every output, standing in for a broken engine
+    struct OffByOneEngine;
+
+    impl Engine for OffByOneEngine {
+        fn name(&self) -> &'static str {
+            "off by one"
+        }
+
+        fn features(&self) -> &'static [Feature] {
+            IntcodeEngine.features()
+        }
+
+        fn run(&self, program: &[i64], input: &[i64], addresses: &[u64]) -> Outcome {
+            let outcome = IntcodeEngine.run(program, input, addresses);
+            Outcome {
+                output: outcome.output.iter().map(|v| v + 1).collect(),
+                ..outcome
+            }
+        }
+    }
+
+    #[test]
+    fn test_minimize_divergence() {
+        let program = vec![3, 9, 1002, 9, 3, 9, 4, 9, 99, 0];
+        let reproducer = minimize(&program, &[5], diverges(&IntcodeEngine, &OffByOneEngine));
+        //the engines print differently before either stops, halting is not needed
+        assert_eq!(reproducer.unwrap().program, vec![4, 0]);
+    }
+}
@@ -0,0 +1,398 @@
+use crate::intcode::{ComputerState, IntcodeComputer, Observer};
+use std::{
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+enum Message {
+    Value(i64),
+    Shutdown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Activity {
+    Running,
+    Blocked,
+    Stopped,
+}
+
+struct Status {
+    activity: Vec<Activity>,
+    //values sent to a machine that it has not received yet
+    pending: Vec<usize>,
+    senders: Vec<Sender<Message>>,
+    deadlocked: bool,
+}
+
+impl Status {
+    fn send(&mut self, to: usize, value: i64) {
+        //a stopped machine has dropped its receiver, the value is lost
+        if self.senders[to].send(Message::Value(value)).is_ok() {
+            self.pending[to] += 1;
+        }
+    }
+
+    //nobody can make progress once every machine is stopped or waits on an empty channel
+    fn shutdown_if_stalled(&mut self) {
+        let stalled = self
+            .activity
+            .iter()
+            .zip(&self.pending)
+            .all(|(&a, &p)| a == Activity::Stopped || (a == Activity::Blocked && p == 0));
+        if !stalled {
+            return;
+        }
+        for (id, &activity) in self.activity.iter().enumerate() {
+            if activity == Activity::Blocked {
+                self.deadlocked = true;
+                let _ = self.senders[id].send(Message::Shutdown);
+            }
+        }
+    }
+}
+
+//the observer the computer came with keeps seeing everything
+struct ChannelOutput {
+    targets: Vec<usize>,
+    status: Arc<Mutex<Status>>,
+    inner: Option<Arc<Mutex<Box<dyn Observer>>>>,
+}
+
+impl Observer for ChannelOutput {
+    fn before_execute(&mut self, instruction_pointer: u64, instruction: i64) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.before_execute(instruction_pointer, instruction)
+        }
+    }
+
+    fn memory_read(&mut self, address: u64, value: i64) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.memory_read(address, value)
+        }
+    }
+
+    fn memory_write(&mut self, address: u64, value: i64) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.memory_write(address, value)
+        }
+    }
+
+    fn input_consumed(&mut self, value: i64) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.input_consumed(value)
+        }
+    }
+
+    fn output_produced(&mut self, value: i64) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.output_produced(value)
+        }
+        let mut status = self.status.lock().expect("Network lock poisoned");
+        for &target in &self.targets {
+            status.send(target, value);
+        }
+    }
+
+    fn relative_base_changed(&mut self, old_base: u64, new_base: u64) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.relative_base_changed(old_base, new_base)
+        }
+    }
+
+    fn state_changed(&mut self, old_state: &ComputerState, new_state: &ComputerState) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.state_changed(old_state, new_state)
+        }
+    }
+}
+
+pub struct Network {
+    computers: Vec<IntcodeComputer>,
+    links: Vec<(usize, usize)>,
+    inputs: Vec<Vec<i64>>,
+}
+
+impl Default for Network {
+    fn default() -> Self {
+        Network::new()
+    }
+}
+
+impl Network {
+    pub fn new() -> Network {
+        Network {
+            computers: Vec::new(),
+            links: Vec::new(),
+            inputs: Vec::new(),
+        }
+    }
+
+    //an observer already attached to the computer is kept and still sees everything
+    pub fn add(&mut self, computer: IntcodeComputer) -> usize {
+        self.computers.push(computer);
+        self.inputs.push(Vec::new());
+        self.computers.len() - 1
+    }
+
+    pub fn add_program(&mut self, program: &[i64]) -> usize {
+        self.add(IntcodeComputer::new(program))
+    }
+
+    //every output of `from` is also sent to `to`
+    pub fn connect(&mut self, from: usize, to: usize) {
+        self.links.push((from, to));
+    }
+
+    //queued before anything runs, for phase settings and the like
+    pub fn send(&mut self, to: usize, value: i64) {
+        self.inputs[to].push(value);
+    }
+
+    pub fn run(self) -> NetworkReport {
+        let count = self.computers.len();
+        let (senders, receivers): (Vec<_>, Vec<_>) = (0..count).map(|_| channel()).unzip();
+        let status = Arc::new(Mutex::new(Status {
+            activity: vec![Activity::Running; count],
+            pending: vec![0; count],
+            senders,
+            deadlocked: false,
+        }));
+        {
+            let mut status = status.lock().expect("Network lock poisoned");
+            for (to, values) in self.inputs.iter().enumerate() {
+                for &value in values {
+                    status.send(to, value);
+                }
+            }
+        }
+
+        let links = self.links;
+        let handles: Vec<_> = self
+            .computers
+            .into_iter()
+            .zip(receivers)
+            .enumerate()
+            .map(|(id, (mut computer, receiver))| {
+                let targets = links
+                    .iter()
+                    .filter(|(from, _)| *from == id)
+                    .map(|&(_, to)| to)
+                    .collect();
+                let inner = computer
+                    .detach_observer()
+                    .map(|observer| Arc::new(Mutex::new(observer)));
+                computer.attach_observer(Box::new(ChannelOutput {
+                    targets,
+                    status: Arc::clone(&status),
+                    inner: inner.clone(),
+                }));
+                let status = Arc::clone(&status);
+                thread::spawn(move || {
+                    let mut computer = run_machine(id, computer, receiver, status);
+                    //the channel output held the other reference and was just dropped
+                    if let Some(inner) = inner {
+                        let observer = Arc::try_unwrap(inner)
+                            .ok()
+                            .and_then(|inner| inner.into_inner().ok());
+                        if let Some(observer) = observer {
+                            computer.attach_observer(observer);
+                        }
+                    }
+                    computer
+                })
+            })
+            .collect();
+
+        let computers = handles
+            .into_iter()
+            .map(|h| h.join().expect("Machine thread panicked"))
+            .collect();
+        let deadlocked = status.lock().expect("Network lock poisoned").deadlocked;
+        NetworkReport {
+            computers,
+            deadlocked,
+        }
+    }
+}
+
+//a machine that halts or faults counts as stopped, so its peers are not left waiting on it
+fn run_machine(
+    id: usize,
+    mut computer: IntcodeComputer,
+    receiver: Receiver<Message>,
+    status: Arc<Mutex<Status>>,
+) -> IntcodeComputer {
+    let mut input = Vec::new();
+    loop {
+        computer.run(&mut input);
+        if computer.state() != &ComputerState::Waiting {
+            break;
+        }
+        {
+            let mut status = status.lock().expect("Network lock poisoned");
+            if status.pending[id] == 0 {
+                status.activity[id] = Activity::Blocked;
+                status.shutdown_if_stalled();
+            }
+        }
+        match receiver.recv() {
+            Ok(Message::Value(value)) => {
+                let mut status = status.lock().expect("Network lock poisoned");
+                status.activity[id] = Activity::Running;
+                status.pending[id] -= 1;
+                input.push(value);
+            }
+            Ok(Message::Shutdown) | Err(_) => break,
+        }
+    }
+    {
+        let mut status = status.lock().expect("Network lock poisoned");
+        status.activity[id] = Activity::Stopped;
+        status.shutdown_if_stalled();
+    }
+    computer.detach_observer();
+    computer
+}
+
+pub struct NetworkReport {
+    computers: Vec<IntcodeComputer>,
+    deadlocked: bool,
+}
+
+impl NetworkReport {
+    //true when at least one machine was left waiting for input that could never come
+    pub fn deadlocked(&self) -> bool {
+        self.deadlocked
+    }
+
+    pub fn computer(&self, id: usize) -> &IntcodeComputer {
+        &self.computers[id]
+    }
+
+    pub fn output(&self, id: usize) -> &[i64] {
+        self.computers[id].output()
+    }
+
+    pub fn into_computers(self) -> Vec<IntcodeComputer> {
+        self.computers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::FaultKind;
+
+    fn amplifiers(program: &[i64], phases: &[i64], feedback: bool) -> NetworkReport {
+        let mut network = Network::new();
+        let ids: Vec<usize> = phases
+            .iter()
+            .map(|&phase| {
+                let id = network.add_program(program);
+                network.send(id, phase);
+                id
+            })
+            .collect();
+        for pair in ids.windows(2) {
+            network.connect(pair[0], pair[1]);
+        }
+        if feedback {
+            network.connect(ids[ids.len() - 1], ids[0]);
+        }
+        network.send(ids[0], 0);
+        network.run()
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let program = vec![
+            3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
+        ];
+        let report = amplifiers(&program, &[4, 3, 2, 1, 0], false);
+        assert!(!report.deadlocked());
+        assert_eq!(report.output(4), &[43210]);
+        for id in 0..5 {
+            assert_eq!(report.computer(id).state(), &ComputerState::Halted);
+        }
+    }
+
+    #[test]
+    fn test_feedback_loop() {
+        let program = vec![
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ];
+        let report = amplifiers(&program, &[9, 8, 7, 6, 5], true);
+        assert!(!report.deadlocked());
+        assert_eq!(report.output(4).last(), Some(&139629729));
+    }
+
+    #[test]
+    fn test_deadlock() {
+        //each waits for the other before saying anything
+        let program = vec![3, 7, 4, 7, 1105, 1, 0, 0];
+        let mut network = Network::new();
+        let a = network.add_program(&program);
+        let b = network.add_program(&program);
+        network.connect(a, b);
+        network.connect(b, a);
+        let report = network.run();
+        assert!(report.deadlocked());
+        for computer in report.into_computers() {
+            assert_eq!(computer.state(), &ComputerState::Waiting);
+        }
+
+        //one halts, the other is left waiting on it
+        let mut network = Network::new();
+        let a = network.add_program(&[104, 1, 99]);
+        let b = network.add_program(&[3, 9, 3, 9, 4, 9, 99, 0, 0, 0]);
+        network.connect(a, b);
+        let report = network.run();
+        assert!(report.deadlocked());
+        assert_eq!(report.computer(b).state(), &ComputerState::Waiting);
+        assert_eq!(report.computer(b).peek(9), 1);
+    }
+
+    #[test]
+    fn test_faulting_machine() {
+        //a faults on the read of a negative address after its first output,
+        //b would otherwise wait for a second value forever
+        let mut network = Network::new();
+        let a = network.add_program(&[104, 1, 1, -1, 2, 3, 99]);
+        let b = network.add_program(&[3, 9, 3, 9, 4, 9, 99, 0, 0, 0]);
+        network.connect(a, b);
+        let report = network.run();
+        assert_eq!(
+            report.computer(a).fault().map(|f| f.kind()),
+            Some(&FaultKind::NegativeAddress(-1))
+        );
+        assert!(report.deadlocked());
+        assert_eq!(report.computer(b).peek(9), 1);
+    }
+
+    #[derive(Default)]
+    struct Outputs {
+        values: Vec<i64>,
+    }
+
+    impl Observer for Outputs {
+        fn output_produced(&mut self, value: i64) {
+            self.values.push(value)
+        }
+    }
+
+    #[test]
+    fn test_keeps_observer() {
+        let outputs = Arc::new(Mutex::new(Outputs::default()));
+        let mut computer = IntcodeComputer::new(&[104, 1, 104, 2, 99]);
+        computer.attach_observer(Box::new(Arc::clone(&outputs)));
+        let mut network = Network::new();
+        let id = network.add(computer);
+        let mut computers = network.run().into_computers();
+        assert_eq!(outputs.lock().unwrap().values, vec![1, 2]);
+        assert!(computers[id].detach_observer().is_some());
+    }
+}
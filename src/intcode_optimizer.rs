@@ -0,0 +1,365 @@
+use crate::{
+    intcode::{load_program_input, Instruction, OpCode, ParameterMode},
+    intcode_transpiler::{check_write, discover_code, stored_constant, Decoded},
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+};
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct OptimizeReport {
+    pub folded: usize,
+    pub threaded: usize,
+    //unreachable cells that were cleared, and how many of those came off the end
+    pub cleared: usize,
+    pub removed: usize,
+    //nothing is touched when the program may rewrite its own code,
+    //which a write through a relative parameter always might
+    pub self_modifying: bool,
+}
+
+//addresses never move, so nothing that points into the image has to be fixed up.
+//a relative parameter can reach any cell, code included, so programs with one are left alone
+pub fn optimize(program: &[i64]) -> (Vec<i64>, OptimizeReport) {
+    let mut report = OptimizeReport::default();
+    let (decoded, _) = discover_code(program);
+    let instructions = match valid_instructions(&decoded) {
+        Some(instructions) => instructions,
+        None => return (program.to_vec(), report),
+    };
+    if instructions.values().any(uses_relative) {
+        report.self_modifying = instructions.values().any(writes_relative);
+        return (program.to_vec(), report);
+    }
+    let is_code = code_cells(program.len(), &instructions);
+    if instructions
+        .iter()
+        .any(|(&address, instruction)| check_write(address, instruction, &is_code).is_err())
+    {
+        report.self_modifying = true;
+        return (program.to_vec(), report);
+    }
+
+    let accessed = accessed_cells(&instructions);
+    let modifiable = |address: u64, instruction: &Instruction| {
+        (address..address + instruction.length()).all(|cell| !accessed.contains(&cell))
+    };
+    let mut image = program.to_vec();
+
+    for (&address, instruction) in &instructions {
+        if !modifiable(address, instruction) {
+            continue;
+        }
+        if let Some(folded) = fold(instruction) {
+            let start = address as usize;
+            if image[start..start + 4] != folded {
+                image[start..start + 4].copy_from_slice(&folded);
+                report.folded += 1;
+            }
+        }
+    }
+
+    for (&address, instruction) in &instructions {
+        if !modifiable(address, instruction) {
+            continue;
+        }
+        let target = match instruction.parameters() {
+            [_, (ParameterMode::Immediate, target)] if *target >= 0 => *target as u64,
+            _ => continue,
+        };
+        let threaded = thread_jump(&instructions, target);
+        let start = address as usize;
+        if threaded != target {
+            image[start + 2] = threaded as i64;
+            report.threaded += 1;
+        }
+        let lands_on_halt = instructions
+            .get(&threaded)
+            .is_some_and(|i| i.opcode() == OpCode::Halt);
+        if always_jumps(instruction) && lands_on_halt {
+            image[start] = 99;
+            report.threaded += 1;
+        }
+    }
+
+    //without relative parameters the base only moves, it is never used
+    drop_unreachable(&mut image, &mut report);
+    (image, report)
+}
+
+pub fn optimize_file(program_file: &str, optimized_file: &str) -> io::Result<OptimizeReport> {
+    let program = load_program_input(program_file)?;
+    let (image, report) = optimize(&program);
+    let image: Vec<String> = image.iter().map(|v| v.to_string()).collect();
+    fs::write(optimized_file, format!("{}\n", image.join(",")))?;
+    Ok(report)
+}
+
+//reachable garbage means the program crashes there, best left alone
+fn valid_instructions(decoded: &BTreeMap<u64, Decoded>) -> Option<BTreeMap<u64, Instruction>> {
+    decoded
+        .iter()
+        .map(|(&address, decoded)| match decoded {
+            Decoded::Valid(instruction) => Some((address, instruction.clone())),
+            Decoded::Invalid(_) => None,
+        })
+        .collect()
+}
+
+fn uses_relative(instruction: &Instruction) -> bool {
+    instruction
+        .parameters()
+        .iter()
+        .any(|(mode, _)| *mode == ParameterMode::Relative)
+}
+
+fn writes_relative(instruction: &Instruction) -> bool {
+    let write = match instruction.opcode() {
+        OpCode::Input => instruction.parameters().first(),
+        OpCode::Add | OpCode::Multiply | OpCode::LessThan | OpCode::Equals => {
+            instruction.parameters().get(2)
+        }
+        _ => None,
+    };
+    write.is_some_and(|(mode, _)| *mode == ParameterMode::Relative)
+}
+
+fn code_cells(length: usize, instructions: &BTreeMap<u64, Instruction>) -> Vec<bool> {
+    let mut is_code = vec![false; length];
+    for (&address, instruction) in instructions {
+        for cell in address..address + instruction.length() {
+            if let Some(c) = is_code.get_mut(cell as usize) {
+                *c = true
+            }
+        }
+    }
+    is_code
+}
+
+//every cell read or written through a position parameter
+fn accessed_cells(instructions: &BTreeMap<u64, Instruction>) -> BTreeSet<u64> {
+    instructions
+        .values()
+        .flat_map(|instruction| instruction.parameters().iter())
+        .filter(|(mode, value)| *mode == ParameterMode::Position && *value >= 0)
+        .map(|&(_, value)| value as u64)
+        .collect()
+}
+
+fn fold(instruction: &Instruction) -> Option<[i64; 4]> {
+    let parameters = instruction.parameters();
+    let value = match (
+        instruction.opcode(),
+        parameters.first()?,
+        parameters.get(1)?,
+    ) {
+        (OpCode::Multiply, (ParameterMode::Immediate, 0), _)
+        | (OpCode::Multiply, _, (ParameterMode::Immediate, 0)) => 0,
+        (opcode, (ParameterMode::Immediate, x), (ParameterMode::Immediate, y)) => match opcode {
+            OpCode::Add => x.checked_add(*y)?,
+            OpCode::Multiply => x.checked_mul(*y)?,
+            OpCode::LessThan => (x < y) as i64,
+            OpCode::Equals => (x == y) as i64,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let (write_mode, write) = parameters[2];
+    let write_mode = match write_mode {
+        ParameterMode::Position => 0,
+        ParameterMode::Immediate => 1,
+        ParameterMode::Relative => 2,
+    };
+    Some([1101 + write_mode * 10_000, value, 0, write])
+}
+
+fn always_jumps(instruction: &Instruction) -> bool {
+    match (instruction.opcode(), instruction.parameters().first()) {
+        (OpCode::JumpIfTrue, Some((ParameterMode::Immediate, condition))) => *condition != 0,
+        (OpCode::JumpIfFalse, Some((ParameterMode::Immediate, condition))) => *condition == 0,
+        _ => false,
+    }
+}
+
+//follows unconditional jumps with immediate targets, stopping at a cycle
+fn thread_jump(instructions: &BTreeMap<u64, Instruction>, target: u64) -> u64 {
+    let mut seen = BTreeSet::new();
+    let mut target = target;
+    while seen.insert(target) {
+        match instructions.get(&target) {
+            Some(instruction) if always_jumps(instruction) => match instruction.parameters()[1] {
+                (ParameterMode::Immediate, next) if next >= 0 => target = next as u64,
+                _ => break,
+            },
+            _ => break,
+        }
+    }
+    target
+}
+
+fn drop_unreachable(image: &mut Vec<i64>, report: &mut OptimizeReport) {
+    let (decoded, _) = discover_code(image);
+    let instructions = match valid_instructions(&decoded) {
+        Some(instructions) => instructions,
+        None => return,
+    };
+    //a jump through a cell only lands where the image or a stored constant says it does
+    for instruction in instructions.values() {
+        let pointer = match instruction.parameters() {
+            [_, (ParameterMode::Position, pointer)]
+                if matches!(
+                    instruction.opcode(),
+                    OpCode::JumpIfTrue | OpCode::JumpIfFalse
+                ) =>
+            {
+                *pointer
+            }
+            _ => continue,
+        };
+        let mut targets = vec![image.get(pointer as usize).copied().unwrap_or(0)];
+        for writer in instructions.values() {
+            let writes_pointer = match writer.opcode() {
+                OpCode::Input => writer.parameters()[0].1 == pointer,
+                OpCode::Add | OpCode::Multiply | OpCode::LessThan | OpCode::Equals => {
+                    writer.parameters()[2].1 == pointer
+                }
+                _ => false,
+            };
+            if writes_pointer {
+                match stored_constant(writer) {
+                    Some(target) => targets.push(target),
+                    None => return,
+                }
+            }
+        }
+        if targets
+            .iter()
+            .any(|&t| t < 0 || !instructions.contains_key(&(t as u64)))
+        {
+            return;
+        }
+    }
+
+    let is_code = code_cells(image.len(), &instructions);
+    let accessed = accessed_cells(&instructions);
+    for (address, cell) in image.iter_mut().enumerate() {
+        if !is_code[address] && !accessed.contains(&(address as u64)) && *cell != 0 {
+            *cell = 0;
+            report.cleared += 1;
+        }
+    }
+    while image.len() > 1 && image[image.len() - 1] == 0 {
+        let last = image.len() - 1;
+        if is_code[last] || accessed.contains(&(last as u64)) {
+            break;
+        }
+        image.pop();
+        report.removed += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{ComputerState, IntcodeComputer};
+
+    fn run(program: &[i64], input: &[i64]) -> (Vec<i64>, usize) {
+        let mut comp = IntcodeComputer::new(program);
+        let mut input: Vec<i64> = input.iter().rev().copied().collect();
+        let mut steps = 0;
+        comp.step(&mut input);
+        while comp.state() == &ComputerState::Running {
+            steps += 1;
+            comp.step(&mut input);
+        }
+        (comp.output().clone(), steps)
+    }
+
+    #[test]
+    fn test_fold() {
+        let program = vec![
+            1101, 2, 3, 19, 1102, 4, 5, 20, 1107, 1, 2, 21, 1002, 19, 0, 22, 4, 19, 99, 0, 0, 0, 0,
+        ];
+        let (image, report) = optimize(&program);
+        assert_eq!(report.folded, 4);
+        assert_eq!(&image[0..4], &[1101, 5, 0, 19]);
+        assert_eq!(&image[4..8], &[1101, 20, 0, 20]);
+        assert_eq!(&image[8..12], &[1101, 1, 0, 21]);
+        assert_eq!(&image[12..16], &[1101, 0, 0, 22]);
+        assert_eq!(run(&image, &[]), run(&program, &[]));
+    }
+
+    #[test]
+    fn test_thread_jumps() {
+        //0 jumps to 4 which jumps to 7, the halt at 3 is never reached
+        let program = vec![1105, 1, 4, 99, 1105, 1, 7, 104, 42, 99];
+        let (image, report) = optimize(&program);
+        assert_eq!(report.threaded, 1);
+        assert_eq!(image, vec![1105, 1, 7, 0, 0, 0, 0, 104, 42, 99]);
+        assert_eq!(report.cleared, 4);
+        let (output, steps) = run(&image, &[]);
+        assert_eq!(output, vec![42]);
+        assert!(steps < run(&program, &[]).1);
+
+        let jump_to_halt = vec![3, 9, 1005, 9, 8, 104, 1, 99, 99, 0];
+        let (image, report) = optimize(&jump_to_halt);
+        assert_eq!(report.threaded, 0);
+        assert_eq!(image, jump_to_halt);
+
+        let always_halts = vec![1105, 1, 5, 104, 1, 99, 5, 6];
+        let (image, report) = optimize(&always_halts);
+        assert_eq!(image, vec![99]);
+        assert_eq!(report.removed, 7);
+    }
+
+    #[test]
+    fn test_leaves_self_modifying_alone() {
+        let gravity_assist = vec![1, 0, 0, 0, 99];
+        let (image, report) = optimize(&gravity_assist);
+        assert!(report.self_modifying);
+        assert_eq!(image, gravity_assist);
+
+        //the constant at 4 is read as data, so it has to stay put
+        let reads_code = vec![1101, 2, 3, 9, 4, 2, 99, 0, 0, 0];
+        let (image, report) = optimize(&reads_code);
+        assert_eq!(report.folded, 0);
+        assert_eq!(&image[0..7], &reads_code[0..7]);
+    }
+
+    #[test]
+    fn test_keeps_indirect_targets() {
+        //jumps through the pointer at 9, which the program overwrites with a stored constant
+        let program = vec![
+            1101, 13, 0, 9, 1105, 1, 10, 99, 99, 7, 105, 1, 9, 104, 5, 99,
+        ];
+        let (image, _) = optimize(&program);
+        assert_eq!(image[9], 7);
+        assert_eq!(run(&image, &[]).0, vec![5]);
+    }
+
+    #[test]
+    fn test_relative_reads_keep_constants() {
+        //the output reads the 3 at cell 2 through the relative base
+        let program = vec![1101, 2, 3, 20, 204, 2, 99];
+        let (image, report) = optimize(&program);
+        assert_eq!(image, program);
+        assert!(!report.self_modifying);
+        assert_eq!(run(&image, &[]).0, vec![3]);
+
+        let relative_write = vec![109, 1, 21101, 2, 3, 2, 4, 0, 99];
+        let (image, report) = optimize(&relative_write);
+        assert_eq!(image, relative_write);
+        assert!(report.self_modifying);
+    }
+
+    #[test]
+    fn test_relative_programs_keep_their_size() {
+        let quine = vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        let (image, report) = optimize(&quine);
+        assert_eq!(image, quine);
+        assert_eq!(report, OptimizeReport::default());
+    }
+}
@@ -0,0 +1,276 @@
+use std::{fmt, marker::PhantomData};
+
+//a fixed number of consecutive outputs
+pub trait Record: Sized {
+    const SIZE: usize;
+
+    //always gets exactly SIZE values
+    fn decode(values: &[i64]) -> Result<Self, String>;
+}
+
+impl Record for (i64, i64) {
+    const SIZE: usize = 2;
+
+    fn decode(values: &[i64]) -> Result<Self, String> {
+        Ok((values[0], values[1]))
+    }
+}
+
+impl Record for (i64, i64, i64) {
+    const SIZE: usize = 3;
+
+    fn decode(values: &[i64]) -> Result<Self, String> {
+        Ok((values[0], values[1], values[2]))
+    }
+}
+
+impl<const N: usize> Record for [i64; N] {
+    //an empty record would never use up any output, so `[i64; 0]` does not compile
+    const SIZE: usize = {
+        assert!(N > 0, "A record needs at least one value");
+        N
+    };
+
+    fn decode(values: &[i64]) -> Result<Self, String> {
+        let mut record = [0; N];
+        record.copy_from_slice(values);
+        Ok(record)
+    }
+}
+
+//arcade cabinets draw with these, x = -1 and y = 0 carries the score instead
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tile {
+    pub x: i64,
+    pub y: i64,
+    pub id: i64,
+}
+
+impl Record for Tile {
+    const SIZE: usize = 3;
+
+    fn decode(values: &[i64]) -> Result<Self, String> {
+        Ok(Tile {
+            x: values[0],
+            y: values[1],
+            id: values[2],
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Turn {
+    Left,
+    Right,
+}
+
+//what a hull painting robot reports after each panel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Paint {
+    pub colour: i64,
+    pub turn: Turn,
+}
+
+impl Record for Paint {
+    const SIZE: usize = 2;
+
+    fn decode(values: &[i64]) -> Result<Self, String> {
+        let turn = match values[1] {
+            0 => Turn::Left,
+            1 => Turn::Right,
+            turn => return Err(format!("Bad turn {}", turn)),
+        };
+        Ok(Paint {
+            colour: values[0],
+            turn,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Packet {
+    pub address: u64,
+    pub x: i64,
+    pub y: i64,
+}
+
+impl Record for Packet {
+    const SIZE: usize = 3;
+
+    fn decode(values: &[i64]) -> Result<Self, String> {
+        if values[0] < 0 {
+            return Err(format!("Bad address {}", values[0]));
+        }
+        Ok(Packet {
+            address: values[0] as u64,
+            x: values[1],
+            y: values[2],
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordError {
+    //counted in records from the start of the output
+    index: usize,
+    message: String,
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "record {}: {}", self.index, self.message)
+    }
+}
+
+//the complete records of an output, a record still being written is left out
+pub struct Records<'a, R> {
+    output: &'a [i64],
+    index: usize,
+    end: usize,
+    record: PhantomData<R>,
+}
+
+impl<'a, R: Record> Records<'a, R> {
+    //the values after the last complete record
+    pub fn remainder(&self) -> &'a [i64] {
+        &self.output[self.output.len() / R::SIZE * R::SIZE..]
+    }
+}
+
+impl<'a, R: Record> Iterator for Records<'a, R> {
+    type Item = Result<R, RecordError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.end {
+            return None;
+        }
+        let start = self.index * R::SIZE;
+        let record =
+            R::decode(&self.output[start..start + R::SIZE]).map_err(|message| RecordError {
+                index: self.index,
+                message,
+            });
+        self.index += 1;
+        Some(record)
+    }
+}
+
+pub fn records<R: Record>(output: &[i64]) -> Records<'_, R> {
+    Records {
+        output,
+        index: 0,
+        end: output.len() / R::SIZE,
+        record: PhantomData,
+    }
+}
+
+//remembers how far it has read, so a computer that is run in turns
+//only hands out the records that arrived since the last read.
+//call `reset` after `load_new_instructions`, the reader cannot always tell on its own
+pub struct RecordReader<R> {
+    next: usize,
+    record: PhantomData<R>,
+}
+
+impl<R: Record> Default for RecordReader<R> {
+    fn default() -> Self {
+        RecordReader::new()
+    }
+}
+
+impl<R: Record> RecordReader<R> {
+    pub fn new() -> RecordReader<R> {
+        RecordReader {
+            next: 0,
+            record: PhantomData,
+        }
+    }
+
+    //starts again from the first record
+    pub fn reset(&mut self) {
+        self.next = 0;
+    }
+
+    //output that got shorter can only belong to a reloaded computer and is read from the start,
+    //one that has already grown past where the reader was needs `reset`
+    pub fn read<'a>(&mut self, output: &'a [i64]) -> Records<'a, R> {
+        let end = output.len() / R::SIZE;
+        let index = if end < self.next { 0 } else { self.next };
+        self.next = end;
+        Records {
+            output,
+            index,
+            end,
+            record: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::IntcodeComputer;
+
+    #[test]
+    fn test_records() {
+        let output = [1, 2, 3, 4, 5, 6, 7];
+        let pairs: Vec<(i64, i64)> = records(&output).map(Result::unwrap).collect();
+        assert_eq!(pairs, vec![(1, 2), (3, 4), (5, 6)]);
+        assert_eq!(records::<(i64, i64)>(&output).remainder(), &[7]);
+
+        let tiles: Vec<Tile> = records(&output).map(Result::unwrap).collect();
+        assert_eq!(tiles[1], Tile { x: 4, y: 5, id: 6 });
+        let arrays: Vec<[i64; 7]> = records(&output).map(Result::unwrap).collect();
+        assert_eq!(arrays, vec![output]);
+
+        let paint: Vec<_> = records::<Paint>(&[1, 0, 0, 2]).collect();
+        assert_eq!(
+            paint[0],
+            Ok(Paint {
+                colour: 1,
+                turn: Turn::Left
+            })
+        );
+        assert_eq!(
+            paint[1].as_ref().unwrap_err().to_string(),
+            "record 1: Bad turn 2"
+        );
+    }
+
+    #[test]
+    fn test_reader() {
+        //sends a packet for every input
+        let program = vec![3, 15, 104, 255, 4, 15, 1002, 15, 2, 16, 4, 16, 1105, 1, 0];
+        let mut comp = IntcodeComputer::new(&program);
+        let mut reader = RecordReader::<Packet>::new();
+        comp.step(&mut vec![5]);
+        comp.step(&mut vec![]);
+        comp.step(&mut vec![]);
+        assert_eq!(reader.read(comp.output()).count(), 0);
+        comp.run(&mut vec![]);
+        let packets: Vec<Packet> = reader.read(comp.output()).map(Result::unwrap).collect();
+        assert_eq!(
+            packets,
+            vec![Packet {
+                address: 255,
+                x: 5,
+                y: 10
+            }]
+        );
+        comp.run(&mut vec![7]);
+        let packets: Vec<Packet> = reader.read(comp.output()).map(Result::unwrap).collect();
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].x, 7);
+        assert_eq!(comp.records::<Packet>().count(), 2);
+
+        //reloaded and run past where the reader had got to
+        comp.load_new_instructions(&program);
+        comp.run(&mut vec![3, 2, 1]);
+        assert_eq!(reader.read(comp.output()).count(), 1);
+        comp.load_new_instructions(&program);
+        comp.run(&mut vec![3, 2, 1]);
+        reader.reset();
+        let packets: Vec<i64> = reader.read(comp.output()).map(|p| p.unwrap().x).collect();
+        assert_eq!(packets, vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,291 @@
+use crate::intcode::{ComputerState, IntcodeComputer};
+use std::collections::VecDeque;
+
+const DEFAULT_QUANTUM: u64 = 1000;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct MachineStats {
+    pub instructions: u64,
+    pub inputs: u64,
+    pub outputs: u64,
+    //slices that ended waiting on an empty inbox
+    pub waits: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SchedulerOutcome {
+    //every machine halted or faulted
+    Finished,
+    //the machines still alive all wait on empty inboxes
+    Deadlocked,
+    //a whole round went by where the only input anybody read was their empty input value
+    Idle,
+}
+
+struct Machine {
+    computer: IntcodeComputer,
+    inbox: VecDeque<i64>,
+    targets: Vec<usize>,
+    //handed out instead of blocking when the inbox is empty
+    empty_input: Option<i64>,
+    stats: MachineStats,
+}
+
+impl Machine {
+    fn is_stopped(&self) -> bool {
+        matches!(
+            self.computer.state(),
+            ComputerState::Halted | ComputerState::Faulted(_)
+        )
+    }
+}
+
+enum Slice {
+    Progressed,
+    Idle,
+    Blocked,
+    Stopped,
+}
+
+pub struct Scheduler {
+    machines: Vec<Machine>,
+    quantum: u64,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Scheduler::new()
+    }
+}
+
+impl Scheduler {
+    pub fn new() -> Scheduler {
+        Scheduler {
+            machines: Vec::new(),
+            quantum: DEFAULT_QUANTUM,
+        }
+    }
+
+    //the most instructions a machine runs before the next one gets a turn
+    pub fn set_quantum(&mut self, quantum: u64) {
+        self.quantum = quantum.max(1);
+    }
+
+    pub fn add(&mut self, computer: IntcodeComputer) -> usize {
+        self.machines.push(Machine {
+            computer,
+            inbox: VecDeque::new(),
+            targets: Vec::new(),
+            empty_input: None,
+            stats: MachineStats::default(),
+        });
+        self.machines.len() - 1
+    }
+
+    pub fn add_program(&mut self, program: &[i64]) -> usize {
+        self.add(IntcodeComputer::new(program))
+    }
+
+    //every output of `from` is queued for `to`
+    pub fn connect(&mut self, from: usize, to: usize) {
+        self.machines[from].targets.push(to);
+    }
+
+    pub fn send(&mut self, to: usize, value: i64) {
+        self.machines[to].inbox.push_back(value);
+    }
+
+    //reading an empty inbox gives this value and ends the machine's turn
+    pub fn set_empty_input(&mut self, id: usize, value: i64) {
+        self.machines[id].empty_input = Some(value);
+    }
+
+    pub fn computer(&self, id: usize) -> &IntcodeComputer {
+        &self.machines[id].computer
+    }
+
+    pub fn output(&self, id: usize) -> &[i64] {
+        self.machines[id].computer.output()
+    }
+
+    pub fn inbox(&self, id: usize) -> &VecDeque<i64> {
+        &self.machines[id].inbox
+    }
+
+    pub fn stats(&self, id: usize) -> MachineStats {
+        self.machines[id].stats
+    }
+
+    //round robin until nothing more can happen, can be called again after sending more input
+    pub fn run(&mut self) -> SchedulerOutcome {
+        loop {
+            let mut progressed = false;
+            let mut idle = false;
+            for id in 0..self.machines.len() {
+                match self.run_slice(id) {
+                    Slice::Progressed => progressed = true,
+                    Slice::Idle => idle = true,
+                    Slice::Blocked | Slice::Stopped => {}
+                }
+            }
+            if progressed {
+                continue;
+            }
+            if idle {
+                return SchedulerOutcome::Idle;
+            } else if self.machines.iter().all(Machine::is_stopped) {
+                return SchedulerOutcome::Finished;
+            } else {
+                return SchedulerOutcome::Deadlocked;
+            }
+        }
+    }
+
+    //a turn that reads or writes nothing but the empty input does not count as progress,
+    //running out of quantum always does
+    fn run_slice(&mut self, id: usize) -> Slice {
+        //a new computer reports halted until it is first stepped
+        if self.machines[id].stats.instructions > 0 && self.machines[id].is_stopped() {
+            return Slice::Stopped;
+        }
+        let mut communicated = false;
+        let mut input = Vec::new();
+        for _ in 0..self.quantum {
+            let seen = self.machines[id].computer.output().len();
+            let machine = &mut self.machines[id];
+            machine.computer.step(&mut input);
+
+            if machine.computer.state() == &ComputerState::Waiting {
+                let (value, empty) = match (machine.inbox.pop_front(), machine.empty_input) {
+                    (Some(value), _) => (value, false),
+                    (None, Some(value)) => (value, true),
+                    (None, None) => {
+                        machine.stats.waits += 1;
+                        return if communicated {
+                            Slice::Progressed
+                        } else {
+                            Slice::Blocked
+                        };
+                    }
+                };
+                machine.stats.inputs += 1;
+                input.push(value);
+                machine.computer.step(&mut input);
+                machine.stats.instructions += 1;
+                if empty {
+                    return if communicated {
+                        Slice::Progressed
+                    } else {
+                        Slice::Idle
+                    };
+                }
+                communicated = true;
+                continue;
+            }
+
+            machine.stats.instructions += 1;
+            let produced: Vec<i64> = machine.computer.output()[seen..].to_vec();
+            machine.stats.outputs += produced.len() as u64;
+            communicated |= !produced.is_empty();
+            let targets = machine.targets.clone();
+            for value in produced {
+                for &target in &targets {
+                    self.machines[target].inbox.push_back(value);
+                }
+            }
+            if self.machines[id].is_stopped() {
+                break;
+            }
+        }
+        Slice::Progressed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feedback_loop() {
+        let program = vec![
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ];
+        let mut scheduler = Scheduler::new();
+        let ids: Vec<usize> = [9, 8, 7, 6, 5]
+            .iter()
+            .map(|&phase| {
+                let id = scheduler.add_program(&program);
+                scheduler.send(id, phase);
+                id
+            })
+            .collect();
+        for pair in ids.windows(2) {
+            scheduler.connect(pair[0], pair[1]);
+        }
+        scheduler.connect(ids[4], ids[0]);
+        scheduler.send(ids[0], 0);
+
+        assert_eq!(scheduler.run(), SchedulerOutcome::Finished);
+        assert_eq!(scheduler.output(ids[4]).last(), Some(&139629729));
+        //the last signal goes back round to an amplifier that has already halted
+        assert_eq!(scheduler.inbox(ids[0]).len(), 1);
+        let stats = scheduler.stats(ids[0]);
+        assert_eq!(stats.inputs, 6);
+        assert_eq!(stats.outputs, 5);
+        assert!(stats.waits >= 4);
+        assert!(stats.instructions > 0);
+    }
+
+    #[test]
+    fn test_deadlock() {
+        let mut scheduler = Scheduler::new();
+        let a = scheduler.add_program(&[3, 7, 4, 7, 1105, 1, 0, 0]);
+        let b = scheduler.add_program(&[3, 7, 4, 7, 1105, 1, 0, 0]);
+        scheduler.connect(a, b);
+        scheduler.connect(b, a);
+        assert_eq!(scheduler.run(), SchedulerOutcome::Deadlocked);
+
+        //stuck until it is sent something
+        let mut scheduler = Scheduler::new();
+        let echo = scheduler.add_program(&[3, 9, 4, 9, 99, 0, 0, 0, 0, 0]);
+        assert_eq!(scheduler.run(), SchedulerOutcome::Deadlocked);
+        scheduler.send(echo, 5);
+        assert_eq!(scheduler.run(), SchedulerOutcome::Finished);
+        assert_eq!(scheduler.output(echo), &[5]);
+    }
+
+    #[test]
+    fn test_idle() {
+        //reads forever, echoing anything that is not -1
+        let program = vec![
+            3, 14, 1008, 14, -1, 15, 1005, 15, 0, 4, 14, 1105, 1, 0, 0, 0,
+        ];
+        let mut scheduler = Scheduler::new();
+        let a = scheduler.add_program(&program);
+        let b = scheduler.add_program(&program);
+        scheduler.set_empty_input(a, -1);
+        scheduler.set_empty_input(b, -1);
+        scheduler.connect(a, b);
+        scheduler.send(a, 42);
+        assert_eq!(scheduler.run(), SchedulerOutcome::Idle);
+        assert_eq!(scheduler.output(b), &[42]);
+
+        scheduler.send(a, 7);
+        assert_eq!(scheduler.run(), SchedulerOutcome::Idle);
+        assert_eq!(scheduler.output(b), &[42, 7]);
+    }
+
+    #[test]
+    fn test_quantum_is_fair() {
+        //a spinning machine must not starve the others
+        let mut scheduler = Scheduler::new();
+        scheduler.set_quantum(10);
+        let spinner = scheduler.add_program(&[1105, 1, 0]);
+        let worker = scheduler.add_program(&[104, 1, 99]);
+        scheduler.run_slice(spinner);
+        scheduler.run_slice(worker);
+        assert_eq!(scheduler.output(worker), &[1]);
+        assert_eq!(scheduler.stats(spinner).instructions, 10);
+    }
+}
@@ -0,0 +1,893 @@
+use crate::intcode::{ComputerState, InstructionSet, IntcodeComputer, OpCode};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::RangeInclusive,
+};
+
+const MAX_PATHS: usize = 4096;
+const MAX_STEPS: usize = 100_000;
+//assignments tried for one path before the symbolic answer is given up on
+const MAX_ASSIGNMENTS: u64 = 10_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Location {
+    Cell(u64),
+    //the nth value the program reads
+    Input(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Goal {
+    Output { index: usize, value: i64 },
+    //checked once the program halts
+    Memory { address: u64, value: i64 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Method {
+    Symbolic,
+    Concrete,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Solution {
+    //one value per symbol, in the order they were added
+    pub values: Vec<i64>,
+    pub method: Method,
+}
+
+//how far the concrete fallback may go before it gives up
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Budget {
+    //assignments tried on a real machine
+    pub candidates: u64,
+    //a candidate still running after this many steps cannot be decided
+    pub steps: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Answer {
+    Solved(Solution),
+    //no values in the domains reach the goal
+    Unsolvable,
+    //the budget ran out before the search could tell
+    Unknown,
+}
+
+//the symbolic run hit something it cannot model
+#[derive(Debug)]
+struct Unsupported;
+
+//a constant plus each symbol times its coefficient, zero coefficients are never stored
+#[derive(Debug, Clone, PartialEq)]
+struct Linear {
+    constant: i64,
+    terms: BTreeMap<usize, i64>,
+}
+
+impl Linear {
+    fn constant(value: i64) -> Linear {
+        Linear {
+            constant: value,
+            terms: BTreeMap::new(),
+        }
+    }
+
+    fn symbol(id: usize) -> Linear {
+        let mut terms = BTreeMap::new();
+        terms.insert(id, 1);
+        Linear { constant: 0, terms }
+    }
+
+    fn as_constant(&self) -> Option<i64> {
+        if self.terms.is_empty() {
+            Some(self.constant)
+        } else {
+            None
+        }
+    }
+
+    fn add(&self, other: &Linear) -> Option<Linear> {
+        let mut sum = self.clone();
+        sum.constant = sum.constant.checked_add(other.constant)?;
+        for (&symbol, &coefficient) in &other.terms {
+            let coefficient = sum
+                .terms
+                .get(&symbol)
+                .copied()
+                .unwrap_or(0)
+                .checked_add(coefficient)?;
+            if coefficient == 0 {
+                sum.terms.remove(&symbol);
+            } else {
+                sum.terms.insert(symbol, coefficient);
+            }
+        }
+        Some(sum)
+    }
+
+    fn scale(&self, factor: i64) -> Option<Linear> {
+        if factor == 0 {
+            return Some(Linear::constant(0));
+        }
+        let terms = self
+            .terms
+            .iter()
+            .map(|(&s, &c)| c.checked_mul(factor).map(|c| (s, c)))
+            .collect::<Option<_>>()?;
+        Some(Linear {
+            constant: self.constant.checked_mul(factor)?,
+            terms,
+        })
+    }
+
+    fn subtract(&self, other: &Linear) -> Option<Linear> {
+        self.add(&other.scale(-1)?)
+    }
+
+    fn evaluate(&self, values: &[i64]) -> Option<i64> {
+        self.terms.iter().try_fold(self.constant, |sum, (&s, &c)| {
+            sum.checked_add(c.checked_mul(values[s])?)
+        })
+    }
+
+    //the smallest and largest value over the symbol domains
+    fn bounds(&self, domains: &[RangeInclusive<i64>]) -> Option<(i64, i64)> {
+        self.terms
+            .iter()
+            .try_fold((self.constant, self.constant), |(lo, hi), (&s, &c)| {
+                let (a, b) = (
+                    c.checked_mul(*domains[s].start())?,
+                    c.checked_mul(*domains[s].end())?,
+                );
+                Some((lo.checked_add(a.min(b))?, hi.checked_add(a.max(b))?))
+            })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Linear(Linear),
+    //non-linear, or read through a symbolic pointer
+    Opaque,
+}
+
+impl Value {
+    fn constant(value: i64) -> Value {
+        Value::Linear(Linear::constant(value))
+    }
+
+    fn as_constant(&self) -> Option<i64> {
+        match self {
+            Value::Linear(linear) => linear.as_constant(),
+            Value::Opaque => None,
+        }
+    }
+
+    fn linear(&self) -> Result<&Linear, Unsupported> {
+        match self {
+            Value::Linear(linear) => Ok(linear),
+            Value::Opaque => Err(Unsupported),
+        }
+    }
+
+    fn add(&self, other: &Value) -> Result<Value, Unsupported> {
+        match (self, other) {
+            (Value::Linear(a), Value::Linear(b)) => Ok(Value::Linear(a.add(b).ok_or(Unsupported)?)),
+            _ => Ok(Value::Opaque),
+        }
+    }
+
+    fn multiply(&self, other: &Value) -> Result<Value, Unsupported> {
+        let scaled = match (self, other) {
+            (Value::Linear(a), Value::Linear(b)) => match (a.as_constant(), b.as_constant()) {
+                (Some(factor), _) => b.scale(factor),
+                (_, Some(factor)) => a.scale(factor),
+                (None, None) => return Ok(Value::Opaque),
+            },
+            _ => return Ok(Value::Opaque),
+        };
+        Ok(Value::Linear(scaled.ok_or(Unsupported)?))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Relation {
+    Zero,
+    NonZero,
+    Negative,
+    NonNegative,
+}
+
+impl Relation {
+    fn holds(self, value: i64) -> bool {
+        match self {
+            Relation::Zero => value == 0,
+            Relation::NonZero => value != 0,
+            Relation::Negative => value < 0,
+            Relation::NonNegative => value >= 0,
+        }
+    }
+
+    fn negate(self) -> Relation {
+        match self {
+            Relation::Zero => Relation::NonZero,
+            Relation::NonZero => Relation::Zero,
+            Relation::Negative => Relation::NonNegative,
+            Relation::NonNegative => Relation::Negative,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Constraint {
+    expression: Linear,
+    relation: Relation,
+}
+
+impl Constraint {
+    fn holds(&self, values: &[i64]) -> bool {
+        self.expression
+            .evaluate(values)
+            .is_some_and(|v| self.relation.holds(v))
+    }
+
+    //false only when the domains rule it out
+    fn feasible(&self, domains: &[RangeInclusive<i64>]) -> bool {
+        let (lo, hi) = match self.expression.bounds(domains) {
+            Some(bounds) => bounds,
+            None => return true,
+        };
+        match self.relation {
+            Relation::Zero => lo <= 0 && 0 <= hi,
+            Relation::NonZero => !(lo == 0 && hi == 0),
+            Relation::Negative => lo < 0,
+            Relation::NonNegative => hi >= 0,
+        }
+    }
+}
+
+enum Flow {
+    Continue,
+    //the other side of a symbolic branch
+    Fork(Box<Path>),
+    Halted,
+    //waiting for input that was not given, or crashed
+    Stopped,
+}
+
+#[derive(Debug, Clone)]
+struct Path {
+    memory: BTreeMap<u64, Value>,
+    instruction_pointer: u64,
+    relative_base: i64,
+    inputs_read: usize,
+    outputs: Vec<Value>,
+    constraints: Vec<Constraint>,
+    steps: usize,
+}
+
+impl Path {
+    fn read(&self, address: u64) -> Value {
+        self.memory
+            .get(&address)
+            .cloned()
+            .unwrap_or_else(|| Value::constant(0))
+    }
+
+    //where parameter n points, None when the pointer itself is symbolic
+    fn address(&self, mode: i64, n: u64) -> Result<Option<u64>, Unsupported> {
+        let parameter = match self.read(self.instruction_pointer + n).as_constant() {
+            Some(parameter) => parameter,
+            None => return Ok(None),
+        };
+        let address = match mode {
+            0 => parameter,
+            2 => self
+                .relative_base
+                .checked_add(parameter)
+                .ok_or(Unsupported)?,
+            _ => return Err(Unsupported),
+        };
+        if address < 0 {
+            return Err(Unsupported);
+        }
+        Ok(Some(address as u64))
+    }
+
+    fn load(&self, mode: i64, n: u64) -> Result<Value, Unsupported> {
+        if mode == 1 {
+            return Ok(self.read(self.instruction_pointer + n));
+        }
+        Ok(match self.address(mode, n)? {
+            Some(address) => self.read(address),
+            None => Value::Opaque,
+        })
+    }
+
+    //a write through a symbolic pointer could land anywhere
+    fn store(&mut self, mode: i64, n: u64, value: Value) -> Result<(), Unsupported> {
+        let address = self.address(mode, n)?.ok_or(Unsupported)?;
+        self.memory.insert(address, value);
+        Ok(())
+    }
+
+    //this path takes the side where the relation holds if it can,
+    //the returned copy takes the other side when both are possible
+    fn assume(
+        &mut self,
+        expression: &Linear,
+        relation: Relation,
+        domains: &[RangeInclusive<i64>],
+    ) -> (bool, Option<Path>) {
+        if let Some(value) = expression.as_constant() {
+            return (relation.holds(value), None);
+        }
+        let holds = Constraint {
+            expression: expression.clone(),
+            relation,
+        };
+        let fails = Constraint {
+            expression: expression.clone(),
+            relation: relation.negate(),
+        };
+        match (holds.feasible(domains), fails.feasible(domains)) {
+            (true, true) => {
+                let mut other = self.clone();
+                other.constraints.push(fails);
+                self.constraints.push(holds);
+                (true, Some(other))
+            }
+            (holds, _) => (holds, None),
+        }
+    }
+
+    fn jump(&mut self, taken: bool, mode: i64) -> Result<(), Unsupported> {
+        if !taken {
+            self.instruction_pointer += 3;
+            return Ok(());
+        }
+        match self.load(mode, 2)?.as_constant() {
+            Some(target) if target >= 0 => self.instruction_pointer = target as u64,
+            _ => return Err(Unsupported),
+        }
+        Ok(())
+    }
+
+    fn step(&mut self, solver: &Solver) -> Result<Flow, Unsupported> {
+        self.steps += 1;
+        if self.steps > MAX_STEPS {
+            return Err(Unsupported);
+        }
+        let code = self
+            .read(self.instruction_pointer)
+            .as_constant()
+            .ok_or(Unsupported)?;
+        let opcode = match OpCode::from_number(code % 100) {
+            Some(opcode) if code >= 0 => opcode,
+            _ => return Ok(Flow::Stopped),
+        };
+        let mode = |n: u32| code / 10_i64.pow(n + 1) % 10;
+        let domains = solver.domains();
+
+        let forked = match opcode {
+            OpCode::Add | OpCode::Multiply => {
+                let (a, b) = (self.load(mode(1), 1)?, self.load(mode(2), 2)?);
+                let result = if opcode == OpCode::Add {
+                    a.add(&b)?
+                } else {
+                    a.multiply(&b)?
+                };
+                self.store(mode(3), 3, result)?;
+                self.instruction_pointer += 4;
+                None
+            }
+            OpCode::LessThan | OpCode::Equals => {
+                let (a, b) = (self.load(mode(1), 1)?, self.load(mode(2), 2)?);
+                let difference = a.linear()?.subtract(b.linear()?).ok_or(Unsupported)?;
+                let relation = if opcode == OpCode::LessThan {
+                    Relation::Negative
+                } else {
+                    Relation::Zero
+                };
+                let (holds, mut other) = self.assume(&difference, relation, &domains);
+                for (path, result) in [(Some(self), holds), (other.as_mut(), !holds)] {
+                    if let Some(path) = path {
+                        path.store(mode(3), 3, Value::constant(result as i64))?;
+                        path.instruction_pointer += 4;
+                    }
+                }
+                other
+            }
+            OpCode::JumpIfTrue | OpCode::JumpIfFalse => {
+                let condition = self.load(mode(1), 1)?;
+                let relation = if opcode == OpCode::JumpIfTrue {
+                    Relation::NonZero
+                } else {
+                    Relation::Zero
+                };
+                let (taken, mut other) = self.assume(condition.linear()?, relation, &domains);
+                self.jump(taken, mode(2))?;
+                if let Some(other) = other.as_mut() {
+                    other.jump(!taken, mode(2))?;
+                }
+                other
+            }
+            OpCode::Input => {
+                let value = match solver.input_at(self.inputs_read) {
+                    Some(Source::Symbol(id)) => Value::Linear(Linear::symbol(id)),
+                    Some(Source::Value(value)) => Value::constant(value),
+                    None => return Ok(Flow::Stopped),
+                };
+                self.store(mode(1), 1, value)?;
+                self.inputs_read += 1;
+                self.instruction_pointer += 2;
+                None
+            }
+            OpCode::Output => {
+                let value = self.load(mode(1), 1)?;
+                self.outputs.push(value);
+                self.instruction_pointer += 2;
+                None
+            }
+            OpCode::AdjustRelativeBaseOffset => {
+                let adjustment = self.load(mode(1), 1)?.as_constant().ok_or(Unsupported)?;
+                self.relative_base = self
+                    .relative_base
+                    .checked_add(adjustment)
+                    .ok_or(Unsupported)?
+                    .max(0);
+                self.instruction_pointer += 2;
+                None
+            }
+            OpCode::Halt => return Ok(Flow::Halted),
+        };
+        Ok(match forked {
+            Some(other) => Flow::Fork(Box::new(other)),
+            None => Flow::Continue,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Source {
+    Symbol(usize),
+    Value(i64),
+}
+
+#[derive(Debug, Clone)]
+struct Symbol {
+    location: Location,
+    domain: RangeInclusive<i64>,
+}
+
+//finds values for the symbolic cells and inputs that make a program reach a goal
+#[derive(Debug, Clone)]
+pub struct Solver {
+    program: Vec<i64>,
+    input: Vec<i64>,
+    symbols: Vec<Symbol>,
+}
+
+impl Solver {
+    pub fn new(program: &[i64]) -> Solver {
+        Solver {
+            program: program.to_vec(),
+            input: Vec::new(),
+            symbols: Vec::new(),
+        }
+    }
+
+    //the concrete input, in the order it is read
+    pub fn set_input(&mut self, input: &[i64]) {
+        self.input = input.to_vec();
+    }
+
+    pub fn symbolic_cell(&mut self, address: u64, domain: RangeInclusive<i64>) -> usize {
+        self.add_symbol(Location::Cell(address), domain)
+    }
+
+    //replaces the nth input, any inputs skipped over read as 0
+    pub fn symbolic_input(&mut self, index: usize, domain: RangeInclusive<i64>) -> usize {
+        self.add_symbol(Location::Input(index), domain)
+    }
+
+    fn add_symbol(&mut self, location: Location, domain: RangeInclusive<i64>) -> usize {
+        self.symbols.push(Symbol { location, domain });
+        self.symbols.len() - 1
+    }
+
+    fn domains(&self) -> Vec<RangeInclusive<i64>> {
+        self.symbols.iter().map(|s| s.domain.clone()).collect()
+    }
+
+    fn input_length(&self) -> usize {
+        self.symbols
+            .iter()
+            .filter_map(|s| match s.location {
+                Location::Input(index) => Some(index + 1),
+                Location::Cell(_) => None,
+            })
+            .fold(self.input.len(), usize::max)
+    }
+
+    fn input_at(&self, index: usize) -> Option<Source> {
+        if index >= self.input_length() {
+            return None;
+        }
+        let symbol = self
+            .symbols
+            .iter()
+            .position(|s| s.location == Location::Input(index));
+        Some(match symbol {
+            Some(id) => Source::Symbol(id),
+            None => Source::Value(self.input.get(index).copied().unwrap_or(0)),
+        })
+    }
+
+    //the smallest solution symbolic execution can find, checked on a real machine.
+    //falls back to trying every value, within the budget, when the program is beyond
+    //the linear model
+    pub fn solve(&self, goal: Goal, budget: Budget) -> Answer {
+        if let Ok(found) = self.solve_symbolically(goal) {
+            match found {
+                Some(values) if self.satisfies(&values, goal, budget.steps) == Some(true) => {
+                    return Answer::Solved(Solution {
+                        values,
+                        method: Method::Symbolic,
+                    })
+                }
+                None => return Answer::Unsolvable,
+                //the model and the machine disagree, trust the machine
+                Some(_) => {}
+            }
+        }
+        self.search(goal, budget)
+    }
+
+    fn start(&self) -> Path {
+        let mut memory: BTreeMap<u64, Value> = self
+            .program
+            .iter()
+            .enumerate()
+            .map(|(a, &v)| (a as u64, Value::constant(v)))
+            .collect();
+        for (id, symbol) in self.symbols.iter().enumerate() {
+            if let Location::Cell(address) = symbol.location {
+                memory.insert(address, Value::Linear(Linear::symbol(id)));
+            }
+        }
+        Path {
+            memory,
+            instruction_pointer: 0,
+            relative_base: 0,
+            inputs_read: 0,
+            outputs: Vec::new(),
+            constraints: Vec::new(),
+            steps: 0,
+        }
+    }
+
+    fn solve_symbolically(&self, goal: Goal) -> Result<Option<Vec<i64>>, Unsupported> {
+        let mut best: Option<Vec<i64>> = None;
+        let mut pending = vec![self.start()];
+        let mut paths = 1;
+        while let Some(mut path) = pending.pop() {
+            let reached = loop {
+                if let Goal::Output { index, .. } = goal {
+                    if let Some(output) = path.outputs.get(index) {
+                        break Some(output.clone());
+                    }
+                }
+                match path.step(self)? {
+                    Flow::Continue => {}
+                    Flow::Fork(other) => {
+                        paths += 1;
+                        if paths > MAX_PATHS {
+                            return Err(Unsupported);
+                        }
+                        pending.push(*other);
+                    }
+                    Flow::Halted => match goal {
+                        Goal::Memory { address, .. } => break Some(path.read(address)),
+                        Goal::Output { .. } => break None,
+                    },
+                    Flow::Stopped => break None,
+                }
+            };
+            let target = match goal {
+                Goal::Output { value, .. } | Goal::Memory { value, .. } => value,
+            };
+            if let Some(reached) = reached {
+                let equation = reached
+                    .linear()?
+                    .subtract(&Linear::constant(target))
+                    .ok_or(Unsupported)?;
+                if let Some(values) = self.solve_path(&equation, &path.constraints)? {
+                    if best.as_ref().is_none_or(|b| values < *b) {
+                        best = Some(values);
+                    }
+                }
+            }
+        }
+        Ok(best)
+    }
+
+    //solves the equation for its last symbol while counting through the others
+    fn solve_path(
+        &self,
+        equation: &Linear,
+        constraints: &[Constraint],
+    ) -> Result<Option<Vec<i64>>, Unsupported> {
+        let domains = self.domains();
+        let pivot = equation.terms.keys().next_back().copied();
+        let used: BTreeSet<usize> = constraints
+            .iter()
+            .flat_map(|c| c.expression.terms.keys())
+            .chain(equation.terms.keys())
+            .copied()
+            .collect();
+        let free: Vec<usize> = used.into_iter().filter(|&s| Some(s) != pivot).collect();
+        let assignments = free
+            .iter()
+            .map(|&s| domain_size(&domains[s]))
+            .fold(1_u64, u64::saturating_mul);
+        if assignments > MAX_ASSIGNMENTS {
+            return Err(Unsupported);
+        }
+        if domains.iter().any(|d| d.is_empty()) {
+            return Ok(None);
+        }
+
+        let mut values: Vec<i64> = domains.iter().map(|d| *d.start()).collect();
+        loop {
+            let solved = match pivot {
+                Some(pivot) => {
+                    values[pivot] = 0;
+                    let coefficient = equation.terms[&pivot];
+                    match equation.evaluate(&values) {
+                        Some(rest) if rest % coefficient == 0 => {
+                            values[pivot] = -rest / coefficient;
+                            domains[pivot].contains(&values[pivot])
+                        }
+                        _ => false,
+                    }
+                }
+                None => equation.constant == 0,
+            };
+            if solved && constraints.iter().all(|c| c.holds(&values)) {
+                return Ok(Some(values));
+            }
+            if !next_assignment(&mut values, &free, &domains) {
+                return Ok(None);
+            }
+        }
+    }
+
+    //every combination of values in order, the first symbol changing slowest
+    fn search(&self, goal: Goal, budget: Budget) -> Answer {
+        let domains = self.domains();
+        if domains.iter().any(|d| d.is_empty()) {
+            return Answer::Unsolvable;
+        }
+        let all: Vec<usize> = (0..self.symbols.len()).collect();
+        let mut values: Vec<i64> = domains.iter().map(|d| *d.start()).collect();
+        let mut undecided = false;
+        for _ in 0..budget.candidates {
+            match self.satisfies(&values, goal, budget.steps) {
+                Some(true) => {
+                    return Answer::Solved(Solution {
+                        values,
+                        method: Method::Concrete,
+                    })
+                }
+                Some(false) => {}
+                None => undecided = true,
+            }
+            if !next_assignment(&mut values, &all, &domains) {
+                return if undecided {
+                    Answer::Unknown
+                } else {
+                    Answer::Unsolvable
+                };
+            }
+        }
+        Answer::Unknown
+    }
+
+    //none when the machine was still running after `max_steps`
+    fn satisfies(&self, values: &[i64], goal: Goal, max_steps: u64) -> Option<bool> {
+        let mut input: Vec<i64> = (0..self.input_length())
+            .map(|index| match self.input_at(index) {
+                Some(Source::Symbol(id)) => values[id],
+                Some(Source::Value(value)) => value,
+                None => 0,
+            })
+            .rev()
+            .collect();
+        let mut computer = IntcodeComputer::new(&self.program);
+        for (symbol, &value) in self.symbols.iter().zip(values) {
+            if let Location::Cell(address) = symbol.location {
+                computer.patch(address, value);
+            }
+        }
+        //a bad guess can send the machine anywhere, what it cannot run faults
+        computer.restrict(InstructionSet::Day9);
+        computer.run_for(&mut input, max_steps);
+        let running = computer.state() == &ComputerState::Running;
+        match goal {
+            Goal::Output { index, value } => match computer.output().get(index) {
+                Some(&output) => Some(output == value),
+                None if running => None,
+                None => Some(false),
+            },
+            Goal::Memory { .. } if running => None,
+            Goal::Memory { address, value } => {
+                Some(computer.state() == &ComputerState::Halted && computer.peek(address) == value)
+            }
+        }
+    }
+}
+
+fn domain_size(domain: &RangeInclusive<i64>) -> u64 {
+    if domain.is_empty() {
+        0
+    } else {
+        (*domain.end() as i128 - *domain.start() as i128 + 1).min(u64::MAX as i128) as u64
+    }
+}
+
+//counts like an odometer over the given symbols, false once it wraps around
+fn next_assignment(values: &mut [i64], symbols: &[usize], domains: &[RangeInclusive<i64>]) -> bool {
+    for &s in symbols.iter().rev() {
+        if values[s] < *domains[s].end() {
+            values[s] += 1;
+            return true;
+        }
+        values[s] = *domains[s].start();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::load_program_input;
+
+    const BUDGET: Budget = Budget {
+        candidates: 1_000_000,
+        steps: 100_000,
+    };
+
+    fn solved(answer: Answer) -> Option<Solution> {
+        match answer {
+            Answer::Solved(solution) => Some(solution),
+            Answer::Unsolvable | Answer::Unknown => None,
+        }
+    }
+
+    #[test]
+    fn test_noun_and_verb() {
+        let program = load_program_input("program.txt").unwrap();
+        let mut solver = Solver::new(&program);
+        solver.symbolic_cell(1, 0..=99);
+        solver.symbolic_cell(2, 0..=99);
+        let goal = Goal::Memory {
+            address: 0,
+            value: 19_690_720,
+        };
+        let solution = solved(solver.solve(goal, BUDGET)).unwrap();
+        assert_eq!(solution.method, Method::Symbolic);
+        assert_eq!(
+            solved(solver.search(goal, BUDGET)).unwrap().values,
+            solution.values
+        );
+    }
+
+    #[test]
+    fn test_branches() {
+        //reads x, prints 3x + 7 when x < 10 and x - 10 otherwise
+        let program = vec![
+            3, 30, 1007, 30, 10, 31, 1006, 31, 20, 1002, 30, 3, 30, 1001, 30, 7, 30, 1105, 1, 24,
+            1001, 30, -10, 30, 4, 30, 99, 0, 0, 0, 0, 0,
+        ];
+        let mut solver = Solver::new(&program);
+        solver.symbolic_input(0, -100..=100);
+        let solve = |value| {
+            solved(solver.solve(Goal::Output { index: 0, value }, BUDGET))
+                .map(|s| (s.method, s.values))
+        };
+        assert_eq!(solve(34), Some((Method::Symbolic, vec![9])));
+        assert_eq!(solve(37), Some((Method::Symbolic, vec![47])));
+        //3x + 7 = 11 has no whole solution and x - 10 = 11 is out of range
+        let mut solver = Solver::new(&program);
+        solver.symbolic_input(0, -100..=15);
+        assert_eq!(
+            solver.solve(
+                Goal::Output {
+                    index: 0,
+                    value: 11
+                },
+                BUDGET
+            ),
+            Answer::Unsolvable
+        );
+    }
+
+    #[test]
+    fn test_non_linear_falls_back() {
+        //prints x * y
+        let program = vec![3, 11, 3, 12, 2, 11, 12, 13, 4, 13, 99, 0, 0, 0];
+        let mut solver = Solver::new(&program);
+        solver.symbolic_input(0, 0..=10);
+        solver.symbolic_input(1, 0..=10);
+        let goal = Goal::Output {
+            index: 0,
+            value: 42,
+        };
+        assert_eq!(
+            solver.solve(goal, BUDGET),
+            Answer::Solved(Solution {
+                values: vec![6, 7],
+                method: Method::Concrete
+            })
+        );
+        //6 * 7 is the 74th assignment tried
+        let budget = Budget {
+            candidates: 73,
+            ..BUDGET
+        };
+        assert_eq!(solver.solve(goal, budget), Answer::Unknown);
+        assert_eq!(
+            solver.solve(
+                Goal::Output {
+                    index: 0,
+                    value: 101
+                },
+                BUDGET
+            ),
+            Answer::Unsolvable
+        );
+
+        //a concrete input before the symbolic one
+        let mut solver = Solver::new(&program);
+        solver.set_input(&[5]);
+        solver.symbolic_input(1, 0..=10);
+        let solution = solved(solver.solve(
+            Goal::Output {
+                index: 0,
+                value: 45,
+            },
+            BUDGET,
+        ))
+        .unwrap();
+        assert_eq!(solution.values, vec![9]);
+        assert_eq!(solution.method, Method::Symbolic);
+    }
+
+    #[test]
+    fn test_undecided_candidates() {
+        //prints 42 when the input is 6, any other input reads a negative address,
+        //which faults
+        let program = vec![
+            3, 20, 1008, 20, 6, 21, 1005, 21, 13, 1, -1, 1, 3, 104, 42, 99, 0, 0, 0, 0, 0, 0,
+        ];
+        let mut solver = Solver::new(&program);
+        solver.symbolic_input(0, 0..=10);
+        let goal = Goal::Output {
+            index: 0,
+            value: 42,
+        };
+        assert_eq!(
+            solved(solver.solve(goal, BUDGET)).map(|s| (s.method, s.values)),
+            Some((Method::Concrete, vec![6]))
+        );
+
+        //loops forever on any input but 6
+        let mut program = program;
+        program[9..13].copy_from_slice(&[1105, 1, 9, 0]);
+        let mut solver = Solver::new(&program);
+        solver.symbolic_input(0, 0..=5);
+        assert_eq!(solver.solve(goal, BUDGET), Answer::Unknown);
+    }
+}
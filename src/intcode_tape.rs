@@ -0,0 +1,340 @@
+use crate::intcode::{ComputerState, IntcodeComputer, Observer, ProgramImage};
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, Write},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+const HEADER: &str = "# intcode tape";
+
+//`instruction` is how many instructions had completed before the one that did the io
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TapeEvent {
+    Input { instruction: u64, value: i64 },
+    Output { instruction: u64, value: i64 },
+}
+
+impl fmt::Display for TapeEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TapeEvent::Input { instruction, value } => write!(f, "in {} {}", instruction, value),
+            TapeEvent::Output { instruction, value } => write!(f, "out {} {}", instruction, value),
+        }
+    }
+}
+
+impl FromStr for TapeEvent {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let number = |s: &str| s.parse::<i64>().map_err(|_| format!("Bad number: {}", s));
+        match parts.as_slice() {
+            ["in", instruction, value] => Ok(TapeEvent::Input {
+                instruction: number(instruction)? as u64,
+                value: number(value)?,
+            }),
+            ["out", instruction, value] => Ok(TapeEvent::Output {
+                instruction: number(instruction)? as u64,
+                value: number(value)?,
+            }),
+            _ => Err(format!("Bad tape line: {}", line)),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Tape {
+    events: Vec<TapeEvent>,
+}
+
+impl Tape {
+    pub fn events(&self) -> &[TapeEvent] {
+        &self.events
+    }
+
+    pub fn inputs(&self) -> Vec<i64> {
+        self.events
+            .iter()
+            .filter_map(|e| match e {
+                TapeEvent::Input { value, .. } => Some(*value),
+                TapeEvent::Output { .. } => None,
+            })
+            .collect()
+    }
+
+    pub fn outputs(&self) -> Vec<i64> {
+        self.events
+            .iter()
+            .filter_map(|e| match e {
+                TapeEvent::Output { value, .. } => Some(*value),
+                TapeEvent::Input { .. } => None,
+            })
+            .collect()
+    }
+
+    pub fn save(&self, file_name: &str) -> io::Result<()> {
+        let mut file = File::create(file_name)?;
+        write!(file, "{}", self)
+    }
+
+    pub fn load(file_name: &str) -> io::Result<Tape> {
+        fs::read_to_string(file_name)?
+            .parse()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+impl fmt::Display for Tape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        for event in &self.events {
+            writeln!(f, "{}", event)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Tape {
+    type Err = String;
+
+    fn from_str(tape: &str) -> Result<Self, Self::Err> {
+        let events = tape
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(str::parse)
+            .collect::<Result<_, _>>()?;
+        Ok(Tape { events })
+    }
+}
+
+#[derive(Debug, Default)]
+struct TapeRecorder {
+    tape: Tape,
+    completed: u64,
+    //an instruction has started but may still wait or fault instead of finishing
+    started: bool,
+}
+
+impl Observer for TapeRecorder {
+    fn before_execute(&mut self, _instruction_pointer: u64, _instruction: i64) {
+        if self.started {
+            self.completed += 1;
+        }
+        self.started = true;
+    }
+
+    fn input_consumed(&mut self, value: i64) {
+        self.tape.events.push(TapeEvent::Input {
+            instruction: self.completed,
+            value,
+        })
+    }
+
+    fn output_produced(&mut self, value: i64) {
+        self.tape.events.push(TapeEvent::Output {
+            instruction: self.completed,
+            value,
+        })
+    }
+
+    fn state_changed(&mut self, _old_state: &ComputerState, new_state: &ComputerState) {
+        if let ComputerState::Waiting | ComputerState::Faulted(_) = new_state {
+            self.started = false;
+        }
+    }
+}
+
+//replaces any observer on the computer until it is stopped
+pub struct Recording {
+    recorder: Arc<Mutex<TapeRecorder>>,
+}
+
+impl Recording {
+    pub fn start(computer: &mut IntcodeComputer) -> Recording {
+        let recorder = Arc::new(Mutex::new(TapeRecorder::default()));
+        computer.attach_observer(Box::new(Arc::clone(&recorder)));
+        Recording { recorder }
+    }
+
+    pub fn tape(&self) -> Tape {
+        self.recorder
+            .lock()
+            .expect("Recorder lock poisoned")
+            .tape
+            .clone()
+    }
+
+    pub fn stop(self, computer: &mut IntcodeComputer) -> Tape {
+        computer.detach_observer();
+        self.tape()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayError {
+    pub index: usize,
+    pub expected: Option<TapeEvent>,
+    pub actual: Option<TapeEvent>,
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let show = |e: &Option<TapeEvent>| e.map_or("nothing".to_string(), |e| e.to_string());
+        write!(
+            f,
+            "Replay diverged at event {}: expected {}, got {}",
+            self.index,
+            show(&self.expected),
+            show(&self.actual)
+        )
+    }
+}
+
+//feeds the recorded inputs back in and checks every event happens again on the same instruction,
+//the image's relative base is restored so a saved image replays like it was recorded
+pub fn replay(image: &ProgramImage, tape: &Tape) -> Result<IntcodeComputer, ReplayError> {
+    let mut computer = IntcodeComputer::from_image(image);
+    let recording = Recording::start(&mut computer);
+    let mut input: Vec<i64> = tape.inputs().into_iter().rev().collect();
+    computer.run(&mut input);
+    let replayed = recording.stop(&mut computer);
+
+    let length = tape.events.len().max(replayed.events.len());
+    for index in 0..length {
+        let expected = tape.events.get(index).copied();
+        let actual = replayed.events.get(index).copied();
+        if expected != actual {
+            return Err(ReplayError {
+                index,
+                expected,
+                actual,
+            });
+        }
+    }
+    Ok(computer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //adds pairs of inputs until it reads a zero
+    const ADDER: [i64; 19] = [
+        3, 17, 1006, 17, 16, 3, 18, 1, 17, 18, 18, 4, 18, 1105, 1, 0, 99, 0, 0,
+    ];
+
+    #[test]
+    fn test_record() {
+        let mut computer = IntcodeComputer::new(&ADDER);
+        let recording = Recording::start(&mut computer);
+        //typed in one at a time, like an interactive session
+        for value in &[3, 4, 10] {
+            computer.run(&mut vec![*value]);
+        }
+        computer.run(&mut vec![-1]);
+        computer.run(&mut vec![0]);
+        let tape = recording.stop(&mut computer);
+
+        assert_eq!(computer.state(), &ComputerState::Halted);
+        assert_eq!(
+            tape.events(),
+            &[
+                TapeEvent::Input {
+                    instruction: 0,
+                    value: 3
+                },
+                TapeEvent::Input {
+                    instruction: 2,
+                    value: 4
+                },
+                TapeEvent::Output {
+                    instruction: 4,
+                    value: 7
+                },
+                TapeEvent::Input {
+                    instruction: 6,
+                    value: 10
+                },
+                TapeEvent::Input {
+                    instruction: 8,
+                    value: -1
+                },
+                TapeEvent::Output {
+                    instruction: 10,
+                    value: 9
+                },
+                TapeEvent::Input {
+                    instruction: 12,
+                    value: 0
+                },
+            ]
+        );
+        assert_eq!(tape.outputs(), vec![7, 9]);
+        assert_eq!(tape.to_string().parse::<Tape>(), Ok(tape));
+    }
+
+    fn image(program: &[i64]) -> ProgramImage {
+        ProgramImage {
+            program: program.to_vec(),
+            relative_base: None,
+        }
+    }
+
+    #[test]
+    fn test_replay() {
+        let mut computer = IntcodeComputer::new(&ADDER);
+        let recording = Recording::start(&mut computer);
+        computer.run(&mut vec![4, 3]);
+        computer.run(&mut vec![0]);
+        let tape = recording.stop(&mut computer);
+
+        let replayed = replay(&image(&ADDER), &tape).unwrap();
+        assert_eq!(replayed.output(), &vec![7]);
+
+        //a bug report against a changed program
+        let mut patched = ADDER.to_vec();
+        patched[7] = 2;
+        let err = replay(&image(&patched), &tape)
+            .err()
+            .expect("The replay should diverge");
+        assert_eq!(err.index, 2);
+        assert_eq!(
+            err.to_string(),
+            "Replay diverged at event 2: expected out 4 7, got out 4 12"
+        );
+
+        let tape: Tape = "# intcode tape\nin 0 3\nout 4 7\n".parse().unwrap();
+        let err = replay(&image(&ADDER), &tape)
+            .err()
+            .expect("The replay should diverge");
+        assert_eq!(err.actual, None);
+        assert!("in 0".parse::<Tape>().is_err());
+    }
+
+    #[test]
+    fn test_replay_relative_base() {
+        //prints the cell after the relative base
+        let saved = ProgramImage {
+            program: vec![204, 1, 99, 42],
+            relative_base: Some(2),
+        };
+        let mut computer = IntcodeComputer::from_image(&saved);
+        let recording = Recording::start(&mut computer);
+        computer.run(&mut vec![]);
+        let tape = recording.stop(&mut computer);
+        assert_eq!(computer.output(), &vec![42]);
+
+        assert_eq!(replay(&saved, &tape).unwrap().output(), &vec![42]);
+        let err = replay(&image(&saved.program), &tape)
+            .err()
+            .expect("The replay should diverge");
+        assert_eq!(
+            err.to_string(),
+            "Replay diverged at event 0: expected out 0 42, got out 0 1"
+        );
+    }
+}
@@ -0,0 +1,643 @@
+use crate::intcode::{decode_instruction, Instruction, OpCode, ParameterMode};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    fmt::Write,
+    fs, io,
+};
+
+#[derive(Debug, PartialEq)]
+pub enum TranspileError {
+    SelfModifying { instruction: u64, address: u64 },
+    ImmediateWrite(u64),
+}
+
+impl fmt::Display for TranspileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranspileError::SelfModifying {
+                instruction,
+                address,
+            } => write!(
+                f,
+                "The instruction at {} writes into the code at {}",
+                instruction, address
+            )?,
+            TranspileError::ImmediateWrite(instruction) => write!(
+                f,
+                "The instruction at {} writes through an immediate parameter",
+                instruction
+            )?,
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub(crate) enum Decoded {
+    Valid(Instruction),
+    Invalid(i64),
+}
+
+impl Decoded {
+    pub(crate) fn length(&self) -> u64 {
+        match self {
+            Decoded::Valid(instruction) => instruction.length(),
+            Decoded::Invalid(_) => 1,
+        }
+    }
+
+    fn ends_block(&self) -> bool {
+        match self {
+            Decoded::Valid(instruction) => matches!(
+                instruction.opcode(),
+                OpCode::JumpIfTrue | OpCode::JumpIfFalse | OpCode::Halt
+            ),
+            Decoded::Invalid(_) => true,
+        }
+    }
+}
+
+pub fn transpile(program: &[i64]) -> Result<String, TranspileError> {
+    let (decoded, leaders) = discover_code(program);
+    let mut is_code = vec![false; program.len()];
+    for (address, instruction) in &decoded {
+        for cell in *address..address + instruction.length() {
+            if let Some(c) = is_code.get_mut(cell as usize) {
+                *c = true
+            }
+        }
+    }
+    for (address, instruction) in &decoded {
+        if let Decoded::Valid(instruction) = instruction {
+            check_write(*address, instruction, &is_code)?;
+        }
+    }
+
+    let mut source = String::new();
+    writeln!(
+        source,
+        "// Generated from an Intcode image by intcode_transpiler, do not edit."
+    )
+    .unwrap();
+    writeln!(source).unwrap();
+    writeln!(
+        source,
+        "const IMAGE: [i64; {}] = {:?};",
+        program.len(),
+        program
+    )
+    .unwrap();
+    writeln!(
+        source,
+        "const IS_CODE: [bool; {}] = {:?};",
+        is_code.len(),
+        is_code
+    )
+    .unwrap();
+    source.push_str(
+        "
+pub fn run(input: &mut dyn FnMut() -> i64, output: &mut dyn FnMut(i64)) {
+    let mut memory = IMAGE.to_vec();
+    let mut relative_base: i64 = 0;
+    let mut block: i64 = 0;
+    loop {
+        match block {
+",
+    );
+    for leader in &leaders {
+        write_block(&mut source, *leader, &decoded, &leaders);
+    }
+    source.push_str(
+        "            //a computed jump into code that was never decoded as a block
+            _ => return interpret(memory, relative_base, block, input, output),
+        }
+    }
+}
+
+pub fn run_with_input(input: &mut Vec<i64>) -> Vec<i64> {
+    let mut output = Vec::new();
+    run(
+        &mut || input.pop().expect(\"Ran out of input\"),
+        &mut |value| output.push(value),
+    );
+    output
+}
+
+fn location(address: i64) -> usize {
+    if address < 0 {
+        panic!(\"Negative locations should not occur\")
+    }
+    address as usize
+}
+
+fn read(memory: &[i64], address: i64) -> i64 {
+    memory.get(location(address)).copied().unwrap_or(0)
+}
+
+fn write(memory: &mut Vec<i64>, address: i64, value: i64) {
+    if IS_CODE.get(location(address)).copied().unwrap_or(false) {
+        panic!(\"Write into the code at {}, self-modifying programs are not supported\", address)
+    }
+    store(memory, address, value)
+}
+
+fn store(memory: &mut Vec<i64>, address: i64, value: i64) {
+    let address = location(address);
+    if address >= memory.len() {
+        memory.resize(address + 1, 0)
+    }
+    memory[address] = value;
+}
+
+//runs the rest of the program one instruction at a time, the blocks are never returned to
+//so the code it runs may also be written to
+fn interpret(
+    mut memory: Vec<i64>,
+    mut relative_base: i64,
+    mut ip: i64,
+    input: &mut dyn FnMut() -> i64,
+    output: &mut dyn FnMut(i64),
+) {
+    loop {
+        let instruction = read(&memory, ip);
+        let mode = |n: u32| instruction / 10_i64.pow(n + 1) % 10;
+        let address = |memory: &[i64], n: u32| match mode(n) {
+            0 => read(memory, ip + n as i64),
+            1 => ip + n as i64,
+            2 => relative_base + read(memory, ip + n as i64),
+            other => panic!(\"Unknown parameter mode {} at {}\", other, ip),
+        };
+        let value = |memory: &[i64], n: u32| read(memory, address(memory, n));
+        match instruction % 100 {
+            opcode @ (1 | 2 | 7 | 8) => {
+                let (first, second) = (value(&memory, 1), value(&memory, 2));
+                let result = match opcode {
+                    1 => first + second,
+                    2 => first * second,
+                    7 => (first < second) as i64,
+                    _ => (first == second) as i64,
+                };
+                //an immediate answer has nowhere to go
+                if mode(3) != 1 {
+                    let answer = address(&memory, 3);
+                    store(&mut memory, answer, result);
+                }
+                ip += 4;
+            }
+            3 => {
+                //an immediate parameter still names the cell
+                let target = match mode(1) {
+                    1 => read(&memory, ip + 1),
+                    _ => address(&memory, 1),
+                };
+                let value = input();
+                store(&mut memory, target, value);
+                ip += 2;
+            }
+            4 => {
+                output(value(&memory, 1));
+                ip += 2;
+            }
+            opcode @ (5 | 6) => {
+                if (value(&memory, 1) != 0) == (opcode == 5) {
+                    ip = value(&memory, 2);
+                } else {
+                    ip += 3;
+                }
+            }
+            9 => {
+                relative_base = (relative_base + value(&memory, 1)).max(0);
+                ip += 2;
+            }
+            99 => return,
+            _ => panic!(\"Unknown opcode {} at {}\", instruction, ip),
+        }
+    }
+}
+",
+    );
+    Ok(source)
+}
+
+pub fn transpile_file(program_file: &str, rust_file: &str) -> io::Result<()> {
+    let program = crate::intcode::load_program_input(program_file)?;
+    match transpile(&program) {
+        Ok(source) => fs::write(rust_file, source),
+        Err(err) => Err(io::Error::new(io::ErrorKind::InvalidData, err.to_string())),
+    }
+}
+
+#[derive(Default, Clone)]
+struct Discovery {
+    decoded: BTreeMap<u64, Decoded>,
+    leaders: BTreeSet<u64>,
+    pointers: Vec<u64>,
+}
+
+impl Discovery {
+    fn explore(&mut self, program: &[i64], start: u64) {
+        let mut to_visit = vec![start];
+        self.leaders.insert(start);
+        while let Some(address) = to_visit.pop() {
+            if self.decoded.contains_key(&address) {
+                continue;
+            }
+            let instruction = match decode_instruction(program, address) {
+                Some(instruction) => instruction,
+                None => {
+                    let value = program.get(address as usize).copied().unwrap_or(0);
+                    self.decoded.insert(address, Decoded::Invalid(value));
+                    continue;
+                }
+            };
+            if let Some(pointer) = stored_constant(&instruction) {
+                if pointer >= 0 && decode_instruction(program, pointer as u64).is_some() {
+                    self.pointers.push(pointer as u64);
+                }
+            }
+            let next = address + instruction.length();
+            match instruction.opcode() {
+                OpCode::Halt => (),
+                OpCode::JumpIfTrue | OpCode::JumpIfFalse => {
+                    let (condition_mode, condition) = instruction.parameters()[0];
+                    let (target_mode, target) = instruction.parameters()[1];
+                    let is_constant = condition_mode == ParameterMode::Immediate;
+                    let jumps = (condition != 0) == (instruction.opcode() == OpCode::JumpIfTrue);
+                    match target_mode {
+                        _ if is_constant && !jumps => (),
+                        ParameterMode::Immediate if target >= 0 => {
+                            self.leaders.insert(target as u64);
+                            to_visit.push(target as u64);
+                        }
+                        //whatever the image holds there is a candidate, like a stored constant
+                        ParameterMode::Position if target >= 0 => {
+                            if let Some(&pointer) = program.get(target as usize) {
+                                if pointer >= 0
+                                    && decode_instruction(program, pointer as u64).is_some()
+                                {
+                                    self.pointers.push(pointer as u64);
+                                }
+                            }
+                        }
+                        _ => (),
+                    }
+                    if !(is_constant && jumps) {
+                        self.leaders.insert(next);
+                        to_visit.push(next);
+                    }
+                }
+                _ => to_visit.push(next),
+            }
+            self.decoded.insert(address, Decoded::Valid(instruction));
+        }
+    }
+
+    fn overlaps(&self, address: u64, length: u64) -> bool {
+        self.decoded
+            .range(address.saturating_sub(3)..address + length)
+            .any(|(start, instruction)| start + instruction.length() > address)
+    }
+
+    fn extends_cleanly(&self, original: &Discovery) -> bool {
+        self.decoded.iter().all(|(address, instruction)| {
+            original.decoded.contains_key(address)
+                || match instruction {
+                    Decoded::Valid(instruction) => {
+                        !original.overlaps(*address, instruction.length())
+                    }
+                    Decoded::Invalid(_) => false,
+                }
+        })
+    }
+}
+
+pub(crate) fn discover_code(program: &[i64]) -> (BTreeMap<u64, Decoded>, BTreeSet<u64>) {
+    let mut code = Discovery::default();
+    code.explore(program, 0);
+    //return addresses and function pointers are stored with a move, a jump through one
+    //can only land on a block if the code it points at was decoded too
+    while let Some(pointer) = code.pointers.pop() {
+        if code.decoded.contains_key(&pointer) {
+            code.leaders.insert(pointer);
+            continue;
+        }
+        let mut trial = code.clone();
+        trial.explore(program, pointer);
+        if trial.extends_cleanly(&code) {
+            code = trial
+        }
+    }
+    (code.decoded, code.leaders)
+}
+
+pub(crate) fn stored_constant(instruction: &Instruction) -> Option<i64> {
+    let parameters = instruction.parameters();
+    match (
+        instruction.opcode(),
+        parameters.first()?,
+        parameters.get(1)?,
+    ) {
+        (OpCode::Add, (ParameterMode::Immediate, x), (ParameterMode::Immediate, 0))
+        | (OpCode::Add, (ParameterMode::Immediate, 0), (ParameterMode::Immediate, x))
+        | (OpCode::Multiply, (ParameterMode::Immediate, x), (ParameterMode::Immediate, 1))
+        | (OpCode::Multiply, (ParameterMode::Immediate, 1), (ParameterMode::Immediate, x)) => {
+            Some(*x)
+        }
+        _ => None,
+    }
+}
+
+pub(crate) fn check_write(
+    address: u64,
+    instruction: &Instruction,
+    is_code: &[bool],
+) -> Result<(), TranspileError> {
+    let write_parameter = match instruction.opcode() {
+        OpCode::Add | OpCode::Multiply | OpCode::LessThan | OpCode::Equals => {
+            instruction.parameters()[2]
+        }
+        OpCode::Input => match instruction.parameters()[0] {
+            (ParameterMode::Immediate, target) => (ParameterMode::Position, target),
+            parameter => parameter,
+        },
+        _ => return Ok(()),
+    };
+    match write_parameter {
+        (ParameterMode::Immediate, _) => Err(TranspileError::ImmediateWrite(address)),
+        (ParameterMode::Position, target)
+            if target >= 0 && is_code.get(target as usize).copied().unwrap_or(false) =>
+        {
+            Err(TranspileError::SelfModifying {
+                instruction: address,
+                address: target as u64,
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+fn write_block(
+    source: &mut String,
+    leader: u64,
+    decoded: &BTreeMap<u64, Decoded>,
+    leaders: &BTreeSet<u64>,
+) {
+    writeln!(source, "            {} => {{", leader).unwrap();
+    let mut address = leader;
+    loop {
+        let instruction = match decoded.get(&address) {
+            Some(instruction) => instruction,
+            None => {
+                writeln!(source, "                block = {};", address).unwrap();
+                break;
+            }
+        };
+        match instruction {
+            Decoded::Valid(instruction) => write_instruction(source, address, instruction),
+            Decoded::Invalid(value) => writeln!(
+                source,
+                "                panic!(\"Unknown opcode {} at {}\");",
+                value, address
+            )
+            .unwrap(),
+        }
+        let next = address + instruction.length();
+        if instruction.ends_block() {
+            break;
+        }
+        if leaders.contains(&next) {
+            writeln!(source, "                block = {};", next).unwrap();
+            break;
+        }
+        address = next;
+    }
+    writeln!(source, "            }}").unwrap();
+}
+
+fn write_instruction(source: &mut String, address: u64, instruction: &Instruction) {
+    let parameters = instruction.parameters();
+    let indent = "                ";
+    match instruction.opcode() {
+        OpCode::Add | OpCode::Multiply | OpCode::LessThan | OpCode::Equals => {
+            let first = read_expression(parameters[0]);
+            let second = read_expression(parameters[1]);
+            let value = match instruction.opcode() {
+                OpCode::Add => format!("{} + {}", first, second),
+                OpCode::Multiply => format!("{} * {}", first, second),
+                OpCode::LessThan => format!("({} < {}) as i64", first, second),
+                _ => format!("({} == {}) as i64", first, second),
+            };
+            writeln!(source, "{}let value = {};", indent, value).unwrap();
+            writeln!(
+                source,
+                "{}write(&mut memory, {}, value);",
+                indent,
+                address_expression(parameters[2])
+            )
+            .unwrap();
+        }
+        OpCode::Input => {
+            writeln!(source, "{}let value = input();", indent).unwrap();
+            writeln!(
+                source,
+                "{}write(&mut memory, {}, value);",
+                indent,
+                address_expression(parameters[0])
+            )
+            .unwrap();
+        }
+        OpCode::Output => writeln!(
+            source,
+            "{}output({});",
+            indent,
+            read_expression(parameters[0])
+        )
+        .unwrap(),
+        OpCode::JumpIfTrue | OpCode::JumpIfFalse => {
+            let comparison = if instruction.opcode() == OpCode::JumpIfTrue {
+                "!="
+            } else {
+                "=="
+            };
+            writeln!(
+                source,
+                "{}if {} {} 0 {{",
+                indent,
+                read_expression(parameters[0]),
+                comparison
+            )
+            .unwrap();
+            writeln!(
+                source,
+                "{}    block = {};",
+                indent,
+                read_expression(parameters[1])
+            )
+            .unwrap();
+            writeln!(source, "{}    continue;", indent).unwrap();
+            writeln!(source, "{}}}", indent).unwrap();
+            writeln!(source, "{}block = {};", indent, address + 3).unwrap();
+        }
+        OpCode::AdjustRelativeBaseOffset => writeln!(
+            source,
+            "{}relative_base = (relative_base + {}).max(0);",
+            indent,
+            read_expression(parameters[0])
+        )
+        .unwrap(),
+        OpCode::Halt => writeln!(source, "{}return;", indent).unwrap(),
+    }
+}
+
+fn read_expression(parameter: (ParameterMode, i64)) -> String {
+    match parameter {
+        (ParameterMode::Immediate, value) if value < 0 => format!("({})", value),
+        (ParameterMode::Immediate, value) => value.to_string(),
+        parameter => format!("read(&memory, {})", address_expression(parameter)),
+    }
+}
+
+fn address_expression(parameter: (ParameterMode, i64)) -> String {
+    match parameter {
+        (ParameterMode::Relative, value) if value < 0 => format!("relative_base - {}", -value),
+        (ParameterMode::Relative, value) => format!("relative_base + {}", value),
+        (_, value) => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transpile() {
+        let prog = vec![
+            3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0,
+            0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
+            20, 1105, 1, 46, 98, 99,
+        ];
+        let source = transpile(&prog).unwrap();
+        for leader in &[0, 9, 16, 22, 31, 36, 46] {
+            assert!(source.contains(&format!("            {} => {{", leader)));
+        }
+        assert!(!source.contains("            19 => {"));
+        assert!(source.contains("let value = (read(&memory, 21) == 8) as i64;"));
+        assert!(source.contains("output(999);"));
+    }
+
+    #[test]
+    fn test_transpile_relative() {
+        let quine = vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        let source = transpile(&quine).unwrap();
+        assert!(source.contains("relative_base = (relative_base + 1).max(0);"));
+        assert!(source.contains("output(read(&memory, relative_base - 1));"));
+        assert!(source.contains("            15 => {"));
+    }
+
+    #[test]
+    fn test_transpile_stored_pointer() {
+        let call_through_pointer = vec![1101, 8, 0, 20, 6, 22, 20, 99, 104, 5, 99];
+        let source = transpile(&call_through_pointer).unwrap();
+        assert!(source.contains("            8 => {"));
+        assert!(source.contains("block = read(&memory, 20);"));
+    }
+
+    //compiles every day 5 and day 9 vector program with rustc and checks each case
+    //against the interpreter. programs that write into their own code are refused
+    #[test]
+    fn test_transpiled_vectors() {
+        use crate::intcode::IntcodeComputer;
+        use std::process::Command;
+
+        let directory = std::env::temp_dir().join(format!("transpiled-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let mut refused = Vec::new();
+        for (path, bundle) in crate::intcode_vectors::load_path("test_vectors").unwrap() {
+            let name = std::path::Path::new(&path)
+                .file_stem()
+                .unwrap()
+                .to_string_lossy()
+                .to_string();
+            if !name.starts_with("day05") && !name.starts_with("day09") {
+                continue;
+            }
+            let mut source = match transpile(&bundle.program) {
+                Ok(source) => source,
+                Err(TranspileError::SelfModifying { .. }) => {
+                    refused.push(name);
+                    continue;
+                }
+                Err(err) => panic!("{}: {}", name, err),
+            };
+            source.push_str(
+                "
+fn main() {
+    let mut input: Vec<i64> = std::env::args().skip(1).map(|a| a.parse().unwrap()).rev().collect();
+    for value in run_with_input(&mut input) {
+        println!(\"{}\", value);
+    }
+}
+",
+            );
+            let rust_file = directory.join(format!("{}.rs", name));
+            let binary = directory.join(&name);
+            fs::write(&rust_file, source).unwrap();
+            let compiled = Command::new("rustc")
+                .args(["--edition", "2018", "-A", "warnings", "-o"])
+                .arg(&binary)
+                .arg(&rust_file)
+                .status()
+                .unwrap();
+            assert!(compiled.success(), "{} does not compile", name);
+
+            for case in &bundle.cases {
+                let mut computer = IntcodeComputer::new(&bundle.program);
+                computer.run(&mut case.input.iter().rev().copied().collect());
+                let run = Command::new(&binary)
+                    .args(case.input.iter().map(|value| value.to_string()))
+                    .output()
+                    .unwrap();
+                assert!(run.status.success(), "{} {}", name, case.name);
+                let output: Vec<i64> = String::from_utf8(run.stdout)
+                    .unwrap()
+                    .lines()
+                    .map(|line| line.parse().unwrap())
+                    .collect();
+                assert_eq!(&output, computer.output(), "{} {}", name, case.name);
+            }
+        }
+        fs::remove_dir_all(&directory).unwrap();
+        refused.sort();
+        assert_eq!(
+            refused,
+            vec![
+                "day05_equal_to_8_immediate",
+                "day05_io",
+                "day05_jump_immediate",
+                "day05_less_than_8_immediate",
+                "day05_negative_immediate"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_transpile_refuses_self_modifying() {
+        let gravity_assist = vec![1, 0, 0, 0, 99];
+        assert_eq!(
+            transpile(&gravity_assist),
+            Err(TranspileError::SelfModifying {
+                instruction: 0,
+                address: 0
+            })
+        );
+
+        let immediate_write = vec![11101, 1, 1, 5, 99];
+        assert_eq!(
+            transpile(&immediate_write),
+            Err(TranspileError::ImmediateWrite(0))
+        );
+    }
+}
@@ -0,0 +1,356 @@
+use crate::{
+    intcode::{ComputerState, IntcodeComputer},
+    intcode_fuzz::panic_message,
+};
+use std::{
+    fmt, fs, io,
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::Path,
+    str::FromStr,
+};
+
+const HEADER: &str = "# intcode test vectors";
+const EXTENSION: &str = "vectors";
+//a case that runs this long is taken to be stuck in a loop
+const MAX_STEPS: u64 = 10_000_000;
+
+//a case without output or memory lines expects the program to print nothing
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TestCase {
+    pub name: String,
+    pub input: Vec<i64>,
+    pub output: Vec<i64>,
+    pub patches: Vec<(u64, i64)>,
+    pub memory: Vec<(u64, i64)>,
+}
+
+impl TestCase {
+    //a panic fails only this case, the rest of the bundle still runs
+    pub fn run(&self, program: &[i64]) -> Result<(), String> {
+        catching(|| self.check(program))
+    }
+
+    fn check(&self, program: &[i64]) -> Result<(), String> {
+        let mut comp = IntcodeComputer::new(program);
+        for &(address, value) in &self.patches {
+            comp.patch(address, value);
+        }
+        let mut input: Vec<i64> = self.input.iter().rev().copied().collect();
+        let steps = comp.run_for(&mut input, MAX_STEPS);
+        match comp.state() {
+            ComputerState::Halted => {}
+            ComputerState::Running => return Err(format!("Still running after {} steps", steps)),
+            ComputerState::Waiting => return Err("Waiting for more input".to_string()),
+            ComputerState::Paused => return Err("Paused".to_string()),
+            ComputerState::Faulted(fault) => {
+                return Err(format!(
+                    "Fault at {}: {:?}",
+                    fault.instruction_pointer(),
+                    fault.kind()
+                ))
+            }
+        }
+        if comp.output() != &self.output {
+            return Err(format!(
+                "Expected output {:?}, got {:?}",
+                self.output,
+                comp.output()
+            ));
+        }
+        for &(address, value) in &self.memory {
+            let actual = comp.peek(address);
+            if actual != value {
+                return Err(format!("Expected {} at {}, got {}", value, address, actual));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn catching(check: impl FnOnce() -> Result<(), String>) -> Result<(), String> {
+    catch_unwind(AssertUnwindSafe(check))
+        .unwrap_or_else(|payload| Err(format!("Panicked: {}", panic_message(&*payload))))
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Bundle {
+    pub program: Vec<i64>,
+    pub cases: Vec<TestCase>,
+}
+
+impl Bundle {
+    pub fn run(&self) -> BundleReport {
+        BundleReport {
+            results: self
+                .cases
+                .iter()
+                .map(|case| (case.name.clone(), case.run(&self.program)))
+                .collect(),
+        }
+    }
+
+    pub fn save(&self, file_name: &str) -> io::Result<()> {
+        fs::write(file_name, self.to_string())
+    }
+
+    pub fn load(file_name: &str) -> io::Result<Bundle> {
+        fs::read_to_string(file_name)?
+            .parse()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+//every bundle in the directory, sorted by file name
+pub fn load_directory(directory: &str) -> io::Result<Vec<(String, Bundle)>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == EXTENSION) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    files
+        .into_iter()
+        .map(|path| {
+            let name = path.to_string_lossy().to_string();
+            let bundle = Bundle::load(&name)
+                .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", name, err)))?;
+            Ok((name, bundle))
+        })
+        .collect()
+}
+
+//a file is loaded on its own, a directory gives all the bundles in it
+pub fn load_path(path: &str) -> io::Result<Vec<(String, Bundle)>> {
+    if Path::new(path).is_dir() {
+        load_directory(path)
+    } else {
+        Ok(vec![(path.to_string(), Bundle::load(path)?)])
+    }
+}
+
+fn join<T: ToString>(values: &[T], separator: &str) -> String {
+    let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+    values.join(separator)
+}
+
+fn join_cells(cells: &[(u64, i64)]) -> String {
+    let cells: Vec<String> = cells
+        .iter()
+        .map(|(address, value)| format!("{}={}", address, value))
+        .collect();
+    cells.join(", ")
+}
+
+impl fmt::Display for Bundle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "program {}", join(&self.program, ","))?;
+        for case in &self.cases {
+            writeln!(f)?;
+            writeln!(f, "case {}", case.name)?;
+            if !case.input.is_empty() {
+                writeln!(f, "input {}", join(&case.input, ","))?;
+            }
+            if !case.output.is_empty() {
+                writeln!(f, "output {}", join(&case.output, ","))?;
+            }
+            if !case.patches.is_empty() {
+                writeln!(f, "patch {}", join_cells(&case.patches))?;
+            }
+            if !case.memory.is_empty() {
+                writeln!(f, "memory {}", join_cells(&case.memory))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_values(text: &str) -> Result<Vec<i64>, String> {
+    text.split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(|v| v.parse().map_err(|_| format!("Bad value '{}'", v)))
+        .collect()
+}
+
+//address=value pairs separated by commas
+fn parse_cells(text: &str) -> Result<Vec<(u64, i64)>, String> {
+    text.split(',')
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+        .map(|cell| {
+            let mut parts = cell.splitn(2, '=');
+            let address = parts.next().unwrap_or("").trim().parse();
+            let value = parts.next().map(|v| v.trim().parse());
+            match (address, value) {
+                (Ok(address), Some(Ok(value))) => Ok((address, value)),
+                _ => Err(format!("Bad cell '{}', expected address=value", cell)),
+            }
+        })
+        .collect()
+}
+
+//a program line followed by cases, each starting with a `case <name>` line
+//and listing input, output, patch and memory lines. anything after # is a comment
+impl FromStr for Bundle {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut program = None;
+        let mut cases: Vec<TestCase> = Vec::new();
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (keyword, rest) = match line.find(char::is_whitespace) {
+                Some(idx) => (&line[..idx], line[idx..].trim()),
+                None => (line, ""),
+            };
+            let at_line = |message: String| format!("line {}: {}", idx + 1, message);
+            match keyword {
+                "program" if program.is_some() => {
+                    return Err(at_line("The program is given twice".to_string()))
+                }
+                "program" => program = Some(parse_values(rest).map_err(at_line)?),
+                "case" if rest.is_empty() => {
+                    return Err(at_line("A case needs a name".to_string()))
+                }
+                "case" => cases.push(TestCase {
+                    name: rest.to_string(),
+                    ..TestCase::default()
+                }),
+                "input" | "output" | "patch" | "memory" => {
+                    let case = cases
+                        .last_mut()
+                        .ok_or_else(|| at_line(format!("{} comes before any case", keyword)))?;
+                    match keyword {
+                        "input" => case.input.extend(parse_values(rest).map_err(at_line)?),
+                        "output" => case.output.extend(parse_values(rest).map_err(at_line)?),
+                        "patch" => case.patches.extend(parse_cells(rest).map_err(at_line)?),
+                        _ => case.memory.extend(parse_cells(rest).map_err(at_line)?),
+                    }
+                }
+                _ => return Err(at_line(format!("Unknown keyword '{}'", keyword))),
+            }
+        }
+        match program {
+            Some(program) if !program.is_empty() => Ok(Bundle { program, cases }),
+            _ => Err("The bundle has no program".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BundleReport {
+    pub results: Vec<(String, Result<(), String>)>,
+}
+
+impl BundleReport {
+    pub fn passed(&self) -> usize {
+        self.results.iter().filter(|(_, r)| r.is_ok()).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.results.len() - self.passed()
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failed() == 0
+    }
+}
+
+impl fmt::Display for BundleReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, result) in &self.results {
+            match result {
+                Ok(()) => writeln!(f, "PASS {}", name)?,
+                Err(reason) => writeln!(f, "FAIL {}: {}", name, reason)?,
+            }
+        }
+        write!(f, "{} passed, {} failed", self.passed(), self.failed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_display() {
+        let text = "
+            # adds the patched cells
+            program 1,5,6,0,99,0,0
+            case sum
+            patch 5=2, 6=3
+            memory 0=5   # the result
+
+            case zeros
+            memory 0=0
+        ";
+        let bundle: Bundle = text.parse().unwrap();
+        assert_eq!(bundle.program, vec![1, 5, 6, 0, 99, 0, 0]);
+        assert_eq!(bundle.cases.len(), 2);
+        assert_eq!(bundle.cases[0].patches, vec![(5, 2), (6, 3)]);
+        assert_eq!(bundle.cases[1].memory, vec![(0, 0)]);
+        assert_eq!(bundle.to_string().parse::<Bundle>().unwrap(), bundle);
+        assert!(bundle.run().is_success());
+
+        let error = |text: &str| text.parse::<Bundle>().unwrap_err();
+        assert_eq!(error("input 1"), "line 1: input comes before any case");
+        assert_eq!(error("program 99\ncase"), "line 2: A case needs a name");
+        assert_eq!(
+            error("program 99\ncase a\nmemory 4"),
+            "line 3: Bad cell '4', expected address=value"
+        );
+        assert_eq!(error("program 9x"), "line 1: Bad value '9x'");
+        assert_eq!(error("# nothing"), "The bundle has no program");
+    }
+
+    #[test]
+    fn test_report() {
+        let bundle: Bundle = "program 3,0,4,0,99
+            case echo
+            input 7
+            output 7
+            case wrong output
+            input 7
+            output 8
+            case no input
+            output 7
+            case wrong memory
+            input 7
+            output 7
+            memory 0=8"
+            .parse()
+            .unwrap();
+        let report = bundle.run();
+        assert_eq!(report.passed(), 1);
+        assert_eq!(
+            report.to_string(),
+            "PASS echo\n\
+             FAIL wrong output: Expected output [8], got [7]\n\
+             FAIL no input: Waiting for more input\n\
+             FAIL wrong memory: Expected 8 at 0, got 7\n\
+             1 passed, 3 failed"
+        );
+    }
+
+    #[test]
+    fn test_panicking_case() {
+        assert_eq!(catching(|| Ok(())), Ok(()));
+        assert_eq!(
+            catching(|| panic!("Unknown opcode {}", 0)),
+            Err("Panicked: Unknown opcode 0".to_string())
+        );
+        let report = BundleReport {
+            results: vec![("bad".to_string(), catching(|| panic!("out of cells")))],
+        };
+        assert_eq!(
+            report.to_string(),
+            "FAIL bad: Panicked: out of cells\n0 passed, 1 failed"
+        );
+    }
+}
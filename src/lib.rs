@@ -0,0 +1,31 @@
+pub mod amplifier;
+pub mod diagnostic_program;
+pub mod extra_secure_container;
+pub mod feedback_amplifier;
+pub mod intcode;
+pub mod intcode_asm;
+pub mod intcode_batch;
+pub mod intcode_compiler;
+pub mod intcode_conformance;
+pub mod intcode_console;
+pub mod intcode_dashboard;
+pub mod intcode_dump;
+pub mod intcode_fuzz;
+pub mod intcode_gdb;
+pub mod intcode_minimize;
+pub mod intcode_network;
+pub mod intcode_optimizer;
+pub mod intcode_records;
+pub mod intcode_scheduler;
+pub mod intcode_symbolic;
+pub mod intcode_tape;
+pub mod intcode_transpiler;
+pub mod intcode_vectors;
+pub mod manhatten;
+pub mod monitoring_station;
+pub mod orbit;
+pub mod program;
+pub mod rocket_equation;
+pub mod secure_container;
+pub mod signal_delay;
+pub mod space_image;
@@ -1,27 +1,15 @@
-mod amplifier;
-mod diagnostic_program;
-mod extra_secure_container;
-mod feedback_amplifier;
-mod intcode;
-mod manhatten;
-mod monitoring_station;
-mod orbit;
-mod program;
-mod rocket_equation;
-mod secure_container;
-mod signal_delay;
-mod space_image;
-
-use crate::{
+use advent_of_code::{
     amplifier::find_best_phase_setting_sequence,
     diagnostic_program::process_instructions,
+    extra_secure_container,
     feedback_amplifier::find_best_feedback_phase_setting_sequence,
-    intcode::IntcodeComputer,
-    manhatten::load_path_directions_input,
+    intcode::{self, IntcodeComputer},
+    manhatten::{self, load_path_directions_input},
     monitoring_station::{load_asteroid_input, AsteroidMap},
     orbit::{load_orbit_input, process_orbit_map},
     program::{find_noun_and_verb, load_program_input, restore_gravity_assist},
     rocket_equation::{load_mass_input, total_fuel},
+    secure_container, signal_delay,
     space_image::{
         create_final_image, create_layers_from_image_data, find_layer_with_lowest_nmb,
         load_image_data,
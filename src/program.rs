@@ -1,4 +1,9 @@
+use crate::{
+    intcode::{InstructionSet, IntcodeComputer},
+    intcode_symbolic::{Answer, Budget, Goal, Solver},
+};
 use std::{
+    convert::TryFrom,
     fs::File,
     io,
     io::{BufRead, BufReader},
@@ -6,98 +11,77 @@ use std::{
 
 pub fn find_noun_and_verb(program: &[i32]) -> Option<(i32, i32)> {
     let desired_output = 19_690_720;
-    let original_instructions = Vec::from(program);
-    for noun in 0..99 {
-        for verb in 0..99 {
-            let processed_instructions =
-                process_instructions(Some(noun), Some(verb), &original_instructions);
-            if processed_instructions[0] == desired_output {
-                return Some((noun, verb));
-            }
-        }
+    let wide: Vec<i64> = program.iter().map(|&v| v.into()).collect();
+    let mut solver = Solver::new(&wide);
+    solver.symbolic_cell(1, 0..=99);
+    solver.symbolic_cell(2, 0..=99);
+    let goal = Goal::Memory {
+        address: 0,
+        value: desired_output,
+    };
+    //every noun and verb, each run of the day 2 program is a few dozen steps
+    let budget = Budget {
+        candidates: 10_000,
+        steps: 10_000,
+    };
+    let solution = match solver.solve(goal, budget) {
+        Answer::Solved(solution) => solution,
+        Answer::Unsolvable | Answer::Unknown => return None,
+    };
+    let (noun, verb) = (solution.values[0] as i32, solution.values[1] as i32);
+    //the solver knows every instruction, the answer has to hold with day 2's alone
+    let memory = run_gravity_assist(Some(noun), Some(verb), program).ok()?;
+    if i64::from(memory[0]) == desired_output {
+        Some((noun, verb))
+    } else {
+        None
     }
-    None
 }
 
 pub fn restore_gravity_assist(program: &[i32]) -> Vec<i32> {
-    let memory = Vec::from(program);
-    process_instructions(Some(12), Some(2), &memory)
+    process_instructions(Some(12), Some(2), program)
 }
 
-const INSTRUCTION_LENGTH: usize = 4;
-
-enum OpCode {
-    Add,
-    Multiply,
-    Halt,
+//panics on anything day 2 does not define
+pub(crate) fn process_instructions(
+    noun: Option<i32>,
+    verb: Option<i32>,
+    instructions: &[i32],
+) -> Vec<i32> {
+    run_gravity_assist(noun, verb, instructions).unwrap_or_else(|err| panic!("{}", err))
 }
 
-impl From<i32> for OpCode {
-    fn from(opcode_number: i32) -> Self {
-        match opcode_number {
-            1 => OpCode::Add,
-            2 => OpCode::Multiply,
-            99 => OpCode::Halt,
-            _ => panic!("Unknown opcode"),
-        }
-    }
-}
-
-fn process_instructions(noun: Option<i32>, verb: Option<i32>, instructions: &[i32]) -> Vec<i32> {
-    let mut processed_instructions = Vec::from(instructions);
+//the program's own cells once it halts, anything written past them is dropped
+fn run_gravity_assist(
+    noun: Option<i32>,
+    verb: Option<i32>,
+    instructions: &[i32],
+) -> Result<Vec<i32>, String> {
+    let program: Vec<i64> = instructions.iter().map(|&v| v.into()).collect();
+    let mut computer = IntcodeComputer::new(&program);
+    computer.restrict(InstructionSet::Day2);
     if let Some(n) = noun {
-        processed_instructions[1] = n
+        computer.patch(1, n.into())
     };
     if let Some(v) = verb {
-        processed_instructions[2] = v
+        computer.patch(2, v.into())
     };
-    let mut instruction_pointer = 0;
-    loop {
-        match processed_instructions[instruction_pointer].into() {
-            OpCode::Add => {
-                update_instructions(&mut processed_instructions, instruction_pointer, |x, y| {
-                    x + y
-                })
-            }
-            OpCode::Multiply => {
-                update_instructions(&mut processed_instructions, instruction_pointer, |x, y| {
-                    x * y
-                })
-            }
-            OpCode::Halt => break,
-        }
-        instruction_pointer += INSTRUCTION_LENGTH
-    }
-    processed_instructions
-}
-
-fn update_instructions(
-    instructions: &mut [i32],
-    instruction_pointer: usize,
-    operation: fn(i32, i32) -> i32,
-) {
-    let positions = determine_positions(instruction_pointer, &instructions);
-    instructions[positions.answer] = operation(
-        instructions[positions.first_nmb],
-        instructions[positions.second_nmb],
-    )
-}
-
-struct Positions {
-    first_nmb: usize,
-    second_nmb: usize,
-    answer: usize,
-}
-
-fn determine_positions(idx: usize, output_opcodes: &[i32]) -> Positions {
-    let first_nmb = output_opcodes[idx + 1] as usize;
-    let second_nmb = output_opcodes[idx + 2] as usize;
-    let answer = output_opcodes[idx + 3] as usize;
-    Positions {
-        first_nmb,
-        second_nmb,
-        answer,
+    //without input or jumps the program always halts or faults
+    computer.run(&mut Vec::new());
+    if let Some(fault) = computer.fault() {
+        return Err(format!(
+            "Fault at {}: {:?}",
+            fault.instruction_pointer(),
+            fault.kind()
+        ));
     }
+    (0..instructions.len() as u64)
+        .map(|address| {
+            let value = computer.peek(address);
+            i32::try_from(value)
+                .map_err(|_| format!("Value {} at {} does not fit in an i32", value, address))
+        })
+        .collect()
 }
 
 pub fn load_program_input(file_name: &str) -> io::Result<Vec<i32>> {
@@ -138,6 +122,29 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_undefined_instructions() {
+        //immediate mode and output only arrived on day 5
+        let result = |program: &[i32]| run_gravity_assist(None, None, program);
+        assert_eq!(
+            result(&[1101, 1, 1, 0, 99]),
+            Err("Fault at 0: UndefinedInstruction(1101)".to_string())
+        );
+        assert_eq!(
+            result(&[1, 0, 0, 0, 4, 0, 99]),
+            Err("Fault at 4: UndefinedInstruction(4)".to_string())
+        );
+        //running off the end meets the zeros after it
+        assert_eq!(
+            result(&[1, 0, 0, 0]),
+            Err("Fault at 4: UndefinedInstruction(0)".to_string())
+        );
+        assert_eq!(
+            result(&[2, 5, 5, 0, 99, 100_000]),
+            Err("Value 10000000000 at 0 does not fit in an i32".to_string())
+        );
+    }
+
     #[test]
     fn test_load_program_input() {
         let correct_program = [1, 0, 0, 3, 1, 1, 2, 3, 0];
@@ -146,4 +153,10 @@ mod tests {
             correct_program
         );
     }
+
+    #[test]
+    fn test_find_noun_and_verb() {
+        let program = load_program_input("program.txt").unwrap();
+        assert_eq!(find_noun_and_verb(&program), Some((69, 79)));
+    }
 }
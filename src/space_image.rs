@@ -6,7 +6,7 @@ use std::{
 };
 
 #[derive(Eq, PartialEq, Debug)]
-pub(crate) struct Layer {
+pub struct Layer {
     data: Vec<Vec<u32>>,
 }
 
@@ -17,7 +17,7 @@ impl Layer {
         }
     }
 
-    pub(crate) fn count_occurences_of(&self, nmb: u32) -> u32 {
+    pub fn count_occurences_of(&self, nmb: u32) -> u32 {
         let flattened = self.data.iter().flatten().copied().collect::<Vec<u32>>();
         match flattened.iter().filter(|x| **x == nmb).count().try_into() {
             Ok(i) => i,
@@ -25,7 +25,7 @@ impl Layer {
         }
     }
 
-    pub(crate) fn data_as_message(&self) -> String {
+    pub fn data_as_message(&self) -> String {
         let mut text = String::new();
 
         for row in &self.data {
@@ -71,7 +71,7 @@ impl From<&u32> for PixelColour {
     }
 }
 
-pub(crate) fn create_layers_from_image_data(data: &[u32], width: u32, height: u32) -> Vec<Layer> {
+pub fn create_layers_from_image_data(data: &[u32], width: u32, height: u32) -> Vec<Layer> {
     let mut layers = Vec::new();
     let mut input_data = Vec::from(data);
     input_data.reverse();
@@ -91,7 +91,7 @@ pub(crate) fn create_layers_from_image_data(data: &[u32], width: u32, height: u3
     layers
 }
 
-pub(crate) fn find_layer_with_lowest_nmb(layers: &[Layer], nmb: u32) -> Option<&Layer> {
+pub fn find_layer_with_lowest_nmb(layers: &[Layer], nmb: u32) -> Option<&Layer> {
     let mut lowest_count = u32::max_value();
     let mut lowest_layer = None;
     for layer in layers {
@@ -106,7 +106,7 @@ pub(crate) fn find_layer_with_lowest_nmb(layers: &[Layer], nmb: u32) -> Option<&
     lowest_layer
 }
 
-pub(crate) fn create_final_image(layers: &[Layer]) -> Layer {
+pub fn create_final_image(layers: &[Layer]) -> Layer {
     let mut final_data: Vec<Vec<u32>> = Vec::new();
 
     for layer in layers {
@@ -130,7 +130,7 @@ pub(crate) fn create_final_image(layers: &[Layer]) -> Layer {
     Layer { data: final_data }
 }
 
-pub(crate) fn load_image_data(file_name: &str) -> io::Result<Vec<u32>> {
+pub fn load_image_data(file_name: &str) -> io::Result<Vec<u32>> {
     let program_input = File::open(file_name)?;
     let mut reader = BufReader::new(program_input);
 